@@ -0,0 +1,44 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use keyboardio_utils::{sort_keycodes, sort_keycodes_branchless, sort_keycodes_stable};
+
+// Boot reports with one released key, the common case, a full report, and a long key list.
+const INPUTS: [(&str, &[u8]); 4] = [
+    ("boot_sparse", &[0x04, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ("boot_released", &[0x00, 0x05, 0x06, 0x00, 0x00, 0x00]),
+    ("boot_full", &[0x04, 0x05, 0x06, 0x07, 0x08, 0x09]),
+    (
+        "list_32",
+        &[
+            0x00, 0x04, 0x00, 0x05, 0x06, 0x00, 0x00, 0x07, 0x08, 0x00, 0x09, 0x00, 0x0a, 0x00,
+            0x00, 0x0b, 0x0c, 0x00, 0x0d, 0x00, 0x00, 0x0e, 0x0f, 0x00, 0x10, 0x00, 0x11, 0x00,
+            0x12, 0x00, 0x00, 0x13,
+        ],
+    ),
+];
+
+fn bench_sort_keycodes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort_keycodes");
+
+    for (name, input) in INPUTS {
+        for (sort_name, sort) in [
+            ("xor_swap", sort_keycodes as fn(&mut [u8])),
+            ("stable", sort_keycodes_stable),
+            ("branchless", sort_keycodes_branchless),
+        ] {
+            group.bench_with_input(BenchmarkId::new(sort_name, name), input, |b, input| {
+                let mut keys = [0u8; 32];
+                let keys = &mut keys[..input.len()];
+
+                b.iter(|| {
+                    keys.copy_from_slice(input);
+                    sort(black_box(keys));
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_sort_keycodes);
+criterion_main!(benches);
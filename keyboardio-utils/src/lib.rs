@@ -9,6 +9,10 @@
 //
 // Does not care about the order of non-zero key slots.
 pub fn sort_keycodes(keys: &mut [u8]) {
+    if keys.is_empty() {
+        return;
+    }
+
     let len = keys.len();
 
     let mut front_idx = 0;
@@ -29,7 +33,60 @@ pub fn sort_keycodes(keys: &mut [u8]) {
     }
 }
 
-pub fn xor_swap(slice: &mut [u8], left_idx: usize, right_idx: usize) {
+// Rearranges the keys list so that the free (= 0x00) slots are at the
+// end of the keys list, keeping the order of the non-zero key slots.
+//
+// So (0x00)(0x01)(0x00)(0x03)(0x02)(0x00) becomes
+//    (0x01)(0x03)(0x02)(0x00)(0x00)(0x00)
+//
+// Keys keep their relative position, e.g. in the order they were pressed, so they don't
+// reshuffle between reports. Unlike `sort_keycodes`, every non-zero slot before the last
+// free slot is moved, instead of only the slots swapped with leading free slots.
+pub fn sort_keycodes_stable(keys: &mut [u8]) {
+    let mut free_idx = 0;
+
+    // Compact non-zero key slots to the front of the list, in order
+    for idx in 0..keys.len() {
+        if keys[idx] != 0 {
+            keys[free_idx] = keys[idx];
+            free_idx += 1;
+        }
+    }
+
+    // Zero-fill the remaining key slots
+    keys[free_idx..].fill(0);
+}
+
+// Rearranges the keys list like `sort_keycodes_stable`, without branching on the key values.
+//
+// So (0x00)(0x01)(0x00)(0x03)(0x02)(0x00) becomes
+//    (0x01)(0x03)(0x02)(0x00)(0x00)(0x00)
+//
+// Every key is written to the free slot, and the free slot only advances past non-zero keys,
+// instead of branching on each key. The zero-fill of the remaining slots still depends on the
+// number of free slots, so the run time is not constant. The benchmark only measures the host,
+// not AVR.
+pub fn sort_keycodes_branchless(keys: &mut [u8]) {
+    let mut free_idx = 0;
+
+    for idx in 0..keys.len() {
+        let key = keys[idx];
+        keys[free_idx] = key;
+        free_idx += (key != 0) as usize;
+    }
+
+    keys[free_idx..].fill(0);
+}
+
+// Swaps the values at the left and right index in place, using XOR instead of a temporary.
+//
+// Returns true if the values were swapped, false if either index is out of range, or the
+// values are equal.
+//
+// The equal-value guard is required for correctness, not just an optimization: XOR-ing a value
+// with itself yields zero, so swapping equal values (or an index with itself) would zero both
+// slots instead of leaving them unchanged.
+pub fn xor_swap(slice: &mut [u8], left_idx: usize, right_idx: usize) -> bool {
     let len = slice.len();
     if left_idx < len && right_idx < len && slice[left_idx] != slice[right_idx] {
         // XOR the right value with the left value to get a mixed value
@@ -39,6 +96,10 @@ pub fn xor_swap(slice: &mut [u8], left_idx: usize, right_idx: usize) {
         slice[right_idx] ^= slice[left_idx];
         // XOR the mixed value with previous left value, leaving the right value
         slice[left_idx] ^= slice[right_idx];
+
+        true
+    } else {
+        false
     }
 }
 
@@ -71,4 +132,70 @@ mod tests {
     
         assert_eq!(unsorted, expected);
     }
+
+    #[test]
+    fn test_sort_keycodes_stable() {
+        // so (0x00)(0x01)(0x00)(0x03)(0x02)(0x00) becomes
+        //    (0x01)(0x03)(0x02)(0x00)(0x00)(0x00)
+        let mut keys = [0x00, 0x01, 0x00, 0x03, 0x02, 0x00];
+        let mut unstable = keys;
+
+        sort_keycodes_stable(&mut keys);
+        sort_keycodes(&mut unstable);
+
+        assert_eq!(keys, [0x01, 0x03, 0x02, 0x00, 0x00, 0x00]);
+        // `sort_keycodes` moves the last key to the first free slot instead
+        assert_eq!(unstable, [0x02, 0x01, 0x03, 0x00, 0x00, 0x00]);
+
+        let mut keys = [0x00, 0x00, 0x00, 0x03, 0x01, 0x02];
+        sort_keycodes_stable(&mut keys);
+        assert_eq!(keys, [0x03, 0x01, 0x02, 0x00, 0x00, 0x00]);
+
+        // already compacted keys are unchanged
+        let mut keys = [0x01, 0x02, 0x03, 0x00, 0x00, 0x00];
+        sort_keycodes_stable(&mut keys);
+        assert_eq!(keys, [0x01, 0x02, 0x03, 0x00, 0x00, 0x00]);
+
+        let mut keys: [u8; 0] = [];
+        sort_keycodes_stable(&mut keys);
+    }
+
+    #[test]
+    fn test_sort_keycodes_branchless() {
+        for keys in [
+            [0x00, 0x01, 0x00, 0x03, 0x02, 0x00],
+            [0x00, 0x00, 0x00, 0x03, 0x01, 0x02],
+            [0x01, 0x02, 0x03, 0x04, 0x05, 0x06],
+            [0x00; 6],
+        ] {
+            let mut branchless = keys;
+            let mut stable = keys;
+
+            sort_keycodes_branchless(&mut branchless);
+            sort_keycodes_stable(&mut stable);
+
+            assert_eq!(branchless, stable);
+        }
+
+        let mut keys: [u8; 0] = [];
+        sort_keycodes_branchless(&mut keys);
+    }
+
+    #[test]
+    fn test_xor_swap() {
+        let mut keys = [0x01, 0x02, 0x02];
+
+        assert!(xor_swap(&mut keys, 0, 1));
+        assert_eq!(keys, [0x02, 0x01, 0x02]);
+
+        // equal values are left untouched, instead of being zeroed
+        assert!(!xor_swap(&mut keys, 0, 2));
+        assert!(!xor_swap(&mut keys, 1, 1));
+        assert_eq!(keys, [0x02, 0x01, 0x02]);
+
+        // out-of-range indices are ignored
+        assert!(!xor_swap(&mut keys, 0, 3));
+        assert!(!xor_swap(&mut keys, 3, 0));
+        assert_eq!(keys, [0x02, 0x01, 0x02]);
+    }
 }
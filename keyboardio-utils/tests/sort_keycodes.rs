@@ -0,0 +1,72 @@
+use keyboardio_utils::{sort_keycodes, sort_keycodes_branchless, sort_keycodes_stable};
+use proptest::prelude::*;
+
+// Boot reports are mostly empty, so weight zero slots as heavily as all other keycodes.
+fn report_keycode() -> impl Strategy<Value = u8> {
+    prop_oneof![Just(0u8), any::<u8>()]
+}
+
+fn sorted_non_zero(keys: &[u8]) -> Vec<u8> {
+    let mut non_zero: Vec<u8> = keys.iter().copied().filter(|&k| k != 0).collect();
+    non_zero.sort_unstable();
+    non_zero
+}
+
+proptest! {
+    #[test]
+    fn test_sort_keycodes_partitions_zeros(mut keys in prop::collection::vec(any::<u8>(), 0..32)) {
+        sort_keycodes(&mut keys);
+
+        let first_zero = keys.iter().position(|&k| k == 0).unwrap_or(keys.len());
+        prop_assert!(keys[first_zero..].iter().all(|&k| k == 0));
+    }
+
+    #[test]
+    fn test_sort_keycodes_preserves_keys(mut keys in prop::collection::vec(any::<u8>(), 0..32)) {
+        let expected = sorted_non_zero(&keys);
+        let len = keys.len();
+
+        sort_keycodes(&mut keys);
+
+        prop_assert_eq!(keys.len(), len);
+        prop_assert_eq!(sorted_non_zero(&keys), expected);
+    }
+
+    #[test]
+    fn test_sort_keycodes_sparse_reports(mut keys in prop::array::uniform6(report_keycode())) {
+        let expected = sorted_non_zero(&keys);
+
+        sort_keycodes(&mut keys);
+
+        let first_zero = keys.iter().position(|&k| k == 0).unwrap_or(keys.len());
+        prop_assert!(keys[first_zero..].iter().all(|&k| k == 0));
+        prop_assert_eq!(sorted_non_zero(&keys), expected);
+    }
+
+    #[test]
+    fn test_sort_keycodes_stable_keeps_order(mut keys in prop::collection::vec(report_keycode(), 0..32)) {
+        let mut expected: Vec<u8> = keys.iter().copied().filter(|&k| k != 0).collect();
+        expected.resize(keys.len(), 0);
+
+        sort_keycodes_stable(&mut keys);
+
+        prop_assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn test_sort_keycodes_branchless_agrees(keys in prop::collection::vec(report_keycode(), 0..32)) {
+        let (mut branchless, mut stable, mut swapped) = (keys.clone(), keys.clone(), keys);
+
+        sort_keycodes_branchless(&mut branchless);
+        sort_keycodes_stable(&mut stable);
+        sort_keycodes(&mut swapped);
+
+        prop_assert_eq!(&branchless, &stable);
+        // the XOR-swap path keeps the same keys and free slots, in another order
+        prop_assert_eq!(sorted_non_zero(&branchless), sorted_non_zero(&swapped));
+        prop_assert_eq!(
+            branchless.iter().filter(|&&k| k == 0).count(),
+            swapped.iter().filter(|&&k| k == 0).count()
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use usb_device::UsbError;
+
+/// Result type returned when sending keyboard reports.
+pub type KeyboardResult<T> = core::result::Result<T, KeyboardError>;
+
+/// Errors returned by keyboard devices.
+#[derive(Debug)]
+pub enum KeyboardError {
+    /// Error returned by the USB stack.
+    Usb(UsbError),
+    /// No free keycode slot is left in the report.
+    RolloverFull,
+    /// The keycode is not valid for the keyboard type.
+    InvalidKey(u8),
+    /// The USB device is not configured by the host, or is suspended, or the HID interface is not
+    /// configured to send reports in the current protocol mode.
+    NotConfigured,
+    /// The report is larger than the max packet size of the endpoint.
+    ReportTooLarge,
+}
+
+impl KeyboardError {
+    /// Gets whether the error is a [UsbError::WouldBlock], i.e. the report can be retried.
+    pub const fn is_would_block(&self) -> bool {
+        matches!(self, Self::Usb(UsbError::WouldBlock))
+    }
+}
+
+impl From<UsbError> for KeyboardError {
+    fn from(err: UsbError) -> Self {
+        match err {
+            // `usbd_hid` refuses to push reports when the protocol mode does not match the
+            // interface subclass.
+            UsbError::InvalidState => Self::NotConfigured,
+            // The USB bus refuses to write packets larger than the endpoint max packet size.
+            UsbError::BufferOverflow => Self::ReportTooLarge,
+            err => Self::Usb(err),
+        }
+    }
+}
+
+/// Gets the name of the [UsbError] variant, e.g. `WouldBlock`, for logging without `Debug`.
+pub const fn usb_error_name(err: &UsbError) -> &'static str {
+    match err {
+        UsbError::WouldBlock => "WouldBlock",
+        UsbError::ParseError => "ParseError",
+        UsbError::BufferOverflow => "BufferOverflow",
+        UsbError::EndpointOverflow => "EndpointOverflow",
+        UsbError::EndpointMemoryOverflow => "EndpointMemoryOverflow",
+        UsbError::InvalidEndpoint => "InvalidEndpoint",
+        UsbError::Unsupported => "Unsupported",
+        UsbError::InvalidState => "InvalidState",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_usb_error() {
+        assert!(KeyboardError::from(UsbError::WouldBlock).is_would_block());
+        assert!(matches!(
+            KeyboardError::from(UsbError::BufferOverflow),
+            KeyboardError::ReportTooLarge
+        ));
+        assert!(matches!(
+            KeyboardError::from(UsbError::InvalidEndpoint),
+            KeyboardError::Usb(UsbError::InvalidEndpoint)
+        ));
+        assert!(matches!(
+            KeyboardError::from(UsbError::InvalidState),
+            KeyboardError::NotConfigured
+        ));
+        assert!(!KeyboardError::RolloverFull.is_would_block());
+    }
+
+    #[test]
+    fn test_usb_error_name() {
+        assert_eq!(usb_error_name(&UsbError::WouldBlock), "WouldBlock");
+        assert_eq!(usb_error_name(&UsbError::InvalidEndpoint), "InvalidEndpoint");
+    }
+}
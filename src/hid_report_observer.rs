@@ -1,16 +1,44 @@
 use usb_device::Result;
 
+use crate::error::usb_error_name;
 use crate::hid_settings::{HIDReport, HIDReportId};
 
 /// Callback function for sending HID reports.
 pub type SendReportHook = fn(id: HIDReportId, report: HIDReport, result: &Result<()>);
 
+/// Callback function for output (host-to-device) HID reports, e.g. the keyboard LED state.
+pub type OutputReportHook = fn(id: HIDReportId, data: &[u8]);
+
+/// Callback function for key presses dropped by a full boot keyboard report.
+pub type RolloverOverflowHook = fn(key: u8);
+
+/// Gets the name of the [UsbError](usb_device::UsbError) that failed a report send, or `None`
+/// if the report was sent.
+pub fn send_error_name(result: &Result<()>) -> Option<&'static str> {
+    result.as_ref().err().map(usb_error_name)
+}
+
+/// [SendReportHook] logging failed report sends with `defmt`, with the report ID and the
+/// [UsbError](usb_device::UsbError) variant.
+///
+/// Used by [HIDReportObserver::default] when the `defmt` feature is enabled, to diagnose dropped
+/// reports in the field. The firmware must provide a `defmt` global logger, e.g. `defmt-rtt`.
+#[cfg(feature = "defmt")]
+pub fn log_send_error(id: HIDReportId, _report: HIDReport, result: &Result<()>) {
+    if let Some(name) = send_error_name(result) {
+        defmt::error!("report {=u8} not sent: {=str}", id as u8, name);
+    }
+}
+
 pub struct HIDReportObserver {
     send_report_hook: Option<SendReportHook>,
+    output_report_hook: Option<OutputReportHook>,
+    rollover_overflow_hook: Option<RolloverOverflowHook>,
 }
 
 impl HIDReportObserver {
     #[allow(non_upper_case_globals)]
+    #[cfg_attr(feature = "defmt", allow(dead_code))]
     const NopSendReportHook: SendReportHook =
         |_id: HIDReportId, _report: HIDReport, _result: &Result<()>| {};
 
@@ -18,13 +46,24 @@ impl HIDReportObserver {
     pub const fn new(send_report_hook: SendReportHook) -> Self {
         Self {
             send_report_hook: Some(send_report_hook),
+            output_report_hook: None,
+            rollover_overflow_hook: None,
         }
     }
 
     /// Creates a default [HIDReportObserver] with no-op [SendReportHook].
+    ///
+    /// With the `defmt` feature, the [SendReportHook] is [log_send_error] instead.
     pub const fn default() -> Self {
+        #[cfg(feature = "defmt")]
+        let send_report_hook: SendReportHook = log_send_error;
+        #[cfg(not(feature = "defmt"))]
+        let send_report_hook = Self::NopSendReportHook;
+
         Self {
-            send_report_hook: Some(Self::NopSendReportHook),
+            send_report_hook: Some(send_report_hook),
+            output_report_hook: None,
+            rollover_overflow_hook: None,
         }
     }
 
@@ -44,4 +83,58 @@ impl HIDReportObserver {
     pub fn set_hook(&mut self, new_hook: SendReportHook) {
         self.send_report_hook = Some(new_hook);
     }
+
+    /// Sets the [OutputReportHook], keeping the [SendReportHook].
+    pub const fn with_output_report_hook(mut self, hook: OutputReportHook) -> Self {
+        self.output_report_hook = Some(hook);
+        self
+    }
+
+    /// Notifies the currently set [OutputReportHook] of an output report from the host.
+    pub fn observe_output_report(&self, id: HIDReportId, data: &[u8]) {
+        if let Some(output_report_hook) = self.output_report_hook {
+            output_report_hook(id, data);
+        }
+    }
+
+    /// Gets the currently set [OutputReportHook].
+    pub fn output_report_hook(&self) -> Option<OutputReportHook> {
+        self.output_report_hook
+    }
+
+    /// Sets the [OutputReportHook].
+    pub fn set_output_report_hook(&mut self, new_hook: OutputReportHook) {
+        self.output_report_hook = Some(new_hook);
+    }
+
+    /// Sets the [RolloverOverflowHook], keeping the other hooks.
+    pub const fn with_rollover_overflow_hook(mut self, hook: RolloverOverflowHook) -> Self {
+        self.rollover_overflow_hook = Some(hook);
+        self
+    }
+
+    /// Notifies the currently set [RolloverOverflowHook] of a key press exceeding the rollover
+    /// limit.
+    pub fn observe_rollover_overflow(&self, key: u8) {
+        if let Some(rollover_overflow_hook) = self.rollover_overflow_hook {
+            rollover_overflow_hook(key);
+        }
+    }
+
+    /// Gets the currently set [RolloverOverflowHook].
+    pub fn rollover_overflow_hook(&self) -> Option<RolloverOverflowHook> {
+        self.rollover_overflow_hook
+    }
+
+    /// Sets the [RolloverOverflowHook].
+    pub fn set_rollover_overflow_hook(&mut self, new_hook: RolloverOverflowHook) {
+        self.rollover_overflow_hook = Some(new_hook);
+    }
+}
+
+impl Default for HIDReportObserver {
+    fn default() -> Self {
+        // calls the inherent `const fn`
+        Self::default()
+    }
 }
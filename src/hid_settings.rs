@@ -1,5 +1,9 @@
 use usbd_hid::descriptor::{KeyboardReport, MediaKeyboardReport, MouseReport, SystemControlReport};
 
+use crate::keyboard::absolute_mouse::AbsoluteMouseReport;
+use crate::keyboard::gamepad::GamepadReport;
+use crate::keyboard::nkro::NKROReport;
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub enum HIDReportId {
@@ -15,9 +19,264 @@ pub enum HIDReportId {
     NKROKeyboard,
 }
 
+#[derive(Clone, Copy, Debug)]
 pub enum HIDReport {
     Keyboard(KeyboardReport),
     MediaKeyboardReport(MediaKeyboardReport),
     MouseReport(MouseReport),
     SystemControl(SystemControlReport),
+    Gamepad(GamepadReport),
+    AbsoluteMouse(AbsoluteMouseReport),
+    NKROKeyboard(NKROReport),
+}
+
+impl HIDReport {
+    /// Serializes the input report into its wire format.
+    ///
+    /// Returns the number of bytes written to `buf`, or 0 if `buf` is too small for the report.
+    pub fn serialize(&self, buf: &mut [u8]) -> usize {
+        // `ssmarshal` asserts on short buffers in debug builds, so serialize into a buffer large
+        // enough for any report first.
+        let mut report_buf = [0u8; 64];
+        let res = match self {
+            Self::Keyboard(r) => ssmarshal::serialize(&mut report_buf, r),
+            Self::MediaKeyboardReport(r) => ssmarshal::serialize(&mut report_buf, r),
+            Self::MouseReport(r) => ssmarshal::serialize(&mut report_buf, r),
+            Self::SystemControl(r) => ssmarshal::serialize(&mut report_buf, r),
+            Self::Gamepad(r) => ssmarshal::serialize(&mut report_buf, r),
+            Self::AbsoluteMouse(r) => ssmarshal::serialize(&mut report_buf, r),
+            Self::NKROKeyboard(r) => ssmarshal::serialize(&mut report_buf, r),
+        };
+
+        match res {
+            Ok(len) if len <= buf.len() => {
+                buf[..len].copy_from_slice(&report_buf[..len]);
+                len
+            }
+            _ => 0,
+        }
+    }
+
+    /// Deserializes an output (host-to-device) report from its wire format into the report.
+    ///
+    /// Only the [KeyboardReport] has output fields (the LED state), all other reports are left
+    /// unchanged.
+    ///
+    /// Returns the number of bytes read from `buf`, or 0 if `buf` is too small for the report.
+    pub fn deserialize(&mut self, buf: &[u8]) -> usize {
+        match (self, buf.first()) {
+            (Self::Keyboard(r), Some(&leds)) => {
+                r.leds = leds;
+                1
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl From<KeyboardReport> for HIDReport {
+    fn from(report: KeyboardReport) -> Self {
+        Self::Keyboard(report)
+    }
+}
+
+impl From<MediaKeyboardReport> for HIDReport {
+    fn from(report: MediaKeyboardReport) -> Self {
+        Self::MediaKeyboardReport(report)
+    }
+}
+
+impl From<MouseReport> for HIDReport {
+    fn from(report: MouseReport) -> Self {
+        Self::MouseReport(report)
+    }
+}
+
+impl From<SystemControlReport> for HIDReport {
+    fn from(report: SystemControlReport) -> Self {
+        Self::SystemControl(report)
+    }
+}
+
+impl From<GamepadReport> for HIDReport {
+    fn from(report: GamepadReport) -> Self {
+        Self::Gamepad(report)
+    }
+}
+
+impl From<AbsoluteMouseReport> for HIDReport {
+    fn from(report: AbsoluteMouseReport) -> Self {
+        Self::AbsoluteMouse(report)
+    }
+}
+
+impl From<NKROReport> for HIDReport {
+    fn from(report: NKROReport) -> Self {
+        Self::NKROKeyboard(report)
+    }
+}
+
+// The `usbd_hid` report types only derive `Debug`, `Clone`, and `Copy`, so compare field-wise.
+impl PartialEq for HIDReport {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Keyboard(a), Self::Keyboard(b)) => {
+                a.modifier == b.modifier
+                    && a.reserved == b.reserved
+                    && a.leds == b.leds
+                    && a.keycodes == b.keycodes
+            }
+            (Self::MediaKeyboardReport(a), Self::MediaKeyboardReport(b)) => {
+                a.usage_id == b.usage_id
+            }
+            (Self::MouseReport(a), Self::MouseReport(b)) => {
+                a.buttons == b.buttons
+                    && a.x == b.x
+                    && a.y == b.y
+                    && a.wheel == b.wheel
+                    && a.pan == b.pan
+            }
+            (Self::SystemControl(a), Self::SystemControl(b)) => a.usage_id == b.usage_id,
+            (Self::Gamepad(a), Self::Gamepad(b)) => a == b,
+            (Self::AbsoluteMouse(a), Self::AbsoluteMouse(b)) => a == b,
+            (Self::NKROKeyboard(a), Self::NKROKeyboard(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl From<HIDReportId> for u8 {
+    fn from(id: HIDReportId) -> u8 {
+        id as u8
+    }
+}
+
+impl TryFrom<u8> for HIDReportId {
+    type Error = u8;
+
+    /// Converts a raw report ID byte into a [HIDReportId].
+    ///
+    /// Returns the raw byte as the error for out-of-range values.
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Mouse),
+            2 => Ok(Self::Keyboard),
+            3 => Ok(Self::RawHID),
+            4 => Ok(Self::ConsumerControl),
+            5 => Ok(Self::SystemControl),
+            6 => Ok(Self::Gamepad),
+            7 => Ok(Self::MouseAbsolute),
+            8 => Ok(Self::NKROKeyboard),
+            _ => Err(id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_id_round_trip() {
+        let ids = [
+            HIDReportId::None,
+            HIDReportId::Mouse,
+            HIDReportId::Keyboard,
+            HIDReportId::RawHID,
+            HIDReportId::ConsumerControl,
+            HIDReportId::SystemControl,
+            HIDReportId::Gamepad,
+            HIDReportId::MouseAbsolute,
+            HIDReportId::NKROKeyboard,
+        ];
+
+        for (raw, id) in ids.into_iter().enumerate() {
+            assert_eq!(u8::from(id), raw as u8);
+            assert_eq!(HIDReportId::try_from(raw as u8), Ok(id));
+        }
+
+        assert_eq!(HIDReportId::try_from(9), Err(9));
+        assert_eq!(HIDReportId::try_from(0xff), Err(0xff));
+    }
+
+    #[test]
+    fn test_report_eq() {
+        let report = KeyboardReport {
+            modifier: 0x02,
+            reserved: 0,
+            leds: 0,
+            keycodes: [0x04, 0x05, 0, 0, 0, 0],
+        };
+        let mut other = report;
+
+        assert_eq!(HIDReport::Keyboard(report), HIDReport::Keyboard(other));
+
+        other.keycodes[1] = 0x06;
+        assert_ne!(HIDReport::Keyboard(report), HIDReport::Keyboard(other));
+
+        assert_ne!(
+            HIDReport::Keyboard(report),
+            HIDReport::SystemControl(SystemControlReport { usage_id: 0x82 })
+        );
+    }
+
+    #[test]
+    fn test_report_from() {
+        let mut gamepad = GamepadReport::default();
+        gamepad.buttons[0] = 0b101;
+        gamepad.rz = -1;
+
+        assert_eq!(HIDReport::from(gamepad), HIDReport::Gamepad(gamepad));
+        assert_eq!(
+            HIDReport::from(SystemControlReport { usage_id: 0x82 }),
+            HIDReport::SystemControl(SystemControlReport { usage_id: 0x82 })
+        );
+        assert_ne!(
+            HIDReport::from(gamepad),
+            HIDReport::from(GamepadReport::default())
+        );
+    }
+
+    #[test]
+    fn test_report_serialize() {
+        let mut report = HIDReport::Keyboard(KeyboardReport {
+            modifier: 0x02,
+            reserved: 0,
+            leds: 0,
+            keycodes: [0x04, 0x05, 0, 0, 0, 0],
+        });
+        let mut buf = [0xffu8; 16];
+
+        assert_eq!(report.serialize(&mut buf), 8);
+        assert_eq!(buf[..8], [0x02, 0x00, 0x04, 0x05, 0x00, 0x00, 0x00, 0x00]);
+
+        assert_eq!(report.serialize(&mut buf[..4]), 0);
+
+        assert_eq!(report.deserialize(&[0b011]), 1);
+        assert_eq!(report.deserialize(&[]), 0);
+        match report {
+            HIDReport::Keyboard(r) => assert_eq!(r.leds, 0b011),
+            _ => panic!("unexpected report type"),
+        }
+
+        let mut report = HIDReport::SystemControl(SystemControlReport { usage_id: 0x82 });
+        assert_eq!(report.serialize(&mut buf), 1);
+        assert_eq!(buf[0], 0x82);
+        assert_eq!(report.deserialize(&[0x01]), 0);
+
+        // `A` and `Keypad Hexadecimal`, the first and last printable keys of the NKRO bitmap
+        let mut nkro = NKROReport {
+            modifier: 0x02,
+            ..NKROReport::default()
+        };
+        nkro.keycodes[0] = 0x10;
+        nkro.keycodes[27] = 0x20;
+
+        let mut buf = [0xffu8; 32];
+        assert_eq!(HIDReport::NKROKeyboard(nkro).serialize(&mut buf), 30);
+        assert_eq!(buf[..3], [0x02, 0x00, 0x10]);
+        assert!(buf[3..29].iter().all(|&byte| byte == 0));
+        assert_eq!(buf[29], 0x20);
+    }
 }
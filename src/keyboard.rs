@@ -1,21 +1,82 @@
-use atmega_usbd::UsbBus;
-use usb_device::bus::UsbBusAllocator;
-use usbd_hid::descriptor::{KeyboardReport, KeyboardUsage, MediaKey, SerializedDescriptor, SystemControlKey, MediaKeyboardReport, SystemControlReport};
-use usbd_hid::hid_class::{HIDClass, HidCountryCode, HidProtocol};
+use core::fmt;
 
+use atmega_usbd::UsbBus as AtmegaUsbBus;
+use usb_device::bus::{UsbBus, UsbBusAllocator};
+use usb_device::device::{UsbDevice, UsbDeviceBuilder, UsbDeviceState, UsbVidPid};
+use usb_device::{Result, UsbError};
+use usbd_hid::descriptor::{AsInputReport, KeyboardReport, KeyboardUsage, MediaKey, SerializedDescriptor, SystemControlKey, MediaKeyboardReport, SystemControlReport};
+use usbd_hid::hid_class::{HIDClass, HidCountryCode, HidProtocol, HidProtocolMode, ReportType};
+
+use crate::error::{KeyboardError, KeyboardResult};
+use crate::hid_settings::{HIDReport, HIDReportId};
 use crate::HIDReportObserver;
 
+use boot::BootKeyboard;
+use builder::KeyboardBuilder;
+use keycode::Keycode;
+use media::{ConsumerRolloverPolicy, MEDIA_MAX_HELD_KEYS};
+use nkro::{NKROKeyboard, NKROKeycodes, NKROReport, ZERO_NKRO_KEYS};
+#[cfg(feature = "report-history")]
+use report_buffer::{ReportBuffer, RECENT_REPORTS_LEN};
+use report_queue::ReportQueue;
+use usb_strings::UsbStrings;
+
+pub mod absolute_mouse;
+pub mod adaptive_poll;
+pub mod auto_shift;
 pub mod boot;
+pub mod builder;
+pub mod caps_word;
+pub mod chord;
+pub mod compose;
+pub mod composite;
+pub mod config;
+pub mod gamepad;
+#[cfg(feature = "critical-section")]
+pub mod guarded;
+pub mod held_key;
+pub mod host_requests;
+pub mod key_event;
+#[cfg(feature = "heapless")]
+pub mod keyboard_macro;
+pub mod keycode;
+#[cfg(feature = "heapless")]
+pub mod keycode_vec;
+pub mod keypad;
+pub mod layer;
+pub mod matrix;
 pub mod media;
 pub mod nkro;
+pub mod raw_hid;
+pub mod report_buffer;
+pub mod report_descriptor;
+pub mod report_queue;
+pub mod string_typer;
 pub mod system_control;
+pub mod usb_strings;
 
 pub type Keycodes = [u8; 6];
 
-pub type KeyboardUsbBus = UsbBus<()>;
-pub type KeyboardUsbBusAllocator = UsbBusAllocator<KeyboardUsbBus>;
+/// Default USB bus used by the [Keyboard], the ATmega32u4 USB peripheral.
+pub type KeyboardUsbBus = AtmegaUsbBus<()>;
+/// USB bus allocator for the [Keyboard], generic over any [UsbBus] implementation.
+pub type KeyboardUsbBusAllocator<B = KeyboardUsbBus> = UsbBusAllocator<B>;
 
 pub(crate) const ZERO_KEYS: Keycodes = [0u8; 6];
+/// Num Lock bit of the keyboard LED output report.
+pub const LED_NUM_LOCK: u8 = 1 << 0;
+/// Caps Lock bit of the keyboard LED output report.
+pub const LED_CAPS_LOCK: u8 = 1 << 1;
+/// Scroll Lock bit of the keyboard LED output report.
+pub const LED_SCROLL_LOCK: u8 = 1 << 2;
+/// Max packet size of the HID interrupt endpoints.
+///
+/// [HIDClass] allocates its endpoints with this fixed size, the maximum for full-speed interrupt
+/// endpoints. Every report fits one packet, including NKRO bitmaps and 64-byte RawHID reports, so
+/// reports are never split or truncated.
+pub const HID_ENDPOINT_SIZE: u16 = 64;
+/// Number of reports queued while the IN endpoint is busy.
+pub const REPORT_QUEUE_LEN: usize = 4;
 // Polling interval for the host to check USB device reports.
 // Higher interval results in better power usage, but slower response time.
 // Lower interval results in faster response times, and more power consumption.
@@ -28,8 +89,18 @@ pub(crate) static POLL_MS: u8 = 128;
 #[cfg(feature = "best-effort")]
 pub(crate) static POLL_MS: u8 = 255;
 
+/// First printable keycode, `A`. The keycodes below are no key, and the error codes.
+pub const FIRST_PRINTABLE: u8 = KeyboardUsage::KeyboardAa as u8;
+/// Last printable keycode, `Keypad Hexadecimal`. The modifier keys follow.
+pub const LAST_PRINTABLE: u8 = KeyboardUsage::KeypadHexadecimal as u8;
+
+/// Gets whether the keycode is a non-modifier key, from [FIRST_PRINTABLE] to [LAST_PRINTABLE].
+///
+/// The keycodes reserved between `ExSel` and `Keypad 00` are not printable.
 pub const fn is_printable(key: u8) -> bool {
-    key <= KeyboardUsage::KeypadHexadecimal as u8
+    let reserved = key > KeyboardUsage::KeyboardExSel as u8 && key < KeyboardUsage::Keypad00 as u8;
+
+    key >= FIRST_PRINTABLE && key <= LAST_PRINTABLE && !reserved
 }
 
 pub const fn is_modifier(key: u8) -> bool {
@@ -44,19 +115,121 @@ pub fn is_system_control(key: u8) -> bool {
     SystemControlKey::from(key) != SystemControlKey::Reserved
 }
 
+/// Const equivalent of [is_media], matching the usages defined by [MediaKey].
+pub const fn is_media_const(key: u8) -> bool {
+    matches!(
+        key,
+        0x00 | 0xb0..=0xb2 | 0xb5..=0xb7 | 0xb9 | 0xbc | 0xcd | 0xe2 | 0xe9 | 0xea
+    )
+}
+
+/// Const equivalent of [is_system_control], matching the usages defined by [SystemControlKey].
+pub const fn is_system_control_const(key: u8) -> bool {
+    matches!(
+        key,
+        0x81..=0x93 | 0x97 | 0x98 | 0x9a | 0x9b | 0xa0..=0xa8 | 0xb0..=0xb7
+    )
+}
+
 pub(crate) const fn key_to_index(key: u8) -> usize {
     (key / 8) as usize
 }
 
+/// Gets whether the keyboard reports have the same modifiers, LEDs, and keycodes.
+///
+/// [KeyboardReport] does not implement [PartialEq].
+pub const fn reports_equal(a: &KeyboardReport, b: &KeyboardReport) -> bool {
+    let mut i = 0;
+    while i < a.keycodes.len() {
+        if a.keycodes[i] != b.keycodes[i] {
+            return false;
+        }
+        i += 1;
+    }
+
+    a.modifier == b.modifier && a.leds == b.leds
+}
+
+/// Gets the index of the key in a bitmap of `len` bytes.
+///
+/// Returns `None` if the key is out of range of the bitmap.
+pub(crate) const fn key_to_index_checked(key: u8, len: usize) -> Option<usize> {
+    let index = key_to_index(key);
+    if index < len {
+        Some(index)
+    } else {
+        None
+    }
+}
+
 pub(crate) const fn key_to_printable_bitfield(key: u8) -> u8 {
     1 << (key % 8)
 }
 
+// Only valid for the modifier keys, callers check with `is_modifier` first. See `modifier_bit`
+// for unchecked keys.
 pub(crate) const fn key_to_modifier_bitfield(key: u8) -> u8 {
+    debug_assert!(is_modifier(key), "not a modifier key");
     1 << (key - KeyboardUsage::KeyboardLeftControl as u8)
 }
 
-// FIXME: allow setting locale at runtime by setting config value in device memory.
+/// Gets the bit of the modifier key in the report modifier bitfield.
+///
+/// Returns `None` if the key is not a modifier key.
+pub const fn modifier_bit(key: u8) -> Option<u8> {
+    if is_modifier(key) {
+        Some(key_to_modifier_bitfield(key))
+    } else {
+        None
+    }
+}
+
+// Gets the keys, including modifiers, set in the `new` report and NKRO bitmap that are not set in
+// the `old` ones.
+fn keys_added(
+    old: (KeyboardReport, NKROKeycodes),
+    new: (KeyboardReport, NKROKeycodes),
+) -> impl Iterator<Item = u8> {
+    let ((old, old_nkro), (new, new_nkro)) = (old, new);
+    let modifiers = new.modifier & !old.modifier;
+
+    let keys = new
+        .keycodes
+        .into_iter()
+        .filter(move |key| *key != 0 && !old.keycodes.contains(key));
+    let nkro_keys =
+        nkro::bitmap_keys(new_nkro).filter(move |&key| !nkro::bitmap_contains(&old_nkro, key));
+
+    let modifier_keys = (0..8u8)
+        .filter(move |bit| modifiers & (1 << bit) != 0)
+        .map(|bit| KeyboardUsage::KeyboardLeftControl as u8 + bit);
+
+    keys.chain(nkro_keys).chain(modifier_keys)
+}
+
+// Moves keycodes between the boot array and the NKRO bitmap, emptying the other one.
+//
+// Keys that do not fit in the NKRO bitmap are dropped, and more than six NKRO keys convert to
+// `ErrorRollOver`.
+fn convert_keycodes(keycodes: &mut Keycodes, bitmap: &mut NKROKeycodes, to_nkro: bool) {
+    if to_nkro {
+        for &key in keycodes.iter().filter(|&&key| key != 0) {
+            if let Some(index) = key_to_index_checked(key, bitmap.len()) {
+                bitmap[index] |= key_to_printable_bitfield(key);
+            }
+        }
+        *keycodes = ZERO_KEYS;
+    } else {
+        let report = NKROReport {
+            keycodes: *bitmap,
+            ..NKROReport::default()
+        };
+        *keycodes = nkro::nkro_to_boot_report(&report).keycodes;
+        *bitmap = ZERO_NKRO_KEYS;
+    }
+}
+
+// Gets the build-time locale. Locales persisted at runtime are loaded with `config::load`.
 pub(crate) const fn keyboard_locale() -> HidCountryCode {
     if cfg!(feature = "arabic") {
         HidCountryCode::Arabic
@@ -133,75 +306,422 @@ pub(crate) const fn keyboard_locale() -> HidCountryCode {
     }
 }
 
-pub struct Keyboard<'k> {
-    hid_class: HIDClass<'k, KeyboardUsbBus>,
+// Number of enabled locale features. The features are mutually exclusive, `keyboard_locale`
+// would silently pick the first one.
+const LOCALE_FEATURES: usize = cfg!(feature = "arabic") as usize
+    + cfg!(feature = "belgian") as usize
+    + cfg!(feature = "canadian-bilingual") as usize
+    + cfg!(feature = "canadian-french") as usize
+    + cfg!(feature = "czech") as usize
+    + cfg!(feature = "danish") as usize
+    + cfg!(feature = "finnish") as usize
+    + cfg!(feature = "french") as usize
+    + cfg!(feature = "german") as usize
+    + cfg!(feature = "greek") as usize
+    + cfg!(feature = "hebrew") as usize
+    + cfg!(feature = "hungary") as usize
+    + cfg!(feature = "international") as usize
+    + cfg!(feature = "italian") as usize
+    + cfg!(feature = "japanese") as usize
+    + cfg!(feature = "korean") as usize
+    + cfg!(feature = "latin-america") as usize
+    + cfg!(feature = "netherlands") as usize
+    + cfg!(feature = "norwegian") as usize
+    + cfg!(feature = "farsi") as usize
+    + cfg!(feature = "poland") as usize
+    + cfg!(feature = "portuguese") as usize
+    + cfg!(feature = "russia") as usize
+    + cfg!(feature = "slovakia") as usize
+    + cfg!(feature = "spanish") as usize
+    + cfg!(feature = "swedish") as usize
+    + cfg!(feature = "swiss-french") as usize
+    + cfg!(feature = "swiss-german") as usize
+    + cfg!(feature = "switzerland") as usize
+    + cfg!(feature = "taiwan") as usize
+    + cfg!(feature = "turkish-q") as usize
+    + cfg!(feature = "uk") as usize
+    + cfg!(feature = "us") as usize
+    + cfg!(feature = "yugoslavia") as usize
+    + cfg!(feature = "turkish-f") as usize;
+
+const _: () = assert!(
+    matches!(LOCALE_FEATURES, 0 | 1),
+    "only one keyboard locale feature may be enabled"
+);
+
+/// Gets the keyboard locale selected by the locale feature of the build, e.g. `us`.
+///
+/// Returns [HidCountryCode::NotSupported] if no locale feature is enabled. Keyboards built with
+/// [KeyboardBuilder::locale] report their configured locale instead.
+pub const fn active_locale() -> HidCountryCode {
+    keyboard_locale()
+}
+
+/// Common operations shared by keyboard devices.
+///
+/// Implementors only need to provide access to the current and last [KeyboardReport], and the
+/// device-specific [press](KeyboardOps::press), [release](KeyboardOps::release), and
+/// [send_report](KeyboardOps::send_report) methods. The remaining methods have default
+/// implementations in terms of the reports.
+///
+/// Example:
+///
+/// ```
+/// use keyboardio_hid::usbd_hid::descriptor::KeyboardReport;
+/// use keyboardio_hid::{KeyboardOps, KeyboardResult};
+///
+/// struct SingleKeyKeyboard {
+///     report: KeyboardReport,
+///     last_report: KeyboardReport,
+/// }
+///
+/// impl KeyboardOps for SingleKeyKeyboard {
+///     fn report(&self) -> &KeyboardReport {
+///         &self.report
+///     }
+///
+///     fn report_mut(&mut self) -> &mut KeyboardReport {
+///         &mut self.report
+///     }
+///
+///     fn last_report(&self) -> &KeyboardReport {
+///         &self.last_report
+///     }
+///
+///     fn last_report_mut(&mut self) -> &mut KeyboardReport {
+///         &mut self.last_report
+///     }
+///
+///     fn press(&mut self, key: u8) -> usize {
+///         self.report.keycodes[0] = key;
+///         1
+///     }
+///
+///     fn release(&mut self, key: u8) -> usize {
+///         if self.report.keycodes[0] == key {
+///             self.report.keycodes[0] = 0;
+///         }
+///         1
+///     }
+///
+///     fn send_report(&mut self) -> KeyboardResult<()> {
+///         self.last_report = self.report;
+///         Ok(())
+///     }
+/// }
+///
+/// let mut keyboard = SingleKeyKeyboard {
+///     report: KeyboardReport::default(),
+///     last_report: KeyboardReport::default(),
+/// };
+///
+/// keyboard.press(0x04);
+/// assert!(keyboard.keycodes_changed());
+///
+/// keyboard.send_report().unwrap();
+/// assert!(!keyboard.keycodes_changed());
+///
+/// keyboard.release_all();
+/// assert!(keyboard.keycodes_changed());
+/// assert!(!keyboard.is_any_modifier_active());
+/// ```
+pub trait KeyboardOps {
+    /// Gets a reference to the current keyboard report.
+    fn report(&self) -> &KeyboardReport;
+
+    /// Gets a mutable reference to the current keyboard report.
+    fn report_mut(&mut self) -> &mut KeyboardReport;
+
+    /// Gets a reference to the last keyboard report.
+    fn last_report(&self) -> &KeyboardReport;
+
+    /// Gets a mutable reference to the last keyboard report.
+    ///
+    /// The last report is the change detection baseline, and must match the modifiers and
+    /// keycodes last sent to the host. Sending reports stages intermediate reports in it.
+    ///
+    /// Changing the modifiers or keycodes of the last report of a [Keyboard] is unsupported, use
+    /// [resync_last_report](Keyboard::resync_last_report) instead. In debug builds,
+    /// [send_report](Self::send_report) panics if they changed since the last send. Other sends,
+    /// e.g. [flush](Keyboard::flush), replace the last report with the sent report, and don't
+    /// check it.
+    fn last_report_mut(&mut self) -> &mut KeyboardReport;
+
+    /// Press a key, and add it to the current report.
+    ///
+    /// Returns 1 if the key was added to the current report, 0 otherwise.
+    fn press(&mut self, key: u8) -> usize;
+
+    /// Release a pressed key if the keycode is present in the current report.
+    ///
+    /// Returns 1 if the key was handled by the device, 0 otherwise.
+    fn release(&mut self, key: u8) -> usize;
+
+    /// Sends the current keyboard report to the host.
+    fn send_report(&mut self) -> KeyboardResult<()>;
+
+    /// Sets the current keyboard report.
+    fn set_report(&mut self, report: KeyboardReport) {
+        *self.report_mut() = report;
+    }
+
+    /// Gets the modifier bitfield of the current keyboard report.
+    fn modifiers(&self) -> u8 {
+        self.report().modifier
+    }
+
+    /// Sets the modifier bitfield of the current keyboard report.
+    fn set_modifiers(&mut self, modifiers: u8) {
+        self.report_mut().modifier = modifiers;
+    }
+
+    /// Adds the modifiers in the bitfield to the current keyboard report.
+    fn add_modifiers(&mut self, modifiers: u8) {
+        self.report_mut().modifier |= modifiers;
+    }
+
+    /// Removes the modifiers in the bitfield from the current keyboard report.
+    fn remove_modifiers(&mut self, modifiers: u8) {
+        self.report_mut().modifier &= !modifiers;
+    }
+
+    /// Release all keycodes registered in the current keyboard report.
+    fn release_all(&mut self) {
+        let report = self.report_mut();
+
+        report.modifier = 0;
+        report.keycodes.copy_from_slice(ZERO_KEYS.as_ref());
+    }
+
+    /// Release all modifiers in the current keyboard report, keeping the other keys held.
+    ///
+    /// Useful to clean up sticky modifiers. The report is not sent.
+    ///
+    /// Same as `set_modifiers(0)`. On a [Keyboard], the modifiers wrapped around a key by
+    /// [press_with_modifiers](Keyboard::press_with_modifiers) are forgotten too, so a modifier
+    /// pressed again afterwards stays pressed on
+    /// [release_with_modifiers](Keyboard::release_with_modifiers).
+    fn release_modifiers_only(&mut self) {
+        self.set_modifiers(0);
+    }
+
+    /// Release all non-modifier keys in the current keyboard report, keeping the modifiers held.
+    ///
+    /// The report is not sent.
+    fn release_keys_only(&mut self) {
+        self.report_mut()
+            .keycodes
+            .copy_from_slice(ZERO_KEYS.as_ref());
+    }
+
+    /// Release all keycodes, and send the released report to the host.
+    ///
+    /// Keycodes are released before modifiers, e.g. when the keyboard loses focus.
+    fn release_all_and_send(&mut self) -> KeyboardResult<()> {
+        self.release_all();
+        self.send_report()
+    }
+
+    /// Press and release a key, sending the keyboard report after each.
+    fn tap(&mut self, key: u8) -> KeyboardResult<()> {
+        self.press(key);
+        self.send_report()?;
+        self.release(key);
+        self.send_report()
+    }
+
+    /// Gets whether the current keyboard report has no modifiers and no keycodes.
+    ///
+    /// Useful for activity detection, e.g. to wake the host while any key is pressed.
+    fn report_is_empty(&self) -> bool {
+        let report = self.report();
+
+        report.modifier == 0 && report.keycodes.iter().all(|&k| k == 0)
+    }
+
+    /// Gets whether the keycodes have changed between the last and current keyboard report.
+    ///
+    /// Ignores the modifiers, see [report_changed](Self::report_changed).
+    fn keycodes_changed(&self) -> bool {
+        let mut changed = 0;
+        for (last, current) in self
+            .last_report()
+            .keycodes
+            .iter()
+            .zip(self.report().keycodes.iter())
+        {
+            changed |= last ^ current;
+        }
+        changed != 0
+    }
+
+    /// Gets whether the modifiers or keycodes have changed between the last and current
+    /// keyboard report.
+    fn report_changed(&self) -> bool {
+        self.last_report().modifier != self.report().modifier || self.keycodes_changed()
+    }
+
+    /// Returns true if the modifer key passed in will be sent during this key report
+    /// Returns false in all other cases
+    fn is_modifier_active(&self, key: u8) -> bool {
+        is_modifier(key) && self.report().modifier & key_to_modifier_bitfield(key) != 0
+    }
+
+    /// Returns true if the modifer key passed in was being sent during the previous key report
+    /// Returns false in all other cases
+    fn was_modifier_active(&self, key: u8) -> bool {
+        is_modifier(key) && self.last_report().modifier & key_to_modifier_bitfield(key) != 0
+    }
+
+    /// Returns true if *any* modifier will be sent during this key report
+    /// Returns false in all other cases
+    fn is_any_modifier_active(&self) -> bool {
+        self.report().modifier > 0
+    }
+
+    /// Returns true if *any* modifier was being sent during the previous key report
+    /// Returns false in all other cases
+    fn was_any_modifier_active(&self) -> bool {
+        self.last_report().modifier > 0
+    }
+
+    /// Gets the number of modifiers active in the current keyboard report.
+    fn count_active_modifiers(&self) -> u8 {
+        self.report().modifier.count_ones() as u8
+    }
+
+    /// Gets the number of LEDs in the current keyboard report.
+    fn leds(&self) -> u8 {
+        self.report().leds
+    }
+}
+
+/// Callback function fired when the host changes the keyboard protocol.
+pub type ProtocolChangeHook = fn(protocol: HidProtocolMode);
+
+pub struct Keyboard<'k, B: UsbBus = KeyboardUsbBus> {
+    hid_class: HIDClass<'k, B>,
     report: KeyboardReport,
     last_report: KeyboardReport,
     observer: HIDReportObserver,
-    default_protocol: HidProtocol,
-    protocol: HidProtocol,
+    default_protocol: HidProtocolMode,
+    protocol: HidProtocolMode,
     idle: u8,
+    // Time since the last keyboard send, for the idle resend in `tick_idle`.
+    idle_ms: u32,
+    nkro: bool,
+    // NKRO keycode bitmaps of `report`, `last_report`, `event_report`, and `sent_report`. An NKRO
+    // keyboard keeps the keycodes of the reports empty, a boot keyboard keeps the bitmaps empty.
+    nkro_keys: NKROKeycodes,
+    last_nkro_keys: NKROKeycodes,
+    event_nkro_keys: NKROKeycodes,
+    sent_nkro_keys: NKROKeycodes,
+    wrapped_modifiers: u8,
+    // Boot and NKRO keyboard reports waiting for the IN endpoint.
+    pending: ReportQueue<HIDReport, REPORT_QUEUE_LEN>,
+    system_control_report: SystemControlReport,
+    last_system_control_report: SystemControlReport,
+    media_keys: [u16; MEDIA_MAX_HELD_KEYS],
+    last_media_report: MediaKeyboardReport,
+    consumer_rollover: ConsumerRolloverPolicy,
+    max_rollover: usize,
+    class_protocol: Option<HidProtocolMode>,
+    on_protocol_change: Option<ProtocolChangeHook>,
+    usb_strings: Option<UsbStrings<'static>>,
+    vid_pid: Option<(u16, u16)>,
+    num_lock_remap: bool,
+    // Keypad keys pressed with their Num Lock navigation usage, see `keypad::keypad_bit`.
+    remapped_keypad: u16,
+    report_ids: bool,
+    event_report: KeyboardReport,
+    // Copy of `last_report` at the end of the last send, checking `last_report_mut` misuse in
+    // `KeyboardOps::send_report`.
+    sent_report: KeyboardReport,
+    #[cfg(feature = "report-history")]
+    recent_reports: ReportBuffer<HIDReport, RECENT_REPORTS_LEN>,
+    host_requests: u8,
+    // Interface number of the HIDClass, matched against the index of host `Set_Idle` requests.
+    interface_number: u8,
+    device_state: Option<UsbDeviceState>,
 }
 
-impl<'k> Keyboard<'k> {
+impl<'k, B: UsbBus> Keyboard<'k, B> {
+    /// Creates a new [KeyboardBuilder] for configuring a [Keyboard].
+    pub const fn builder() -> KeyboardBuilder {
+        KeyboardBuilder::new()
+    }
+
     /// Creates a new Boot [Keyboard] device.
-    pub fn new_boot(bus: &'k KeyboardUsbBusAllocator) -> Self {
-        let hid_class = HIDClass::new_with_settings(
-            bus,
-            KeyboardReport::desc(),
-            POLL_MS,
-            boot::boot_hid_class_settings(HidProtocol::Keyboard),
-        );
+    pub fn new_boot(bus: &'k KeyboardUsbBusAllocator<B>) -> Self {
+        KeyboardBuilder::new().boot().build(bus)
+    }
 
-        Self::new_with_hid_class(hid_class)
+    /// Creates a new Boot [Keyboard] device, starting in the protocol.
+    ///
+    /// The protocol is also the default protocol, restored after a USB reset. See
+    /// [KeyboardBuilder::protocol].
+    pub fn new_boot_with_protocol(
+        bus: &'k KeyboardUsbBusAllocator<B>,
+        protocol: HidProtocolMode,
+    ) -> Self {
+        KeyboardBuilder::new().boot().protocol(protocol).build(bus)
     }
 
     /// Creates a new NKRO [Keyboard] device.
-    pub fn new_nkro(bus: &'k KeyboardUsbBusAllocator) -> Self {
-        let hid_class = HIDClass::new_with_settings(
-            bus,
-            KeyboardReport::desc(),
-            POLL_MS,
-            nkro::nkro_hid_class_settings(),
-        );
-
-        Self::new_with_hid_class(hid_class)
+    pub fn new_nkro(bus: &'k KeyboardUsbBusAllocator<B>) -> Self {
+        KeyboardBuilder::new().nkro().build(bus)
     }
 
     /// Creates a new Media [Keyboard] device.
-    pub fn new_media(bus: &'k KeyboardUsbBusAllocator) -> Self {
-        let hid_class = HIDClass::new_with_settings(
-            bus,
-            MediaKeyboardReport::desc(),
-            POLL_MS,
-            media::media_hid_class_settings(),
-        );
-
-        Self::new_with_hid_class(hid_class)
+    pub fn new_media(bus: &'k KeyboardUsbBusAllocator<B>) -> Self {
+        KeyboardBuilder::new().media().build(bus)
     }
 
     /// Creates a new System Control [Keyboard] device.
-    pub fn new_system_control(bus: &'k KeyboardUsbBusAllocator) -> Self {
-        let hid_class = HIDClass::new_with_settings(
-            bus,
-            SystemControlReport::desc(),
-            POLL_MS,
-            system_control::system_control_hid_class_settings(),
-        );
-
-        Self::new_with_hid_class(hid_class)
+    pub fn new_system_control(bus: &'k KeyboardUsbBusAllocator<B>) -> Self {
+        KeyboardBuilder::new().system_control().build(bus)
     }
 
     /// Creates a new [Keyboard] device with the provided HIDClass.
-    pub fn new_with_hid_class(hid_class: HIDClass<'k, KeyboardUsbBus>) -> Self {
+    pub fn new_with_hid_class(hid_class: HIDClass<'k, B>) -> Self {
+        let class_protocol = hid_class.get_protocol_mode().ok();
+
         Self {
             hid_class,
             report: KeyboardReport::default(),
             last_report: KeyboardReport::default(),
             observer: HIDReportObserver::default(),
-            default_protocol: HidProtocol::Keyboard,
-            protocol: HidProtocol::Keyboard,
+            default_protocol: HidProtocolMode::Boot,
+            protocol: HidProtocolMode::Boot,
             idle: 0,
+            idle_ms: 0,
+            nkro: false,
+            nkro_keys: ZERO_NKRO_KEYS,
+            last_nkro_keys: ZERO_NKRO_KEYS,
+            event_nkro_keys: ZERO_NKRO_KEYS,
+            sent_nkro_keys: ZERO_NKRO_KEYS,
+            wrapped_modifiers: 0,
+            pending: ReportQueue::new(),
+            system_control_report: SystemControlReport { usage_id: 0 },
+            last_system_control_report: SystemControlReport { usage_id: 0 },
+            media_keys: [0; MEDIA_MAX_HELD_KEYS],
+            last_media_report: MediaKeyboardReport { usage_id: 0 },
+            consumer_rollover: ConsumerRolloverPolicy::DropNewest,
+            max_rollover: ZERO_KEYS.len(),
+            class_protocol,
+            on_protocol_change: None,
+            usb_strings: None,
+            vid_pid: None,
+            num_lock_remap: false,
+            remapped_keypad: 0,
+            report_ids: false,
+            event_report: KeyboardReport::default(),
+            sent_report: KeyboardReport::default(),
+            #[cfg(feature = "report-history")]
+            recent_reports: ReportBuffer::new(),
+            host_requests: 0,
+            interface_number: 0,
+            device_state: None,
         }
     }
 
@@ -209,23 +729,11 @@ impl<'k> Keyboard<'k> {
     ///
     /// Allows setting a custom [HIDReportObserver] implementation for firing a callback function
     /// on HID report events.
-    pub fn new_with_observer(bus: &'k KeyboardUsbBusAllocator, observer: HIDReportObserver) -> Self {
-        let hid_class = HIDClass::new_with_settings(
-            bus,
-            KeyboardReport::desc(),
-            POLL_MS,
-            boot::boot_hid_class_settings(HidProtocol::Keyboard),
-        );
-
-        Self {
-            hid_class,
-            report: KeyboardReport::default(),
-            last_report: KeyboardReport::default(),
-            observer,
-            default_protocol: HidProtocol::Keyboard,
-            protocol: HidProtocol::Keyboard,
-            idle: 0,
-        }
+    pub fn new_with_observer(
+        bus: &'k KeyboardUsbBusAllocator<B>,
+        observer: HIDReportObserver,
+    ) -> Self {
+        KeyboardBuilder::new().observer(observer).build(bus)
     }
 
     pub fn as_ref(&self) -> &Self {
@@ -237,159 +745,2222 @@ impl<'k> Keyboard<'k> {
     }
 
     /// Initialize the HIDClass for a Boot [Keyboard].
-    pub fn init_boot(&'k mut self, bus: &'k KeyboardUsbBusAllocator) {
+    pub fn init_boot(&mut self, bus: &'k KeyboardUsbBusAllocator<B>) {
         self.hid_class = HIDClass::new_with_settings(
             bus,
             KeyboardReport::desc(),
             POLL_MS,
-            boot::boot_hid_class_settings(self.protocol),
+            boot::boot_hid_class_settings(HidProtocol::Keyboard),
         );
+        self.set_mode(false, HidProtocolMode::Boot);
     }
 
     /// Initialize the HIDClass for a NKRO [Keyboard].
-    pub fn init_nkro(&'k mut self, bus: &'k KeyboardUsbBusAllocator) {
+    pub fn init_nkro(&mut self, bus: &'k KeyboardUsbBusAllocator<B>) {
         self.hid_class = HIDClass::new_with_settings(
             bus,
-            KeyboardReport::desc(),
+            nkro::nkro_descriptor(),
             POLL_MS,
             nkro::nkro_hid_class_settings(),
         );
+        self.set_mode(true, HidProtocolMode::Report);
     }
 
     /// Initialize the HIDClass for a media [Keyboard].
-    pub fn init_media(&mut self, bus: &'k KeyboardUsbBusAllocator) {
+    pub fn init_media(&mut self, bus: &'k KeyboardUsbBusAllocator<B>) {
         self.hid_class = HIDClass::new_with_settings(
             bus,
             MediaKeyboardReport::desc(),
             POLL_MS,
             media::media_hid_class_settings(),
         );
+        self.set_mode(false, HidProtocolMode::Boot);
     }
 
     /// Initialize the HIDClass for a system control [Keyboard].
-    pub fn init_system_control(&'k mut self, bus: &'k KeyboardUsbBusAllocator) {
+    pub fn init_system_control(&mut self, bus: &'k KeyboardUsbBusAllocator<B>) {
         self.hid_class = HIDClass::new_with_settings(
             bus,
             SystemControlReport::desc(),
             POLL_MS,
             system_control::system_control_hid_class_settings(),
         );
+        self.set_mode(false, HidProtocolMode::Boot);
     }
 
-    /// Gets a reference to the current keyboard report.
-    pub fn report(&self) -> &KeyboardReport {
-        &self.report
-    }
-
-    /// Sets the current keyboard report.
-    pub fn set_report(&mut self, report: KeyboardReport) {
-        self.report = report;
-    }
-
-    /// Gets a mutable reference to the current keyboard report.
-    pub fn report_mut(&mut self) -> &mut KeyboardReport {
-        &mut self.report
-    }
+    // Switches the keycode representation and protocol after reinitializing the HIDClass.
+    //
+    // Pressed keys are carried over to the new representation, and reports queued for the
+    // previous HIDClass are dropped.
+    fn set_mode(&mut self, nkro: bool, protocol: HidProtocolMode) {
+        if self.nkro != nkro {
+            convert_keycodes(&mut self.report.keycodes, &mut self.nkro_keys, nkro);
+            convert_keycodes(
+                &mut self.last_report.keycodes,
+                &mut self.last_nkro_keys,
+                nkro,
+            );
+            convert_keycodes(
+                &mut self.event_report.keycodes,
+                &mut self.event_nkro_keys,
+                nkro,
+            );
+            convert_keycodes(
+                &mut self.sent_report.keycodes,
+                &mut self.sent_nkro_keys,
+                nkro,
+            );
+        }
 
-    /// Gets a reference to the last keyboard report.
-    pub fn last_report(&self) -> &KeyboardReport {
-        &self.report
+        self.nkro = nkro;
+        self.default_protocol = protocol;
+        self.protocol = protocol;
+        self.class_protocol = self.hid_class.get_protocol_mode().ok();
+        self.pending.clear();
     }
 
-    /// Gets a mutable reference to the last keyboard report.
-    pub fn last_report_mut(&mut self) -> &mut KeyboardReport {
-        &mut self.last_report
+    /// Gets whether the keyboard stores keycodes as an NKRO bitmap, see
+    /// [nkro_report](Self::nkro_report).
+    ///
+    /// Boot keyboards store up to six keycodes in an array instead.
+    pub fn is_nkro(&self) -> bool {
+        self.nkro
     }
 
     /// Gets a reference to the [HIDClass] for the USB bus.
-    pub fn hid_class(&self) -> &HIDClass<'k, KeyboardUsbBus> {
+    pub fn hid_class(&self) -> &HIDClass<'k, B> {
         &self.hid_class
     }
 
     /// Gets a mutable reference to the [HIDClass] for the USB bus.
-    pub fn hid_class_mut(&mut self) -> &mut HIDClass<'k, KeyboardUsbBus> {
+    pub fn hid_class_mut(&mut self) -> &mut HIDClass<'k, B> {
         &mut self.hid_class
     }
 
-    /// Gets the currently set protocol for the boot keyboard.
-    pub fn protocol(&self) -> HidProtocol {
+    /// Gets the currently set protocol for the keyboard.
+    pub fn protocol(&self) -> HidProtocolMode {
         self.protocol
     }
 
-    /// Sets the protocol for the boot keyboard.
-    pub fn set_protocol(&mut self, protocol: HidProtocol) {
+    /// Sets the protocol for the keyboard.
+    ///
+    /// Call this when the host requests a protocol change with `Set_Protocol`. An NKRO keyboard
+    /// sends 6-key reports while in [HidProtocolMode::Boot].
+    pub fn set_protocol(&mut self, protocol: HidProtocolMode) {
         self.protocol = protocol;
     }
 
-    /// Gets the default protocol for the boot keyboard.
-    pub fn default_protocol(&self) -> HidProtocol {
-        self.protocol
+    /// Sets the [ProtocolChangeHook] fired when the host changes the keyboard protocol.
+    pub fn set_protocol_change_hook(&mut self, hook: ProtocolChangeHook) {
+        self.on_protocol_change = Some(hook);
+    }
+
+    /// Applies protocol changes requested by the host with `Set_Protocol`.
+    ///
+    /// The [HIDClass] handles the control request during `UsbDevice::poll`. This reads the
+    /// protocol back from the class, and updates the keyboard protocol when the host changed it.
+    /// Manual [set_protocol](Self::set_protocol) calls are kept until the next host request.
+    ///
+    /// On a host protocol change, reports queued in the old format are dropped, the idle period
+    /// is restarted, and the last report is cleared, so the next `send_report` sends the held
+    /// keys in the new format.
+    ///
+    /// Returns true if the protocol changed. Called by [poll](Self::poll).
+    pub fn handle_control(&mut self) -> bool {
+        let class_protocol = self.hid_class.get_protocol_mode().ok();
+
+        if class_protocol == self.class_protocol {
+            return false;
+        }
+        self.class_protocol = class_protocol;
+
+        match class_protocol {
+            Some(protocol) if protocol != self.protocol => {
+                self.set_protocol(protocol);
+                self.pending.clear();
+                self.idle_ms = 0;
+                self.last_report = KeyboardReport {
+                    leds: self.last_report.leds,
+                    ..KeyboardReport::default()
+                };
+                self.last_nkro_keys = ZERO_NKRO_KEYS;
+                self.sent_report = self.last_report;
+                self.sent_nkro_keys = ZERO_NKRO_KEYS;
+
+                if let Some(hook) = self.on_protocol_change {
+                    hook(protocol);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Reads the keyboard LED state sent by the host in an output report.
+    ///
+    /// Hosts send the LEDs on the interrupt OUT endpoint, or with a `Set_Report` control request
+    /// handled by the [HIDClass] during `UsbDevice::poll`. The state is kept in the `leds` field
+    /// of the current and previous keyboard reports, see [LED_NUM_LOCK].
+    ///
+    /// Returns the new LED state if the host sent one, after notifying the
+    /// [OutputReportHook](crate::OutputReportHook) with [HIDReportId::Keyboard]. Called by
+    /// [poll](Self::poll).
+    pub fn read_leds(&mut self) -> Option<u8> {
+        let mut data = [0u8; 8];
+
+        let len = match self.hid_class.pull_raw_output(data.as_mut()) {
+            Ok(len) => len,
+            Err(_) => match self.hid_class.pull_raw_report(data.as_mut()) {
+                Ok(info) if info.report_type == ReportType::Output => info.len,
+                _ => 0,
+            },
+        };
+
+        // composite keyboards receive the LED state after the keyboard report ID
+        let leds = if self.report_ids { 1 } else { 0 };
+
+        if len > leds {
+            self.report.leds = data[leds];
+            self.last_report.leds = data[leds];
+            self.observer
+                .observe_output_report(HIDReportId::Keyboard, &data[leds..len]);
+            Some(data[leds])
+        } else {
+            None
+        }
+    }
+
+    /// Gets the default protocol for the keyboard.
+    pub fn default_protocol(&self) -> HidProtocolMode {
+        self.default_protocol
     }
 
     /// Switch back to default protocol after a USB reset event.
     pub fn on_usb_reset(&mut self) {
         self.protocol = self.default_protocol;
+        self.pending.clear();
     }
 
-    /// Gets the idle state of the boot keyboard.
-    pub fn idle(&self) -> u8 {
-        self.idle
+    /// Returns the keyboard to its initial state, e.g. on reconnect or after reloading the
+    /// configuration.
+    ///
+    /// Clears the current and last reports, including the media and system control reports, drops
+    /// queued reports, restores the default protocol, and resets the idle state. The host LED
+    /// state and the keyboard settings, e.g. the [HIDReportObserver], are kept. No report is sent.
+    ///
+    /// The protocol mode of the [HIDClass] is not changed. If the host switched the protocol
+    /// with `Set_Protocol`, the keyboard and the class disagree until the host switches it again.
+    /// Call [set_protocol](Self::set_protocol) with the [HIDClass] protocol to keep it instead.
+    pub fn reset(&mut self) {
+        let leds = self.last_report.leds;
+        self.report = KeyboardReport {
+            leds,
+            ..KeyboardReport::default()
+        };
+        self.last_report = self.report;
+        self.event_report = KeyboardReport::default();
+        self.sent_report = self.report;
+        self.nkro_keys = ZERO_NKRO_KEYS;
+        self.last_nkro_keys = ZERO_NKRO_KEYS;
+        self.event_nkro_keys = ZERO_NKRO_KEYS;
+        self.sent_nkro_keys = ZERO_NKRO_KEYS;
+        #[cfg(feature = "report-history")]
+        self.recent_reports.clear();
+        self.wrapped_modifiers = 0;
+        self.remapped_keypad = 0;
+        self.media_keys = [0; MEDIA_MAX_HELD_KEYS];
+        self.last_media_report = MediaKeyboardReport { usage_id: 0 };
+        self.system_control_report = SystemControlReport { usage_id: 0 };
+        self.last_system_control_report = SystemControlReport { usage_id: 0 };
+        self.idle = 0;
+        self.idle_ms = 0;
+        self.host_requests = 0;
+        self.on_usb_reset();
     }
 
-    /// Begin the keyboard reports (no-op by default).
-    pub fn begin(&self) {}
+    /// Gets the [UsbStrings] configured with [KeyboardBuilder::usb_strings].
+    pub fn usb_strings(&self) -> Option<UsbStrings<'static>> {
+        self.usb_strings
+    }
 
-    /// Perform USB device setup.
-    pub fn setup(&mut self) {}
+    /// Gets the USB vendor and product IDs configured with [KeyboardBuilder::vid_pid].
+    pub fn vid_pid(&self) -> Option<(u16, u16)> {
+        self.vid_pid
+    }
 
-    /// Release all keycodes registered in the current keyboard report.
-    pub fn release_all(&mut self) {
-        let report = self.report_mut();
+    /// Gets the [HIDReportObserver] notified of sent reports.
+    pub fn observer(&self) -> &HIDReportObserver {
+        &self.observer
+    }
 
-        report.modifier = 0;
-        report.keycodes.copy_from_slice(ZERO_KEYS.as_ref());
+    /// Gets a mutable reference to the [HIDReportObserver], e.g. to enable logging at runtime
+    /// with [set_hook](HIDReportObserver::set_hook).
+    pub fn observer_mut(&mut self) -> &mut HIDReportObserver {
+        &mut self.observer
     }
 
-    /// Gets whether the keycodes have changed between the last and current keyboard report.
-    pub fn keycodes_changed(&self) -> bool {
-        let mut changed = 0;
-        for (last, current) in self
-            .last_report()
-            .keycodes
-            .iter()
-            .zip(self.report().keycodes.iter())
-        {
-            changed |= last ^ current;
-        }
-        changed != 0
+    /// Creates a [UsbDeviceBuilder] with the configured VID/PID and [UsbStrings].
+    ///
+    /// Build the device after allocating all classes on the bus.
+    ///
+    /// Returns `None` if no VID/PID is configured with [KeyboardBuilder::vid_pid].
+    pub fn usb_device_builder(
+        &self,
+        bus: &'k KeyboardUsbBusAllocator<B>,
+    ) -> Option<UsbDeviceBuilder<'k, B>> {
+        let (vid, pid) = self.vid_pid?;
+        let builder = UsbDeviceBuilder::new(bus, UsbVidPid(vid, pid));
+
+        Some(match self.usb_strings {
+            Some(strings) => strings.apply(builder),
+            None => builder,
+        })
     }
 
-    /// Returns true if the modifer key passed in will be sent during this key report
-    /// Returns false in all other cases
-    pub fn is_modifier_active(&self, key: u8) -> bool {
-        is_modifier(key) && self.report.modifier & key_to_modifier_bitfield(key) != 0
+    /// Gets whether the host has configured the USB device.
+    ///
+    /// Reports pushed before the host configures the device are lost, or fail with `WouldBlock`
+    /// while the host enumerates it.
+    pub fn is_configured(&self, usb_dev: &UsbDevice<'_, B>) -> bool {
+        usb_dev.state() == UsbDeviceState::Configured
     }
 
-    /// Returns true if the modifer key passed in was being sent during the previous key report
-    /// Returns false in all other cases
-    pub fn was_modifier_active(&self, key: u8) -> bool {
-        is_modifier(key) && self.last_report.modifier & key_to_modifier_bitfield(key) != 0
+    /// Records the state of the USB device, gating every report send on it.
+    ///
+    /// Call this after polling the USB device. Until the device is configured, or while it is
+    /// suspended, the boot, NKRO, media, and system control sends return
+    /// [KeyboardError::NotConfigured] without pushing, and [poll](Self::poll) keeps the queued
+    /// reports. The sends are not gated before the first call.
+    pub fn update_device_state(&mut self, usb_dev: &UsbDevice<'_, B>) {
+        self.device_state = Some(usb_dev.state());
     }
 
-    /// Returns true if *any* modifier will be sent during this key report
-    /// Returns false in all other cases
-    pub fn is_any_modifier_active(&self) -> bool {
-        self.report.modifier > 0
+    /// Gets the USB device state recorded by [update_device_state](Self::update_device_state).
+    pub fn device_state(&self) -> Option<UsbDeviceState> {
+        self.device_state
     }
 
-    /// Returns true if *any* modifier was being sent during the previous key report
-    /// Returns false in all other cases
-    pub fn was_any_modifier_active(&self) -> bool {
-        self.last_report.modifier > 0
+    // Refuses to send while the recorded device state is not configured, see
+    // `update_device_state`. Checked before a send changes any report state.
+    pub(crate) fn check_device_state(&self) -> KeyboardResult<()> {
+        match self.device_state {
+            Some(state) if state != UsbDeviceState::Configured => Err(KeyboardError::NotConfigured),
+            _ => Ok(()),
+        }
     }
 
-    /// Gets the number of LEDs in the current keyboard report.
-    pub fn leds(&self) -> u8 {
-        self.report.leds
+    /// Sends the current keyboard report, if the host has configured the USB device.
+    ///
+    /// Returns [KeyboardError::NotConfigured] without pushing the report otherwise. The report is
+    /// kept, and sent by the next call once the device is configured.
+    pub fn send_report_configured(&mut self, usb_dev: &UsbDevice<'_, B>) -> KeyboardResult<()> {
+        if !self.is_configured(usb_dev) {
+            return Err(KeyboardError::NotConfigured);
+        }
+
+        KeyboardOps::send_report(self)
+    }
+
+    /// Gets the idle rate of the boot keyboard, in the 4 ms units of the HID `Set_Idle` request.
+    ///
+    /// Zero is an indefinite idle rate, the report is only sent on changes. Follows the host's
+    /// `Set_Idle` requests when the [Keyboard] is passed to `UsbDevice::poll`.
+    pub fn idle(&self) -> u8 {
+        self.idle
+    }
+
+    /// Sets the idle rate of the boot keyboard, in the 4 ms units of the HID `Set_Idle` request.
+    ///
+    /// Restarts the idle period, see [tick_idle](Self::tick_idle).
+    pub fn set_idle_rate(&mut self, units: u8) {
+        self.idle = units;
+        self.idle_ms = 0;
+    }
+
+    /// Gets the USB interface number of the keyboard [HIDClass].
+    pub fn interface_number(&self) -> u8 {
+        self.interface_number
+    }
+
+    /// Sets the USB interface number of the keyboard [HIDClass], the number of interfaces
+    /// allocated on the USB bus before it.
+    ///
+    /// Host `Set_Idle` requests only set the [idle](Self::idle) rate when sent to this
+    /// interface. Defaults to 0, for a keyboard allocated before any other USB class.
+    pub fn set_interface_number(&mut self, interface_number: u8) {
+        self.interface_number = interface_number;
+    }
+
+    /// Gets the idle rate of the boot keyboard in milliseconds, zero for an indefinite rate.
+    pub fn idle_rate_ms(&self) -> u16 {
+        self.idle as u16 * 4
+    }
+
+    /// Advances the idle clock by the elapsed milliseconds.
+    ///
+    /// Resends the current keyboard report when no keyboard report was sent for the idle rate,
+    /// and returns whether it was resent. Never resends with an indefinite idle rate. The idle
+    /// clock saturates at `u32::MAX` instead of wrapping around.
+    pub fn tick_idle(&mut self, elapsed_ms: u32) -> KeyboardResult<bool> {
+        let rate_ms = self.idle_rate_ms() as u32;
+        if rate_ms == 0 {
+            return Ok(false);
+        }
+
+        self.idle_ms = self.idle_ms.saturating_add(elapsed_ms);
+        if self.idle_ms < rate_ms {
+            return Ok(false);
+        }
+
+        // restart the idle period even if the device is unconfigured, or the send fails
+        self.idle_ms = 0;
+        self.flush().map(|_| true)
+    }
+
+    /// Gets the maximum number of keycodes pressed at once on a boot keyboard.
+    pub fn max_rollover(&self) -> usize {
+        self.max_rollover
+    }
+
+    /// Sets the maximum number of keycodes pressed at once on a boot keyboard.
+    ///
+    /// The limit is clamped to the six keycode slots of the boot report. Lower limits help with
+    /// hosts, e.g. some BIOSes, that handle fewer keys. Keys pressed over the limit are dropped.
+    pub fn set_max_rollover(&mut self, max_rollover: usize) {
+        self.max_rollover = max_rollover.clamp(1, ZERO_KEYS.len());
+    }
+
+    /// Press a key, and add it to the current report.
+    ///
+    /// Returns [KeyboardError::InvalidKey] for keys that are neither printable nor modifiers, and
+    /// [KeyboardError::RolloverFull] if a boot keyboard has [max_rollover](Self::max_rollover)
+    /// keys pressed.
+    pub fn try_press(&mut self, key: u8) -> KeyboardResult<()> {
+        if !is_printable(key) && !is_modifier(key) {
+            return Err(KeyboardError::InvalidKey(key));
+        }
+
+        match KeyboardOps::press(self, key) {
+            0 => Err(KeyboardError::RolloverFull),
+            _ => Ok(()),
+        }
+    }
+
+    /// Press a printable key, and add it to the current report, e.g. for typing into a text
+    /// field.
+    ///
+    /// Returns [KeyboardError::InvalidKey] for modifiers and other non-printable keys, without
+    /// pressing them. Otherwise behaves like [try_press](Self::try_press).
+    pub fn press_printable_only(&mut self, key: u8) -> KeyboardResult<()> {
+        if !is_printable(key) {
+            return Err(KeyboardError::InvalidKey(key));
+        }
+
+        self.try_press(key)
+    }
+
+    /// Press a modifier key, and add it to the current report.
+    ///
+    /// Returns [KeyboardError::InvalidKey] for non-modifier keys, without pressing them.
+    pub fn press_modifier_only(&mut self, key: u8) -> KeyboardResult<()> {
+        if !is_modifier(key) {
+            return Err(KeyboardError::InvalidKey(key));
+        }
+
+        self.try_press(key)
+    }
+
+    /// Removes a key from the current keyboard report, without sending the report.
+    pub fn clear_key(&mut self, key: u8) {
+        KeyboardOps::release(self, key);
+    }
+
+    /// Flips the presence of a key in the current keyboard report, without sending the report.
+    ///
+    /// Presses the key if it is released, and releases it if it is pressed, e.g. for latching
+    /// sticky modifiers regardless of the physical key state.
+    ///
+    /// Returns the result of the press or release.
+    pub fn toggle(&mut self, key: u8) -> usize {
+        let pressed = if is_modifier(key) {
+            self.report.modifier & key_to_modifier_bitfield(key) != 0
+        } else {
+            self.is_key_pressed(key)
+        };
+
+        if pressed {
+            KeyboardOps::release(self, key)
+        } else {
+            KeyboardOps::press(self, key)
+        }
+    }
+
+    /// Gets whether the provided key is pressed in the current keyboard report.
+    pub fn is_key_pressed(&self, key: u8) -> bool {
+        if self.nkro {
+            NKROKeyboard::is_key_pressed(self, key)
+        } else {
+            BootKeyboard::is_key_pressed(self, key)
+        }
+    }
+
+    /// Gets whether the provided key was pressed in the previous keyboard report.
+    pub fn was_key_pressed(&self, key: u8) -> bool {
+        if self.nkro {
+            NKROKeyboard::was_key_pressed(self, key)
+        } else {
+            BootKeyboard::was_key_pressed(self, key)
+        }
+    }
+
+    /// Press a key together with the provided modifier bitfield, e.g. `LSHIFT(Key_4)`.
+    ///
+    /// Modifiers that were not already active are released again by
+    /// [release_with_modifiers](Self::release_with_modifiers). `send_report` sends the modifier
+    /// change before the key, so the host sees the shifted key.
+    ///
+    /// Returns 1 if the key was added to the current report, 0 otherwise.
+    pub fn press_with_modifiers(&mut self, key: u8, modifiers: u8) -> usize {
+        let pressed = KeyboardOps::press(self, key);
+
+        if pressed != 0 {
+            self.wrapped_modifiers |= modifiers & !self.report.modifier;
+            self.report.modifier |= modifiers;
+        }
+
+        pressed
+    }
+
+    /// Release a key pressed with [press_with_modifiers](Self::press_with_modifiers).
+    ///
+    /// Restores the modifier state from before the key was pressed, modifiers that were already
+    /// active stay pressed.
+    pub fn release_with_modifiers(&mut self, key: u8, modifiers: u8) -> usize {
+        let released = modifiers & self.wrapped_modifiers;
+
+        self.wrapped_modifiers &= !released;
+        self.report.modifier &= !released;
+
+        KeyboardOps::release(self, key)
+    }
+
+    /// Press a key by its [KeyboardUsage], and add it to the current report.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use keyboardio_hid::usb_device::bus::{UsbBus, UsbBusAllocator};
+    /// use keyboardio_hid::usbd_hid::descriptor::KeyboardUsage;
+    /// use keyboardio_hid::Keyboard;
+    ///
+    /// fn press_a<B: UsbBus>(bus: &UsbBusAllocator<B>) {
+    ///     let mut keyboard = Keyboard::new_boot(bus);
+    ///
+    ///     keyboard.press_usage(KeyboardUsage::KeyboardAa);
+    ///     assert!(keyboard.is_key_pressed(KeyboardUsage::KeyboardAa as u8));
+    /// }
+    /// ```
+    ///
+    /// Returns 1 if the key was added to the current report, 0 otherwise.
+    pub fn press_usage(&mut self, usage: KeyboardUsage) -> usize {
+        KeyboardOps::press(self, usage.keycode())
+    }
+
+    /// Release a pressed key by its [KeyboardUsage].
+    pub fn release_usage(&mut self, usage: KeyboardUsage) -> usize {
+        KeyboardOps::release(self, usage.keycode())
+    }
+
+    /// Press and release a key by its [KeyboardUsage], see [KeyboardOps::tap].
+    pub fn tap_usage(&mut self, usage: KeyboardUsage) -> KeyboardResult<()> {
+        KeyboardOps::tap(self, usage.keycode())
+    }
+
+    /// Gets whether the provided [KeyboardUsage] is pressed in the current keyboard report.
+    ///
+    /// Modifier usages are checked against the report modifiers.
+    pub fn is_usage_pressed(&self, usage: KeyboardUsage) -> bool {
+        let key = usage.keycode();
+
+        if is_modifier(key) {
+            self.is_modifier_active(key)
+        } else {
+            self.is_key_pressed(key)
+        }
+    }
+
+    /// Gets whether the provided [KeyboardUsage] was pressed in the previous keyboard report.
+    ///
+    /// Modifier usages are checked against the report modifiers.
+    pub fn was_usage_pressed(&self, usage: KeyboardUsage) -> bool {
+        let key = usage.keycode();
+
+        if is_modifier(key) {
+            self.was_modifier_active(key)
+        } else {
+            self.was_key_pressed(key)
+        }
+    }
+
+    /// Gets the number of non-modifier keys pressed in the current keyboard report.
+    pub fn count_pressed_keys(&self) -> u8 {
+        let keys = self.report.keycodes.iter().filter(|&&k| k != 0).count() as u8;
+        let nkro_keys: u8 = self.nkro_keys.iter().map(|k| k.count_ones() as u8).sum();
+
+        keys + nkro_keys
+    }
+
+    /// Gets the number of reports waiting to be sent by [poll](Self::poll).
+    pub fn pending_reports(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Sends a boot or NKRO keyboard report to the host, queueing it if the IN endpoint is busy.
+    ///
+    /// Queued reports are sent in order by [poll](Self::poll). Returns `Ok(0)` if the report was
+    /// queued, and [UsbError::WouldBlock] if the queue is full.
+    pub(crate) fn push_report(&mut self, report: HIDReport) -> Result<usize> {
+        if self.pending.is_empty() {
+            match self.push_keyboard_input(&report) {
+                Err(UsbError::WouldBlock) => (),
+                res => return res,
+            }
+        }
+
+        self.pending
+            .push_back(report)
+            .map(|_| 0)
+            .map_err(|_| UsbError::WouldBlock)
+    }
+
+    // Pushes a boot or NKRO keyboard report, the only reports queued by `push_report`.
+    fn push_keyboard_input(&self, report: &HIDReport) -> Result<usize> {
+        match report {
+            HIDReport::Keyboard(r) => self.push_input(HIDReportId::Keyboard, r),
+            HIDReport::NKROKeyboard(r) => self.push_input(HIDReportId::NKROKeyboard, r),
+            _ => Err(UsbError::Unsupported),
+        }
+    }
+
+    // Pushes an input report to the host, prefixed with the report ID on composite keyboards.
+    pub(crate) fn push_input<R: AsInputReport>(
+        &self,
+        id: HIDReportId,
+        report: &R,
+    ) -> Result<usize> {
+        if !self.report_ids {
+            return self.hid_class.push_input(report);
+        }
+
+        let mut buf = [0u8; 64];
+        buf[0] = id.into();
+        let len =
+            ssmarshal::serialize(&mut buf[1..], report).map_err(|_| UsbError::BufferOverflow)?;
+
+        self.hid_class.push_raw_input(&buf[..len + 1])
+    }
+
+    // Marks the current keyboard report as sent.
+    //
+    // Every keyboard send path ends here. The three-phase sends stage intermediate reports in
+    // `last_report`, committing afterwards keeps it equal to `report` once the send is done. The
+    // media and system control sends only mark their own report, so a composite keyboard keeps
+    // the changes of the other reports for their next send.
+    pub(crate) fn commit_report(&mut self) {
+        self.last_report = self.report;
+        self.sent_report = self.report;
+        self.last_nkro_keys = self.nkro_keys;
+        self.sent_nkro_keys = self.nkro_keys;
+        self.idle_ms = 0;
+    }
+
+    // Gets whether `last_report` still has the modifiers and keycodes left by the last send.
+    //
+    // LED state is read into `last_report` between sends, so it is not compared.
+    fn last_report_synced(&self) -> bool {
+        self.last_report.modifier == self.sent_report.modifier
+            && self.last_report.keycodes == self.sent_report.keycodes
+            && self.last_nkro_keys == self.sent_nkro_keys
+    }
+
+    /// Sends the current keyboard report to the host, even if it is unchanged.
+    ///
+    /// Useful to refresh the host's state, e.g. after reconnecting. Applies to boot and NKRO
+    /// keyboards, following the current protocol.
+    pub fn flush(&mut self) -> KeyboardResult<()> {
+        self.check_device_state()?;
+        self.commit_report();
+
+        if self.nkro && self.protocol == HidProtocolMode::Report {
+            return self.send_last_nkro_report();
+        }
+
+        self.push_boot_report()
+    }
+
+    /// Replaces the last keyboard report, the change detection baseline, without sending it.
+    ///
+    /// Use this to resynchronize with the host, e.g. after the host state was reset outside of
+    /// the keyboard. The next [send_report](KeyboardOps::send_report) sends the changes between
+    /// `report` and the current report.
+    ///
+    /// The keycodes of `report` are a boot array, an NKRO keyboard stores them in its bitmap.
+    pub fn resync_last_report(&mut self, report: KeyboardReport) {
+        self.last_report = report;
+        self.last_nkro_keys = ZERO_NKRO_KEYS;
+        if self.nkro {
+            convert_keycodes(
+                &mut self.last_report.keycodes,
+                &mut self.last_nkro_keys,
+                true,
+            );
+        }
+
+        self.sent_report = self.last_report;
+        self.sent_nkro_keys = self.last_nkro_keys;
+    }
+
+    /// Replaces the current keyboard report, and sends it to the host as a single report, e.g. for
+    /// replaying recorded reports in macros or host-side tests.
+    ///
+    /// Unlike [set_report](KeyboardOps::set_report) followed by `send_report`, no intermediate
+    /// report is sent for modifier changes. Sends like [flush](Self::flush), following the
+    /// current protocol, so an NKRO report is sent as a bitmap in report protocol.
+    ///
+    /// The keycodes of `report` are a boot array, see [set_report](KeyboardOps::set_report).
+    pub fn inject_report(&mut self, report: KeyboardReport) -> KeyboardResult<()> {
+        KeyboardOps::set_report(self, report);
+        self.flush()
+    }
+
+    /// Sends the current keyboard report as a 6-key boot protocol report, e.g. for a BIOS.
+    ///
+    /// The bitmap of an NKRO keyboard is converted with
+    /// [nkro_to_boot_report](nkro::nkro_to_boot_report), setting every keycode to `ErrorRollOver`
+    /// if more than six keys are pressed. The report is sent even if it is unchanged, and the last
+    /// report is not updated.
+    pub fn push_boot_report(&mut self) -> KeyboardResult<()> {
+        self.check_device_state()?;
+
+        let report = if self.nkro {
+            KeyboardReport {
+                leds: self.report.leds,
+                ..nkro::nkro_to_boot_report(&self.nkro_report())
+            }
+        } else {
+            self.report
+        };
+        let report = HIDReport::Keyboard(report);
+        // replace the Ok(usize) with Ok(())
+        let ret = self.push_report(report).map(|_| ());
+        self.observe_report(HIDReportId::Keyboard, report, &ret);
+
+        ret.map_err(KeyboardError::from)
+    }
+
+    // Notifies the observer of a sent report, and records it in the recent reports.
+    pub(crate) fn observe_report(&mut self, id: HIDReportId, report: HIDReport, ret: &Result<()>) {
+        #[cfg(feature = "report-history")]
+        if ret.is_ok() {
+            self.recent_reports.push(report);
+        }
+
+        self.observer.observe_report(id, report, ret);
+    }
+
+    /// Gets the most recently sent reports, from oldest to newest.
+    ///
+    /// Keeps the last [RECENT_REPORTS_LEN] keyboard, media, and system control reports handed to
+    /// the USB stack, e.g. for dumping with [dump_report](crate::dump_report) after a phantom
+    /// keypress. Cleared by [reset](Self::reset).
+    #[cfg(feature = "report-history")]
+    pub fn recent_reports(&self) -> impl Iterator<Item = &HIDReport> {
+        self.recent_reports.iter()
+    }
+
+    /// Sends reports queued while the IN endpoint was busy.
+    ///
+    /// Call this regularly from the main loop, e.g. after polling the USB device. Applies host
+    /// protocol changes and LED state first, see [handle_control](Self::handle_control) and
+    /// [read_leds](Self::read_leds).
+    pub fn poll(&mut self) -> KeyboardResult<()> {
+        self.handle_control();
+        self.read_leds();
+
+        if self.check_device_state().is_err() {
+            return Ok(());
+        }
+
+        while let Some(report) = self.pending.front() {
+            match self.push_keyboard_input(report) {
+                Ok(_) => {
+                    self.pending.pop_front();
+                }
+                Err(UsbError::WouldBlock) => return Ok(()),
+                Err(err) => {
+                    self.pending.pop_front();
+                    return Err(err.into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets an iterator over the non-modifier keys pressed in the current keyboard report.
+    pub fn pressed_keys(&self) -> impl Iterator<Item = u8> {
+        let keys = self.report.keycodes.into_iter().filter(|&key| key != 0);
+
+        keys.chain(nkro::bitmap_keys(self.nkro_keys))
+    }
+
+    /// Gets an iterator over the modifier keys set in the current keyboard report.
+    ///
+    /// Yields the usages from Left Control to Right GUI, in the order of the modifier bits.
+    pub fn pressed_modifiers(&self) -> impl Iterator<Item = KeyboardUsage> {
+        let modifiers = self.report.modifier;
+
+        (0..8u8)
+            .filter(move |bit| modifiers & (1 << bit) != 0)
+            .map(|bit| KeyboardUsage::from(KeyboardUsage::KeyboardLeftControl as u8 + bit))
+    }
+
+    /// Gets a copy of the current keyboard report, with the keys pressed and held so far.
+    ///
+    /// The report is not sent. Useful to inspect or compare the pending state, e.g. with
+    /// [reports_equal], before sending it.
+    pub fn press_and_hold_report(&self) -> KeyboardReport {
+        self.report
+    }
+
+    /// Gets the keys changed between the previous and current keyboard reports.
+    ///
+    /// Returns iterators over the newly pressed and newly released keys, in that order.
+    /// Modifier keys are included after the non-modifier keys.
+    pub fn report_diff(&self) -> (impl Iterator<Item = u8>, impl Iterator<Item = u8>) {
+        (
+            keys_added(
+                (self.last_report, self.last_nkro_keys),
+                (self.report, self.nkro_keys),
+            ),
+            keys_added(
+                (self.report, self.nkro_keys),
+                (self.last_report, self.last_nkro_keys),
+            ),
+        )
+    }
+
+    /// Begin the keyboard reports (no-op by default).
+    pub fn begin(&self) {}
+
+    /// Perform USB device setup.
+    pub fn setup(&mut self) {}
+}
+
+// Formats the pressed keycodes of a [Keyboard] as a list.
+struct PressedKeys<'a, 'k, B: UsbBus>(&'a Keyboard<'k, B>);
+
+impl<B: UsbBus> fmt::Debug for PressedKeys<'_, '_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.pressed_keys()).finish()
+    }
+}
+
+impl<B: UsbBus> fmt::Debug for Keyboard<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keyboard")
+            .field("protocol", &self.protocol)
+            .field("idle", &self.idle)
+            .field("nkro", &self.nkro)
+            .field("modifier", &format_args!("{:#04x}", self.report.modifier))
+            .field("keycodes", &PressedKeys(self))
+            .field("pending_reports", &self.pending.len())
+            .finish()
+    }
+}
+
+impl<B: UsbBus> KeyboardOps for Keyboard<'_, B> {
+    fn report(&self) -> &KeyboardReport {
+        &self.report
+    }
+
+    fn report_mut(&mut self) -> &mut KeyboardReport {
+        &mut self.report
+    }
+
+    fn last_report(&self) -> &KeyboardReport {
+        &self.last_report
+    }
+
+    fn last_report_mut(&mut self) -> &mut KeyboardReport {
+        &mut self.last_report
+    }
+
+    fn set_modifiers(&mut self, modifiers: u8) {
+        // the modifiers wrapped by `press_with_modifiers` are replaced
+        self.wrapped_modifiers = 0;
+        self.report.modifier = modifiers;
+    }
+
+    /// Sets the current keyboard report.
+    ///
+    /// The keycodes of `report` are a boot array, an NKRO keyboard stores them in its bitmap.
+    fn set_report(&mut self, report: KeyboardReport) {
+        self.report = report;
+        self.nkro_keys = ZERO_NKRO_KEYS;
+        if self.nkro {
+            convert_keycodes(&mut self.report.keycodes, &mut self.nkro_keys, true);
+        }
+    }
+
+    fn release_all(&mut self) {
+        self.wrapped_modifiers = 0;
+        self.report.modifier = 0;
+        self.report.keycodes.copy_from_slice(ZERO_KEYS.as_ref());
+        self.nkro_keys = ZERO_NKRO_KEYS;
+    }
+
+    fn release_keys_only(&mut self) {
+        self.report.keycodes.copy_from_slice(ZERO_KEYS.as_ref());
+        self.nkro_keys = ZERO_NKRO_KEYS;
+    }
+
+    fn report_is_empty(&self) -> bool {
+        self.report.modifier == 0 && self.pressed_keys().next().is_none()
+    }
+
+    fn keycodes_changed(&self) -> bool {
+        self.last_report.keycodes != self.report.keycodes || self.last_nkro_keys != self.nkro_keys
+    }
+
+    fn press(&mut self, key: u8) -> usize {
+        let resolved = self.resolve_keypad(key);
+
+        let pressed = if self.nkro {
+            NKROKeyboard::press(self, resolved)
+        } else {
+            BootKeyboard::press(self, resolved)
+        };
+
+        if pressed != 0 {
+            self.record_keypad_press(key, resolved);
+        }
+        pressed
+    }
+
+    fn release(&mut self, key: u8) -> usize {
+        // Num Lock may have changed since the key was pressed, release the usage it was pressed
+        // with
+        let key = self.take_keypad_release(key);
+
+        if self.nkro {
+            NKROKeyboard::release(self, key)
+        } else {
+            BootKeyboard::release(self, key)
+        }
+    }
+
+    fn send_report(&mut self) -> KeyboardResult<()> {
+        debug_assert!(
+            self.last_report_synced(),
+            "last_report was modified outside of send_report"
+        );
+
+        let ret = if self.nkro {
+            NKROKeyboard::send_report(self)
+        } else {
+            BootKeyboard::send_report(self)
+        };
+        // a failed send leaves the staged report in `last_report`
+        self.sent_report = self.last_report;
+        self.sent_nkro_keys = self.last_nkro_keys;
+
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::test::{
+        assert_nkro_report_sequence, assert_send_gated, mock_usb_device, MockUsbBus,
+    };
+
+    const _: () = assert!(is_media_const(MediaKey::PlayPause as u8));
+    const _: () = assert!(!is_media_const(MediaKey::Reserved as u8));
+    const _: () = assert!(is_system_control_const(SystemControlKey::Sleep as u8));
+    const _: () = assert!(!is_system_control_const(SystemControlKey::Reserved as u8));
+
+    #[test]
+    fn test_const_key_classification() {
+        for key in 0..=u8::MAX {
+            assert_eq!(is_media_const(key), is_media(key), "media key: {key:#04x}");
+            assert_eq!(
+                is_system_control_const(key),
+                is_system_control(key),
+                "system control key: {key:#04x}"
+            );
+        }
+    }
+
+    fn assert_constructed<B: UsbBus>(keyboard: &Keyboard<'_, B>) {
+        assert_eq!(keyboard.report().modifier, 0);
+        assert_eq!(keyboard.report().keycodes, ZERO_KEYS);
+        assert_eq!(keyboard.idle(), 0);
+        assert!(!keyboard.is_any_modifier_active());
+        assert!(keyboard.report_is_empty());
+    }
+
+    #[test]
+    fn test_keyboard_modes_construct() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+
+        let boot = Keyboard::new_boot(&alloc);
+        let nkro = Keyboard::new_nkro(&alloc);
+        let media = Keyboard::new_media(&alloc);
+        let system_control = Keyboard::new_system_control(&alloc);
+        let observed = Keyboard::new_with_observer(&alloc, HIDReportObserver::default());
+
+        let _usb_dev = mock_usb_device(&alloc);
+
+        for keyboard in [&boot, &nkro, &media, &system_control, &observed] {
+            assert_constructed(keyboard);
+        }
+
+        assert_eq!(boot.protocol(), HidProtocolMode::Boot);
+        assert_eq!(nkro.protocol(), HidProtocolMode::Report);
+        assert_eq!(nkro.default_protocol(), HidProtocolMode::Report);
+    }
+
+    #[test]
+    fn test_observer_default_trait() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let observer: HIDReportObserver = Default::default();
+        let mut keyboard = Keyboard::new_with_observer(&alloc, observer);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        assert_constructed(&keyboard);
+
+        // the default observer only has the no-op send report hook
+        assert!(keyboard.observer.hook().is_some());
+        assert!(keyboard.observer.output_report_hook().is_none());
+        assert!(keyboard.observer.rollover_overflow_hook().is_none());
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+    }
+
+    #[test]
+    fn test_observer_mut_swaps_hook() {
+        static SENT: AtomicUsize = AtomicUsize::new(0);
+
+        fn count_sent(_id: HIDReportId, _report: HIDReport, _result: &Result<()>) {
+            SENT.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        KeyboardOps::tap(&mut keyboard, KeyboardUsage::KeyboardAa as u8).unwrap();
+        assert_eq!(SENT.load(Ordering::SeqCst), 0);
+
+        keyboard.observer_mut().set_hook(count_sent);
+        assert!(keyboard.observer().hook().is_some());
+
+        KeyboardOps::tap(&mut keyboard, KeyboardUsage::KeyboardAa as u8).unwrap();
+        assert_eq!(SENT.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_observer_send_error_name() {
+        static SEND_ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+        fn count_send_errors(_id: HIDReportId, _report: HIDReport, result: &Result<()>) {
+            if let Some(name) = crate::send_error_name(result) {
+                assert_eq!(name, "InvalidEndpoint");
+                SEND_ERRORS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+        keyboard.observer_mut().set_hook(count_send_errors);
+
+        KeyboardOps::tap(&mut keyboard, KeyboardUsage::KeyboardAa as u8).unwrap();
+        assert_eq!(SEND_ERRORS.load(Ordering::SeqCst), 0);
+
+        usb_dev.bus().fail_next_write(UsbError::InvalidEndpoint);
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardBb as u8);
+        assert!(KeyboardOps::send_report(&mut keyboard).is_err());
+        assert_eq!(SEND_ERRORS.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "defmt")]
+    #[test]
+    fn test_default_observer_logs_send_error() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        KeyboardOps::tap(&mut keyboard, KeyboardUsage::KeyboardAa as u8).unwrap();
+        assert_eq!(crate::test::defmt_logged(), 0);
+
+        usb_dev.bus().fail_next_write(UsbError::InvalidEndpoint);
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardBb as u8);
+        assert!(KeyboardOps::send_report(&mut keyboard).is_err());
+        assert!(crate::test::defmt_logged() > 0);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "last_report was modified outside of send_report")]
+    fn test_last_report_mut_desync() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardLeftShift as u8);
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        // LED state in the last report is not part of change detection
+        keyboard.last_report_mut().leds = LED_CAPS_LOCK;
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        keyboard.last_report_mut().modifier = 0;
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+    }
+
+    #[test]
+    fn test_resync_last_report() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        // the host lost the pressed key, e.g. after a KVM switch
+        keyboard.resync_last_report(KeyboardReport::default());
+        assert!(keyboard.report_changed());
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[cfg(feature = "report-history")]
+    #[test]
+    fn test_recent_reports() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+        let sent = RECENT_REPORTS_LEN + 4;
+
+        for key in key_a..key_a + sent as u8 {
+            KeyboardOps::press(&mut keyboard, key);
+            KeyboardOps::send_report(&mut keyboard).unwrap();
+        }
+
+        // only the latest reports are kept, oldest first
+        let pressed = keyboard.recent_reports().map(|report| match report {
+            HIDReport::NKROKeyboard(report) => report.keycodes.iter().map(|k| k.count_ones()).sum(),
+            _ => 0,
+        });
+        assert!(pressed.eq(5..=sent as u32));
+
+        keyboard.reset();
+        assert_eq!(keyboard.recent_reports().count(), 0);
+    }
+
+    #[test]
+    fn test_keyboard_mode_reinit() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        keyboard.init_nkro(&alloc);
+        keyboard.init_media(&alloc);
+        keyboard.init_system_control(&alloc);
+        keyboard.init_boot(&alloc);
+
+        let _usb_dev = mock_usb_device(&alloc);
+
+        assert_constructed(&keyboard);
+    }
+
+    #[test]
+    fn test_keyboard_mode_conversion() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+        let key_z = KeyboardUsage::KeyboardZz as u8;
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        for key in [key_a, key_z, shift] {
+            KeyboardOps::press(&mut keyboard, key);
+        }
+
+        keyboard.init_nkro(&alloc);
+        assert!(keyboard.is_nkro());
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Report);
+        assert!(keyboard.pressed_keys().eq([key_a, key_z]));
+        assert!(keyboard.is_modifier_active(shift));
+
+        keyboard.init_media(&alloc);
+        assert!(!keyboard.is_nkro());
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Boot);
+        assert!(keyboard.pressed_keys().eq([key_a, key_z]));
+
+        keyboard.init_nkro(&alloc);
+        keyboard.init_system_control(&alloc);
+        assert!(keyboard.pressed_keys().eq([key_a, key_z]));
+
+        keyboard.init_boot(&alloc);
+        assert!(keyboard.pressed_keys().eq([key_a, key_z]));
+        assert!(keyboard.is_key_pressed(key_z));
+
+        let _usb_dev = mock_usb_device(&alloc);
+    }
+
+    #[test]
+    fn test_clear_key() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut boot = Keyboard::new_boot(&alloc);
+        let mut nkro = Keyboard::new_nkro(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+        let key_b = KeyboardUsage::KeyboardBb as u8;
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+
+        for keyboard in [&mut boot, &mut nkro] {
+            KeyboardOps::press(keyboard, key_a);
+            KeyboardOps::press(keyboard, key_b);
+            KeyboardOps::press(keyboard, shift);
+            assert!(!keyboard.report_is_empty());
+
+            keyboard.clear_key(key_a);
+            keyboard.clear_key(shift);
+            assert!(!keyboard.report_is_empty());
+            assert!(!keyboard.is_any_modifier_active());
+
+            keyboard.clear_key(key_b);
+            assert!(keyboard.report_is_empty());
+        }
+
+        // boot keyboards store keycodes as an array
+        KeyboardOps::press(&mut boot, key_b);
+        assert_eq!(boot.report().keycodes, [key_b, 0, 0, 0, 0, 0]);
+
+        // NKRO keyboards store keycodes as a bitmap
+        KeyboardOps::press(&mut nkro, key_b);
+        assert_eq!(nkro.nkro_report().keycodes[..2], [0b0010_0000, 0]);
+        assert_eq!(nkro.report().keycodes, ZERO_KEYS);
+    }
+
+    #[test]
+    fn test_count_pressed_keys() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut boot = Keyboard::new_boot(&alloc);
+        let mut nkro = Keyboard::new_nkro(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let keys = [
+            KeyboardUsage::KeyboardAa as u8,
+            KeyboardUsage::KeyboardBb as u8,
+            KeyboardUsage::KeyboardLeftShift as u8,
+            KeyboardUsage::KeyboardCc as u8,
+            KeyboardUsage::KeyboardRightAlt as u8,
+        ];
+
+        for keyboard in [&mut boot, &mut nkro] {
+            assert_eq!(keyboard.count_pressed_keys(), 0);
+            assert_eq!(keyboard.count_active_modifiers(), 0);
+
+            for &key in keys.iter() {
+                KeyboardOps::press(keyboard, key);
+            }
+
+            assert_eq!(keyboard.count_pressed_keys(), 3);
+            assert_eq!(keyboard.count_active_modifiers(), 2);
+
+            keyboard.clear_key(KeyboardUsage::KeyboardBb as u8);
+            keyboard.clear_key(KeyboardUsage::KeyboardRightAlt as u8);
+
+            assert_eq!(keyboard.count_pressed_keys(), 2);
+            assert_eq!(keyboard.count_active_modifiers(), 1);
+            assert!(keyboard.pressed_keys().eq([
+                KeyboardUsage::KeyboardAa as u8,
+                KeyboardUsage::KeyboardCc as u8
+            ]));
+        }
+    }
+
+    #[test]
+    fn test_send_report_retries_would_block() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        usb_dev.bus().fail_next_write(UsbError::WouldBlock);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+        assert!(usb_dev.bus().in_reports().is_empty());
+        assert_eq!(keyboard.pending_reports(), 1);
+
+        keyboard.poll().unwrap();
+        assert_eq!(keyboard.pending_reports(), 0);
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00]]
+        );
+    }
+
+    #[test]
+    fn test_try_press_errors() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut boot = Keyboard::new_boot(&alloc);
+        let mut nkro = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+        for key in key_a..key_a + 6 {
+            boot.try_press(key).unwrap();
+        }
+        assert!(matches!(
+            boot.try_press(key_a + 6),
+            Err(KeyboardError::RolloverFull)
+        ));
+
+        // every printable key fits in the NKRO bitmap
+        let key_f12 = KeyboardUsage::KeyboardF12 as u8;
+        nkro.try_press(key_f12).unwrap();
+        assert!(nkro.is_key_pressed(key_f12));
+
+        usb_dev.bus().fail_next_write(UsbError::InvalidEndpoint);
+        assert!(matches!(
+            KeyboardOps::send_report(&mut boot),
+            Err(KeyboardError::Usb(UsbError::InvalidEndpoint))
+        ));
+    }
+
+    #[test]
+    fn test_try_press_invalid_key() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut boot = Keyboard::new_boot(&alloc);
+        let mut nkro = Keyboard::new_nkro(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        // no key, error codes, and reserved keys are invalid, not a full rollover
+        for keyboard in [&mut boot, &mut nkro] {
+            for key in [0x00, KeyboardUsage::KeyboardErrorRollOver as u8, 0x03, 0xa5, 0xaf] {
+                assert!(matches!(
+                    keyboard.try_press(key),
+                    Err(KeyboardError::InvalidKey(k)) if k == key
+                ));
+            }
+            assert!(keyboard.report_is_empty());
+        }
+    }
+
+    #[test]
+    fn test_is_printable_range() {
+        assert!(!is_printable(0x00));
+        assert!(!is_printable(KeyboardUsage::KeyboardErrorRollOver as u8));
+        assert!(is_printable(FIRST_PRINTABLE));
+        assert!(is_printable(LAST_PRINTABLE));
+        assert!(!is_printable(0xa5));
+        assert!(!is_printable(KeyboardUsage::KeyboardLeftControl as u8));
+
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut boot = Keyboard::new_boot(&alloc);
+        let mut nkro = Keyboard::new_nkro(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        // no key is rejected, instead of taking a slot or a bitmap bit
+        for keyboard in [&mut boot, &mut nkro] {
+            assert_eq!(KeyboardOps::press(keyboard, 0x00), 0);
+            assert!(keyboard.report_is_empty());
+            assert_eq!(KeyboardOps::press(keyboard, FIRST_PRINTABLE), 1);
+        }
+    }
+
+    #[test]
+    fn test_press_category_only() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+
+        assert!(matches!(
+            keyboard.press_printable_only(shift),
+            Err(KeyboardError::InvalidKey(key)) if key == shift
+        ));
+        assert!(matches!(
+            keyboard.press_modifier_only(key_a),
+            Err(KeyboardError::InvalidKey(key)) if key == key_a
+        ));
+        assert!(keyboard.report_is_empty());
+
+        keyboard.press_printable_only(key_a).unwrap();
+        keyboard.press_modifier_only(shift).unwrap();
+        assert!(keyboard.is_key_pressed(key_a));
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0x02);
+    }
+
+    #[test]
+    fn test_usage_api() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut boot = Keyboard::new_boot(&alloc);
+        let mut nkro = Keyboard::new_nkro(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        for keyboard in [&mut boot, &mut nkro] {
+            assert_eq!(keyboard.press_usage(KeyboardUsage::KeyboardAa), 1);
+            assert_eq!(keyboard.press_usage(KeyboardUsage::KeyboardLeftShift), 1);
+
+            assert!(keyboard.is_key_pressed(KeyboardUsage::KeyboardAa as u8));
+            assert!(keyboard.is_usage_pressed(KeyboardUsage::KeyboardAa));
+            assert!(keyboard.is_usage_pressed(KeyboardUsage::KeyboardLeftShift));
+            assert!(!keyboard.is_usage_pressed(KeyboardUsage::KeyboardBb));
+            assert!(!keyboard.was_usage_pressed(KeyboardUsage::KeyboardAa));
+
+            keyboard.release_usage(KeyboardUsage::KeyboardAa);
+            keyboard.release_usage(KeyboardUsage::KeyboardLeftShift);
+            assert!(keyboard.report_is_empty());
+        }
+    }
+
+    #[test]
+    fn test_press_with_modifiers_restores_modifiers() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let key_4 = KeyboardUsage::Keyboard4Dollar as u8;
+        let ctrl = KeyboardUsage::KeyboardLeftControl as u8;
+        let shift = key_to_modifier_bitfield(KeyboardUsage::KeyboardLeftShift as u8);
+
+        KeyboardOps::press(&mut keyboard, ctrl);
+        keyboard.press_with_modifiers(key_4, shift | key_to_modifier_bitfield(ctrl));
+        assert_eq!(keyboard.count_active_modifiers(), 2);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        keyboard.release_with_modifiers(key_4, shift | key_to_modifier_bitfield(ctrl));
+        assert!(keyboard.is_modifier_active(ctrl));
+        assert_eq!(keyboard.count_active_modifiers(), 1);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        // 4 is bit 1 of the fifth bitmap byte
+        let report = |modifier, keys| {
+            let mut report = NKROReport {
+                modifier,
+                ..NKROReport::default()
+            };
+            report.keycodes[4] = keys;
+            report
+        };
+        assert_nkro_report_sequence(
+            usb_dev.bus(),
+            &[
+                report(0x03, 0x00),
+                report(0x03, 0x02),
+                report(0x03, 0x00),
+                report(0x01, 0x00),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_report_diff() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut boot = Keyboard::new_boot(&alloc);
+        let mut nkro = Keyboard::new_nkro(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+        let key_b = KeyboardUsage::KeyboardBb as u8;
+        let key_c = KeyboardUsage::KeyboardCc as u8;
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+        let alt = KeyboardUsage::KeyboardRightAlt as u8;
+
+        for keyboard in [&mut boot, &mut nkro] {
+            let (mut pressed, mut released) = keyboard.report_diff();
+            assert_eq!(pressed.next(), None);
+            assert_eq!(released.next(), None);
+
+            for key in [key_a, key_b, shift] {
+                KeyboardOps::press(keyboard, key);
+            }
+            KeyboardOps::send_report(keyboard).unwrap();
+
+            KeyboardOps::release(keyboard, key_a);
+            KeyboardOps::release(keyboard, shift);
+            KeyboardOps::press(keyboard, key_c);
+            KeyboardOps::press(keyboard, alt);
+
+            let (pressed, released) = keyboard.report_diff();
+            assert!(pressed.eq([key_c, alt]));
+            assert!(released.eq([key_a, shift]));
+
+            KeyboardOps::send_report(keyboard).unwrap();
+
+            let (mut pressed, mut released) = keyboard.report_diff();
+            assert_eq!(pressed.next(), None);
+            assert_eq!(released.next(), None);
+        }
+    }
+
+    #[test]
+    fn test_release_all_and_send() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardLeftShift as u8);
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::Keyboard4Dollar as u8);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        keyboard.release_all_and_send().unwrap();
+
+        assert!(keyboard.report_is_empty());
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_release_modifiers_or_keys_only() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+
+        KeyboardOps::press(&mut keyboard, shift);
+        KeyboardOps::press(&mut keyboard, key_a);
+        keyboard.release_modifiers_only();
+        assert_eq!(keyboard.modifiers(), 0);
+        assert_eq!(
+            keyboard.report().keycodes,
+            [0x04, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+
+        KeyboardOps::press(&mut keyboard, shift);
+        keyboard.release_keys_only();
+        assert_eq!(keyboard.modifiers(), key_to_modifier_bitfield(shift));
+        assert_eq!(keyboard.report().keycodes, ZERO_KEYS);
+
+        // the wrapped shift is forgotten, a shift pressed again stays pressed
+        let shift_bit = key_to_modifier_bitfield(shift);
+        keyboard.release_modifiers_only();
+        keyboard.press_with_modifiers(key_a, shift_bit);
+        keyboard.release_modifiers_only();
+        KeyboardOps::press(&mut keyboard, shift);
+        keyboard.release_with_modifiers(key_a, shift_bit);
+        assert_eq!(keyboard.modifiers(), shift_bit);
+        assert_eq!(keyboard.report().keycodes, ZERO_KEYS);
+
+        // same after releasing everything
+        keyboard.release_all();
+        keyboard.press_with_modifiers(key_a, shift_bit);
+        keyboard.release_all();
+        KeyboardOps::press(&mut keyboard, shift);
+        keyboard.release_with_modifiers(key_a, shift_bit);
+        assert_eq!(keyboard.modifiers(), shift_bit);
+
+        // nothing was sent
+        assert!(usb_dev.bus().in_reports().is_empty());
+    }
+
+    #[test]
+    fn test_modifier_accessors() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let ctrl = key_to_modifier_bitfield(KeyboardUsage::KeyboardLeftControl as u8);
+        let shift = key_to_modifier_bitfield(KeyboardUsage::KeyboardLeftShift as u8);
+        let gui = key_to_modifier_bitfield(KeyboardUsage::KeyboardRightGUI as u8);
+
+        assert_eq!(keyboard.modifiers(), 0);
+        assert!(!keyboard.is_any_modifier_active());
+
+        keyboard.add_modifiers(ctrl | shift);
+        keyboard.add_modifiers(shift);
+        assert_eq!(keyboard.modifiers(), ctrl | shift);
+        assert!(keyboard.is_modifier_active(KeyboardUsage::KeyboardLeftShift as u8));
+        assert_eq!(keyboard.count_active_modifiers(), 2);
+
+        keyboard.remove_modifiers(ctrl | gui);
+        assert_eq!(keyboard.modifiers(), shift);
+        assert!(keyboard.is_any_modifier_active());
+
+        keyboard.set_modifiers(gui);
+        assert_eq!(keyboard.modifiers(), gui);
+
+        keyboard.remove_modifiers(gui);
+        assert_eq!(keyboard.modifiers(), 0);
+        assert!(!keyboard.is_any_modifier_active());
+    }
+
+    #[test]
+    fn test_pressed_modifiers() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        assert_eq!(keyboard.pressed_modifiers().count(), 0);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardRightGUI as u8);
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardLeftControl as u8);
+
+        assert!(keyboard.pressed_modifiers().eq([
+            KeyboardUsage::KeyboardLeftControl,
+            KeyboardUsage::KeyboardRightGUI,
+        ]));
+    }
+
+    #[test]
+    fn test_keyboard_debug() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut boot = Keyboard::new_boot(&alloc);
+        let mut nkro = Keyboard::new_nkro(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        for keyboard in [&mut boot, &mut nkro] {
+            KeyboardOps::press(keyboard, KeyboardUsage::KeyboardBb as u8);
+            KeyboardOps::press(keyboard, KeyboardUsage::KeyboardAa as u8);
+            KeyboardOps::press(keyboard, KeyboardUsage::KeyboardLeftShift as u8);
+        }
+
+        let boot = std::format!("{boot:?}");
+        assert!(boot.contains("protocol: Boot"), "{boot}");
+        assert!(boot.contains("modifier: 0x02"), "{boot}");
+        assert!(boot.contains("keycodes: [5, 4]"), "{boot}");
+
+        let nkro = std::format!("{nkro:?}");
+        assert!(nkro.contains("protocol: Report"), "{nkro}");
+        assert!(nkro.contains("keycodes: [4, 5]"), "{nkro}");
+    }
+
+    #[test]
+    fn test_flush() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut boot = Keyboard::new_boot(&alloc);
+        let mut nkro = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        for keyboard in [&mut boot, &mut nkro] {
+            KeyboardOps::press(keyboard, KeyboardUsage::KeyboardAa as u8);
+            KeyboardOps::send_report(keyboard).unwrap();
+
+            // unchanged reports are only sent by flush
+            KeyboardOps::send_report(keyboard).unwrap();
+            keyboard.flush().unwrap();
+            keyboard.flush().unwrap();
+        }
+
+        let boot_a = [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let mut nkro_a = [0x00; 2 + nkro::NKRO_KEYCODES_LEN];
+        nkro_a[2] = 0x10;
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [&boot_a[..], &boot_a, &boot_a, &nkro_a, &nkro_a, &nkro_a]
+        );
+    }
+
+    #[test]
+    fn test_inject_report() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        // Ctrl+Shift with two keys, replacing the pressed key in one report
+        keyboard
+            .inject_report(KeyboardReport {
+                modifier: 0x03,
+                reserved: 0,
+                leds: 0,
+                keycodes: [0x05, 0x06, 0x00, 0x00, 0x00, 0x00],
+            })
+            .unwrap();
+
+        assert!(!keyboard.report_changed());
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x03, 0x00, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_modifier_only_report() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut boot = Keyboard::new_boot(&alloc);
+        let mut nkro = Keyboard::new_nkro(&alloc);
+        let mut nkro_boot = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        nkro_boot.set_protocol(HidProtocolMode::Boot);
+
+        for keyboard in [&mut boot, &mut nkro, &mut nkro_boot] {
+            KeyboardOps::press(keyboard, KeyboardUsage::KeyboardLeftShift as u8);
+            assert!(!keyboard.keycodes_changed());
+            assert!(keyboard.report_changed());
+
+            KeyboardOps::send_report(keyboard).unwrap();
+            assert!(!keyboard.report_changed());
+        }
+
+        // the NKRO keyboard in boot protocol sends a boot report
+        let boot_shift = [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let mut nkro_shift = [0x00; 2 + nkro::NKRO_KEYCODES_LEN];
+        nkro_shift[0] = 0x02;
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [&boot_shift[..], &nkro_shift, &boot_shift]
+        );
+    }
+
+    #[test]
+    fn test_last_report_synchronized() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut boot = Keyboard::new_boot(&alloc);
+        let mut nkro = Keyboard::new_nkro(&alloc);
+        let mut media = Keyboard::new_media(&alloc);
+        let mut system_control = Keyboard::new_system_control(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+        let key_b = KeyboardUsage::KeyboardBb as u8;
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+
+        for keyboard in [&mut boot, &mut nkro] {
+            for keys in [&[shift, key_b][..], &[key_a]] {
+                for &key in keys {
+                    KeyboardOps::press(keyboard, key);
+                }
+                KeyboardOps::release(keyboard, key_b);
+                KeyboardOps::send_report(keyboard).unwrap();
+
+                assert!(reports_equal(keyboard.last_report(), keyboard.report()));
+                assert!(!keyboard.report_changed());
+            }
+        }
+
+        media::MediaKeyboard::press(&mut media, MediaKey::Mute as u8);
+        media::MediaKeyboard::send_report(&mut media).unwrap();
+        let (last, current) = (media.last_media_report(), media.media_report());
+        let (last_usage_id, usage_id) = (last.usage_id, current.usage_id);
+        assert_eq!(last_usage_id, usage_id);
+
+        system_control::SystemControlKeyboard::press(
+            &mut system_control,
+            SystemControlKey::Sleep as u8,
+        );
+        system_control::SystemControlKeyboard::send_report(&mut system_control).unwrap();
+        let last = system_control.last_system_control_report().usage_id;
+        assert_eq!(last, system_control.system_control_report().usage_id);
+    }
+
+    #[test]
+    fn test_host_set_protocol() {
+        static BOOT_CHANGES: AtomicUsize = AtomicUsize::new(0);
+
+        fn count_boot_changes(protocol: HidProtocolMode) {
+            if protocol == HidProtocolMode::Boot {
+                BOOT_CHANGES.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = KeyboardBuilder::new()
+            .nkro()
+            .on_protocol_change(count_boot_changes)
+            .build(&alloc);
+        let mut usb_dev = mock_usb_device(&alloc);
+
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Report);
+        assert!(!keyboard.handle_control());
+
+        // manual changes are kept while the host doesn't request a protocol
+        keyboard.set_protocol(HidProtocolMode::Boot);
+        keyboard.poll().unwrap();
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Boot);
+        keyboard.set_protocol(HidProtocolMode::Report);
+
+        // Set_Protocol(Boot) on interface 0
+        usb_dev
+            .bus()
+            .queue_setup([0x21, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        usb_dev.poll(&mut [keyboard.hid_class_mut()]);
+
+        keyboard.poll().unwrap();
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Boot);
+        assert_eq!(BOOT_CHANGES.load(Ordering::SeqCst), 1);
+
+        assert!(!keyboard.handle_control());
+        assert_eq!(BOOT_CHANGES.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_host_set_protocol_resets_idle() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let mut usb_dev = mock_usb_device(&alloc);
+
+        keyboard.set_idle_rate(2);
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+        assert!(!keyboard.tick_idle(7).unwrap());
+
+        // Set_Protocol(Report) on interface 0
+        usb_dev
+            .bus()
+            .queue_setup([0x21, 0x0b, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        usb_dev.poll(&mut [keyboard.hid_class_mut()]);
+        keyboard.poll().unwrap();
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Report);
+
+        // the idle period restarted, and the held key is sent again in the new protocol
+        assert!(!keyboard.tick_idle(1).unwrap());
+        assert!(keyboard.report_changed());
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_equal() {
+        let report = KeyboardReport {
+            modifier: 0x02,
+            reserved: 0,
+            leds: 0x01,
+            keycodes: [0x04, 0x05, 0, 0, 0, 0],
+        };
+        assert!(reports_equal(&report, &report.clone()));
+
+        // the reserved byte is ignored
+        let reserved = KeyboardReport {
+            reserved: 0xff,
+            ..report
+        };
+        assert!(reports_equal(&report, &reserved));
+
+        for other in [
+            KeyboardReport {
+                modifier: 0x00,
+                ..report
+            },
+            KeyboardReport {
+                leds: 0x00,
+                ..report
+            },
+            KeyboardReport {
+                keycodes: [0x05, 0x04, 0, 0, 0, 0],
+                ..report
+            },
+        ] {
+            assert!(!reports_equal(&report, &other));
+            assert!(!reports_equal(&other, &report));
+        }
+    }
+
+    #[test]
+    fn test_send_report_configured() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let mut usb_dev = mock_usb_device(&alloc);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+
+        assert!(!keyboard.is_configured(&usb_dev));
+        assert!(matches!(
+            keyboard.send_report_configured(&usb_dev),
+            Err(KeyboardError::NotConfigured)
+        ));
+        assert!(usb_dev.bus().in_reports().is_empty());
+
+        // SET_CONFIGURATION(1)
+        usb_dev
+            .bus()
+            .queue_setup([0x00, 0x09, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        usb_dev.poll(&mut []);
+
+        assert!(keyboard.is_configured(&usb_dev));
+        keyboard.send_report_configured(&usb_dev).unwrap();
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00]]
+        );
+    }
+
+    #[test]
+    fn test_device_state_gates_boot_send() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        assert_eq!(keyboard.device_state(), None);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        assert_send_gated(
+            &alloc,
+            &mut keyboard,
+            |keyboard| KeyboardOps::send_report(keyboard),
+            &[&[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00]],
+        );
+        assert_eq!(keyboard.device_state(), Some(UsbDeviceState::Configured));
+    }
+
+    #[test]
+    fn test_flush_unconfigured() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        assert_send_gated(
+            &alloc,
+            &mut keyboard,
+            |keyboard| keyboard.flush(),
+            &[&[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00]],
+        );
+    }
+
+    #[test]
+    fn test_tick_idle_unconfigured() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        keyboard.update_device_state(&usb_dev);
+        keyboard.set_idle_rate(2);
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+
+        assert!(matches!(
+            keyboard.tick_idle(8),
+            Err(KeyboardError::NotConfigured)
+        ));
+        // the idle period restarted, instead of failing on every tick
+        assert!(!keyboard.tick_idle(7).unwrap());
+        assert!(matches!(
+            keyboard.tick_idle(1),
+            Err(KeyboardError::NotConfigured)
+        ));
+
+        keyboard.poll().unwrap();
+        assert!(usb_dev.bus().in_reports().is_empty());
+    }
+
+    #[test]
+    fn test_tap() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        keyboard.tap(KeyboardUsage::KeyboardAa as u8).unwrap();
+        keyboard.tap_usage(KeyboardUsage::KeyboardBb).unwrap();
+
+        assert!(keyboard.report_is_empty());
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_new_boot_with_protocol() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot_with_protocol(&alloc, HidProtocolMode::Report);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        assert!(!keyboard.is_nkro());
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Report);
+        assert_eq!(keyboard.default_protocol(), HidProtocolMode::Report);
+
+        keyboard.set_protocol(HidProtocolMode::Boot);
+        keyboard.on_usb_reset();
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Report);
+    }
+
+    #[test]
+    fn test_send_report_too_large() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        // the 8-byte boot report doesn't fit the endpoint
+        usb_dev.bus().set_in_max_packet_size(1, 4);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        assert!(matches!(
+            KeyboardOps::send_report(&mut keyboard),
+            Err(KeyboardError::ReportTooLarge)
+        ));
+        assert_eq!(keyboard.pending_reports(), 0);
+        assert!(usb_dev.bus().in_reports().is_empty());
+    }
+
+    #[test]
+    fn test_report_activity() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+
+        assert!(keyboard.report_is_empty());
+        assert!(!keyboard.report_changed());
+
+        for key in [key_a, shift] {
+            KeyboardOps::press(&mut keyboard, key);
+            assert!(!keyboard.report_is_empty());
+            assert!(keyboard.report_changed());
+
+            KeyboardOps::send_report(&mut keyboard).unwrap();
+            assert!(!keyboard.report_changed());
+
+            KeyboardOps::release(&mut keyboard, key);
+            assert!(keyboard.report_is_empty());
+            assert!(keyboard.report_changed());
+
+            KeyboardOps::send_report(&mut keyboard).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_modifier_bit() {
+        let modifiers = [
+            KeyboardUsage::KeyboardLeftControl,
+            KeyboardUsage::KeyboardLeftShift,
+            KeyboardUsage::KeyboardLeftAlt,
+            KeyboardUsage::KeyboardLeftGUI,
+            KeyboardUsage::KeyboardRightControl,
+            KeyboardUsage::KeyboardRightShift,
+            KeyboardUsage::KeyboardRightAlt,
+            KeyboardUsage::KeyboardRightGUI,
+        ];
+
+        for (i, modifier) in modifiers.into_iter().enumerate() {
+            assert_eq!(modifier_bit(modifier as u8), Some(1 << i));
+        }
+
+        assert_eq!(modifier_bit(KeyboardUsage::KeyboardAa as u8), None);
+        assert_eq!(modifier_bit(0xe8), None);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not a modifier key")]
+    fn test_key_to_modifier_bitfield_invalid() {
+        key_to_modifier_bitfield(KeyboardUsage::KeyboardAa as u8);
+    }
+
+    #[test]
+    fn test_reset() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardLeftShift as u8);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+        media::MediaKeyboard::press(&mut keyboard, MediaKey::Mute as u8);
+        keyboard.set_protocol(HidProtocolMode::Report);
+        usb_dev.bus().queue_out(1, &[LED_NUM_LOCK]);
+        keyboard.read_leds();
+        let sent = usb_dev.bus().in_reports().len();
+
+        keyboard.reset();
+
+        // the host LED state is kept
+        assert!(keyboard.num_lock());
+        assert_eq!(keyboard.last_report().leds, LED_NUM_LOCK);
+
+        assert_constructed(&keyboard);
+        assert!(!keyboard.keycodes_changed());
+        assert!(!keyboard.was_any_modifier_active());
+        let usage_id = keyboard.media_report().usage_id;
+        assert_eq!(usage_id, 0);
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Boot);
+        assert_eq!(keyboard.pending_reports(), 0);
+        assert_eq!(usb_dev.bus().in_reports().len(), sent);
+    }
+
+    #[test]
+    fn test_idle_rate_resend() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        // indefinite idle rate never resends
+        assert_eq!(keyboard.idle_rate_ms(), 0);
+        assert!(!keyboard.tick_idle(u32::MAX).unwrap());
+
+        keyboard.set_idle_rate(125);
+        assert_eq!(keyboard.idle(), 125);
+        assert_eq!(keyboard.idle_rate_ms(), 500);
+        keyboard.set_idle_rate(u8::MAX);
+        assert_eq!(keyboard.idle_rate_ms(), 1020);
+
+        keyboard.set_idle_rate(2);
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        assert!(!keyboard.tick_idle(7).unwrap());
+        assert!(keyboard.tick_idle(1).unwrap());
+        // the resend restarts the idle period
+        assert!(!keyboard.tick_idle(7).unwrap());
+
+        // so does a keyboard send
+        KeyboardOps::release(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+        assert!(!keyboard.tick_idle(7).unwrap());
+        assert!(keyboard.tick_idle(1).unwrap());
+
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_press_and_hold_report() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        let snapshot = keyboard.press_and_hold_report();
+        assert!(reports_equal(&snapshot, keyboard.report()));
+
+        // later changes don't affect the snapshot
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardLeftShift as u8);
+        KeyboardOps::release(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        assert!(!reports_equal(&snapshot, keyboard.report()));
+        assert_eq!(snapshot.modifier, 0);
+        assert_eq!(snapshot.keycodes, [0x04, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+        // nothing was sent
+        assert!(usb_dev.bus().in_reports().is_empty());
+    }
+
+    #[test]
+    fn test_set_report() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        keyboard.set_report(KeyboardReport {
+            modifier: 0x22,
+            reserved: 0,
+            leds: LED_CAPS_LOCK,
+            keycodes: [0x04, 0x05, 0x06, 0x07, 0x08, 0x09],
+        });
+
+        let report = *keyboard.report();
+        assert_eq!(report.modifier, 0x22);
+        assert_eq!(report.leds, LED_CAPS_LOCK);
+        assert_eq!(report.keycodes, [0x04, 0x05, 0x06, 0x07, 0x08, 0x09]);
+        assert!(keyboard.report_changed());
+    }
+
+    #[test]
+    fn test_toggle() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+
+        for key in [key_a, shift] {
+            assert_eq!(keyboard.toggle(key), 1);
+        }
+        assert!(keyboard.is_key_pressed(key_a));
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0x02);
+
+        keyboard.toggle(key_a);
+        assert!(!keyboard.is_key_pressed(key_a));
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0x02);
+
+        keyboard.toggle(shift);
+        assert!(keyboard.report_is_empty());
+    }
+
+    static LED_REPORT: AtomicUsize = AtomicUsize::new(0);
+
+    fn record_led_report(id: HIDReportId, data: &[u8]) {
+        if id == HIDReportId::Keyboard {
+            LED_REPORT.store(0x100 | data[0] as usize, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_output_report_hook() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let observer = HIDReportObserver::default().with_output_report_hook(record_led_report);
+        let mut keyboard = Keyboard::new_with_observer(&alloc, observer);
+        let usb_dev = mock_usb_device(&alloc);
+
+        keyboard.poll().unwrap();
+        assert_eq!(LED_REPORT.load(Ordering::SeqCst), 0);
+
+        usb_dev.bus().queue_out(1, &[LED_NUM_LOCK | LED_CAPS_LOCK]);
+        keyboard.poll().unwrap();
+        assert_eq!(LED_REPORT.load(Ordering::SeqCst), 0x103);
+        assert_eq!(KeyboardOps::leds(&keyboard), 0x03);
+    }
+
+    #[cfg(feature = "us")]
+    #[test]
+    fn test_active_locale_us() {
+        assert_eq!(active_locale(), HidCountryCode::US);
     }
 }
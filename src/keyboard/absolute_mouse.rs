@@ -0,0 +1,400 @@
+use usb_device::bus::UsbBus;
+use usbd_hid::descriptor::generator_prelude::*;
+use usbd_hid::hid_class::{
+    HIDClass, HidClassSettings, HidCountryCode, HidProtocol, HidSubClass, ProtocolModeConfig,
+};
+
+use crate::error::{KeyboardError, KeyboardResult};
+use crate::hid_settings::{HIDReport, HIDReportId};
+use crate::HIDReportObserver;
+
+use super::{KeyboardUsbBus, KeyboardUsbBusAllocator, POLL_MS};
+
+/// Number of buttons supported by the [AbsoluteMouseReport].
+pub const ABSOLUTE_MOUSE_BUTTONS: u8 = 3;
+/// Maximum X and Y coordinate of the [AbsoluteMouseReport], the far edge of the screen.
+pub const ABSOLUTE_MOUSE_MAX: u16 = 0x7fff;
+
+pub const fn absolute_mouse_hid_class_settings() -> HidClassSettings {
+    HidClassSettings {
+        subclass: HidSubClass::NoSubClass,
+        protocol: HidProtocol::Generic,
+        config: ProtocolModeConfig::DefaultBehavior,
+        locale: HidCountryCode::NotSupported,
+    }
+}
+
+/// AbsoluteMouseReport describes a report and its companion descriptor that can be used to send
+/// button presses and absolute pointer positions to a host, e.g. like a tablet.
+///
+/// Coordinates range from `0` to [ABSOLUTE_MOUSE_MAX] on both axes, and are scaled by the host to
+/// the screen size. The vertical wheel and horizontal pan are relative, like on a regular mouse.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AbsoluteMouseReport {
+    pub buttons: u8,
+    pub x: u16,
+    pub y: u16,
+    pub wheel: i8,
+    pub pan: i8,
+}
+
+// `gen_hid_descriptor` derives the logical range from the field type, so the descriptor is written
+// out to report the positive 16-bit range.
+#[rustfmt::skip]
+const ABSOLUTE_MOUSE_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x02, // Usage (Mouse)
+    0xa1, 0x01, // Collection (Application)
+    0x09, 0x01, //   Usage (Pointer)
+    0xa1, 0x00, //   Collection (Physical)
+    0x05, 0x09, //     Usage Page (Button)
+    0x19, 0x01, //     Usage Minimum (1)
+    0x29, 0x03, //     Usage Maximum (3)
+    0x15, 0x00, //     Logical Minimum (0)
+    0x25, 0x01, //     Logical Maximum (1)
+    0x75, 0x01, //     Report Size (1)
+    0x95, 0x03, //     Report Count (3)
+    0x81, 0x02, //     Input (Data, Variable, Absolute)
+    0x75, 0x05, //     Report Size (5)
+    0x95, 0x01, //     Report Count (1)
+    0x81, 0x03, //     Input (Constant, Variable, Absolute)
+    0x05, 0x01, //     Usage Page (Generic Desktop)
+    0x09, 0x30, //     Usage (X)
+    0x09, 0x31, //     Usage (Y)
+    0x15, 0x00, //     Logical Minimum (0)
+    0x26, 0xff, 0x7f, // Logical Maximum (32767)
+    0x75, 0x10, //     Report Size (16)
+    0x95, 0x02, //     Report Count (2)
+    0x81, 0x02, //     Input (Data, Variable, Absolute)
+    0x09, 0x38, //     Usage (Wheel)
+    0x15, 0x81, //     Logical Minimum (-127)
+    0x25, 0x7f, //     Logical Maximum (127)
+    0x75, 0x08, //     Report Size (8)
+    0x95, 0x01, //     Report Count (1)
+    0x81, 0x06, //     Input (Data, Variable, Relative)
+    0x05, 0x0c, //     Usage Page (Consumer)
+    0x0a, 0x38, 0x02, // Usage (AC Pan)
+    0x15, 0x81, //     Logical Minimum (-127)
+    0x25, 0x7f, //     Logical Maximum (127)
+    0x75, 0x08, //     Report Size (8)
+    0x95, 0x01, //     Report Count (1)
+    0x81, 0x06, //     Input (Data, Variable, Relative)
+    0xc0, //         End Collection
+    0xc0, // End Collection
+];
+
+impl SerializedDescriptor for AbsoluteMouseReport {
+    fn desc() -> &'static [u8] {
+        ABSOLUTE_MOUSE_DESCRIPTOR
+    }
+}
+
+impl Serialize for AbsoluteMouseReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_tuple(5)?;
+        s.serialize_element(&self.buttons)?;
+        s.serialize_element(&self.x)?;
+        s.serialize_element(&self.y)?;
+        s.serialize_element(&self.wheel)?;
+        s.serialize_element(&self.pan)?;
+        s.end()
+    }
+}
+
+impl AsInputReport for AbsoluteMouseReport {}
+
+/// Pointer device reporting absolute coordinates, e.g. for KVM or remote-control setups.
+///
+/// Unlike a relative mouse, each report places the pointer at a fixed position on the screen.
+pub struct AbsoluteMouse<'m, B: UsbBus = KeyboardUsbBus> {
+    hid_class: HIDClass<'m, B>,
+    report: AbsoluteMouseReport,
+    last_report: AbsoluteMouseReport,
+    observer: HIDReportObserver,
+}
+
+impl<'m, B: UsbBus> AbsoluteMouse<'m, B> {
+    /// Creates a new [AbsoluteMouse] device.
+    pub fn new(bus: &'m KeyboardUsbBusAllocator<B>) -> Self {
+        Self::new_with_observer(bus, HIDReportObserver::default())
+    }
+
+    /// Creates a new [AbsoluteMouse] device.
+    ///
+    /// Allows setting a custom [HIDReportObserver] implementation for firing a callback function
+    /// on HID report events.
+    pub fn new_with_observer(
+        bus: &'m KeyboardUsbBusAllocator<B>,
+        observer: HIDReportObserver,
+    ) -> Self {
+        let hid_class = HIDClass::new_with_settings(
+            bus,
+            AbsoluteMouseReport::desc(),
+            POLL_MS,
+            absolute_mouse_hid_class_settings(),
+        );
+
+        Self {
+            hid_class,
+            report: AbsoluteMouseReport::default(),
+            last_report: AbsoluteMouseReport::default(),
+            observer,
+        }
+    }
+
+    /// Gets a reference to the current absolute mouse report.
+    pub fn report(&self) -> &AbsoluteMouseReport {
+        &self.report
+    }
+
+    /// Gets a reference to the last absolute mouse report.
+    pub fn last_report(&self) -> &AbsoluteMouseReport {
+        &self.last_report
+    }
+
+    /// Gets a reference to the [HIDClass] for the USB bus.
+    pub fn hid_class(&self) -> &HIDClass<'m, B> {
+        &self.hid_class
+    }
+
+    /// Moves the pointer to the absolute position.
+    ///
+    /// Coordinates are clamped to [ABSOLUTE_MOUSE_MAX].
+    pub fn move_abs(&mut self, x: u16, y: u16) {
+        self.report.x = x.min(ABSOLUTE_MOUSE_MAX);
+        self.report.y = y.min(ABSOLUTE_MOUSE_MAX);
+    }
+
+    /// Scrolls the vertical wheel by the amount, positive values scroll up.
+    ///
+    /// The scroll is sent once by the next report, and adds up with scrolls not sent yet.
+    pub fn scroll(&mut self, dy: i8) {
+        self.report.wheel = self.report.wheel.saturating_add(dy);
+    }
+
+    /// Scrolls horizontally by the amount with the AC Pan usage, positive values scroll right.
+    ///
+    /// The pan is sent once by the next report, and adds up with pans not sent yet.
+    pub fn pan(&mut self, dx: i8) {
+        self.report.pan = self.report.pan.saturating_add(dx);
+    }
+
+    /// Press a button, and add it to the current report.
+    ///
+    /// Buttons are numbered from `1` to [ABSOLUTE_MOUSE_BUTTONS], matching the HID button usages.
+    ///
+    /// Returns 1 if the button is in range.
+    /// Returns 0 otherwise.
+    pub fn press_button(&mut self, button: u8) -> usize {
+        if is_absolute_mouse_button(button) {
+            self.report.buttons |= 1 << (button - 1);
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Release a pressed button, and remove it from the current report.
+    ///
+    /// Returns 1 if the button is in range.
+    /// Returns 0 otherwise.
+    pub fn release_button(&mut self, button: u8) -> usize {
+        if is_absolute_mouse_button(button) {
+            self.report.buttons &= !(1 << (button - 1));
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Release all buttons in the current report, keeping the pointer position.
+    pub fn release_all(&mut self) {
+        self.report.buttons = 0;
+    }
+
+    /// Sets the buttons, moves the pointer, and scrolls, then sends all changes in one report.
+    ///
+    /// `buttons` is the button bitfield, with bit 0 for button `1`. The pointer moves by `dx` and
+    /// `dy` from its current position, clamped to the screen. Useful for drag gestures, where
+    /// separate button and move reports would make the host see a click before the move.
+    pub fn update(&mut self, buttons: u8, dx: i8, dy: i8, wheel: i8) -> KeyboardResult<()> {
+        self.report.buttons = buttons & ((1 << ABSOLUTE_MOUSE_BUTTONS) - 1);
+        self.move_abs(
+            self.report.x.saturating_add_signed(dx.into()),
+            self.report.y.saturating_add_signed(dy.into()),
+        );
+        self.scroll(wheel);
+
+        self.send_report()
+    }
+
+    /// Sends the current absolute mouse report to the host, if it changed since the last report.
+    ///
+    /// Reports with a wheel or pan movement are always sent, and the movement is cleared from the
+    /// current report once sent. A failed send keeps the movement for the next report.
+    pub fn send_report(&mut self) -> KeyboardResult<()> {
+        let scrolled = self.report.wheel != 0 || self.report.pan != 0;
+
+        if scrolled || self.report != self.last_report {
+            let report = self.report;
+            // replace the Ok(usize) with Ok(())
+            let ret = self.hid_class.push_input(&report).map(|_| ());
+            self.observer.observe_report(
+                HIDReportId::MouseAbsolute,
+                HIDReport::AbsoluteMouse(report),
+                &ret,
+            );
+
+            if ret.is_ok() {
+                // the wheel and pan are relative, and must not repeat in the next report
+                self.report.wheel = 0;
+                self.report.pan = 0;
+                self.last_report = self.report;
+            }
+
+            ret.map_err(KeyboardError::from)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+const fn is_absolute_mouse_button(button: u8) -> bool {
+    button >= 1 && button <= ABSOLUTE_MOUSE_BUTTONS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use usb_device::bus::UsbBusAllocator;
+    use usb_device::UsbError;
+
+    #[test]
+    fn test_absolute_mouse_descriptor() {
+        let desc = AbsoluteMouseReport::desc();
+
+        // the X/Y logical range follows the axis usages
+        let axes = desc
+            .windows(4)
+            .position(|w| w == [0x09, 0x30, 0x09, 0x31])
+            .unwrap();
+        assert_eq!(
+            desc[axes + 4..axes + 13],
+            [0x15, 0x00, 0x26, 0xff, 0x7f, 0x75, 0x10, 0x95, 0x02]
+        );
+        assert_eq!(desc.last(), Some(&0xc0));
+    }
+
+    #[test]
+    fn test_absolute_mouse_encoding() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut mouse = AbsoluteMouse::new(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        assert_eq!(mouse.press_button(1), 1);
+        assert_eq!(mouse.press_button(4), 0);
+        mouse.move_abs(0x1234, u16::MAX);
+        mouse.send_report().unwrap();
+        // unchanged reports are not pushed
+        mouse.send_report().unwrap();
+
+        mouse.release_all();
+        mouse.send_report().unwrap();
+
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x01, 0x34, 0x12, 0xff, 0x7f, 0x00, 0x00],
+                [0x00, 0x34, 0x12, 0xff, 0x7f, 0x00, 0x00]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_absolute_mouse_wheel_descriptor() {
+        let desc = AbsoluteMouseReport::desc();
+
+        // both wheels are relative 8-bit inputs
+        for usage in [&[0x09, 0x38][..], &[0x0a, 0x38, 0x02][..]] {
+            let pos = desc.windows(usage.len()).position(|w| w == usage).unwrap();
+            assert_eq!(
+                desc[pos + usage.len()..pos + usage.len() + 10],
+                [0x15, 0x81, 0x25, 0x7f, 0x75, 0x08, 0x95, 0x01, 0x81, 0x06]
+            );
+        }
+        assert!(desc.windows(2).any(|w| w == [0x05, 0x0c]));
+    }
+
+    #[test]
+    fn test_absolute_mouse_scroll_encoding() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut mouse = AbsoluteMouse::new(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        mouse.scroll(-1);
+        mouse.pan(2);
+        mouse.send_report().unwrap();
+        // the movement is only sent once
+        mouse.send_report().unwrap();
+
+        // repeated scrolls are sent, even though the report is unchanged
+        mouse.scroll(1);
+        mouse.send_report().unwrap();
+        mouse.scroll(1);
+        mouse.send_report().unwrap();
+
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x02],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00],
+            ]
+        );
+        assert_eq!(mouse.report().wheel, 0);
+    }
+
+    #[test]
+    fn test_absolute_mouse_scroll_send_error() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut mouse = AbsoluteMouse::new(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        mouse.scroll(-1);
+        usb_dev.bus().fail_next_write(UsbError::WouldBlock);
+        assert!(mouse.send_report().unwrap_err().is_would_block());
+
+        // the scroll is kept, and sent by the next report
+        assert_eq!(mouse.report().wheel, -1);
+        mouse.send_report().unwrap();
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [[0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x00]]
+        );
+    }
+
+    #[test]
+    fn test_absolute_mouse_update() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut mouse = AbsoluteMouse::new(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        mouse.move_abs(0x100, 0x200);
+
+        // button down and move are pushed together
+        mouse.update(0x01, 16, -1, 0).unwrap();
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [[0x01, 0x10, 0x01, 0xff, 0x01, 0x00, 0x00]]
+        );
+
+        // out of range buttons are dropped, and the pointer stays on screen
+        mouse.move_abs(0, ABSOLUTE_MOUSE_MAX);
+        mouse.update(0xff, -1, 1, -1).unwrap();
+        assert_eq!(
+            usb_dev.bus().in_reports()[1],
+            [0x07, 0x00, 0x00, 0xff, 0x7f, 0xff, 0x00]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+/// Default idle time, in milliseconds, before switching to the slow polling interval.
+pub const ADAPTIVE_POLL_IDLE_MS: u32 = 30_000;
+
+/// Chooses the polling interval from key activity: fast while typing, slow while idle.
+///
+/// The polling interval is part of the endpoint descriptor, and `usb-device` has no way to
+/// change it on an enumerated device. When [tick](Self::tick) or
+/// [on_keypress](Self::on_keypress) return a new interval, the firmware applies it by
+/// rebuilding the keyboard with [KeyboardBuilder::poll_ms](super::builder::KeyboardBuilder::poll_ms)
+/// and forcing the host to re-enumerate the device, e.g. by detaching from the bus.
+///
+/// Re-enumeration takes hundreds of milliseconds, and the first keypress after an idle period
+/// triggers it, so the idle timeout should be long compared to pauses in typing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptivePoll {
+    fast_ms: u8,
+    slow_ms: u8,
+    idle_timeout_ms: u32,
+    idle_ms: u32,
+    slow: bool,
+}
+
+impl AdaptivePoll {
+    /// Creates a new [AdaptivePoll], starting at the fast interval.
+    pub const fn new(fast_ms: u8, slow_ms: u8, idle_timeout_ms: u32) -> Self {
+        Self {
+            fast_ms,
+            slow_ms,
+            idle_timeout_ms,
+            idle_ms: 0,
+            slow: false,
+        }
+    }
+
+    /// Gets the target polling interval in milliseconds.
+    pub const fn interval_ms(&self) -> u8 {
+        if self.slow {
+            self.slow_ms
+        } else {
+            self.fast_ms
+        }
+    }
+
+    /// Gets whether the slow polling interval is selected.
+    pub const fn is_idle(&self) -> bool {
+        self.slow
+    }
+
+    /// Advances the idle clock by the elapsed milliseconds.
+    ///
+    /// Returns the slow interval when the idle timeout is reached, and `None` otherwise. The idle
+    /// clock saturates at `u32::MAX` instead of wrapping around, so a long idle period stays idle.
+    pub fn tick(&mut self, elapsed_ms: u32) -> Option<u8> {
+        self.idle_ms = self.idle_ms.saturating_add(elapsed_ms);
+
+        if !self.slow && self.idle_ms >= self.idle_timeout_ms {
+            self.slow = true;
+            Some(self.slow_ms)
+        } else {
+            None
+        }
+    }
+
+    /// Resets the idle clock on a keypress.
+    ///
+    /// Returns the fast interval when reverting from the slow interval, and `None` otherwise.
+    pub fn on_keypress(&mut self) -> Option<u8> {
+        self.idle_ms = 0;
+
+        if self.slow {
+            self.slow = false;
+            Some(self.fast_ms)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for AdaptivePoll {
+    fn default() -> Self {
+        Self::new(super::POLL_MS, u8::MAX, ADAPTIVE_POLL_IDLE_MS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adaptive_poll_interval() {
+        let mut poll = AdaptivePoll::new(1, 100, 50);
+        assert_eq!(poll.interval_ms(), 1);
+
+        assert_eq!(poll.tick(30), None);
+        assert_eq!(poll.on_keypress(), None);
+
+        // the keypress restarted the idle clock
+        assert_eq!(poll.tick(30), None);
+        assert_eq!(poll.tick(20), Some(100));
+        assert!(poll.is_idle());
+        assert_eq!(poll.interval_ms(), 100);
+
+        // only the transition is reported
+        assert_eq!(poll.tick(u32::MAX), None);
+        assert_eq!(poll.interval_ms(), 100);
+
+        assert_eq!(poll.on_keypress(), Some(1));
+        assert_eq!(poll.on_keypress(), None);
+        assert!(!poll.is_idle());
+        assert_eq!(poll.interval_ms(), 1);
+    }
+}
@@ -0,0 +1,304 @@
+use usbd_hid::descriptor::KeyboardUsage;
+
+use crate::error::KeyboardResult;
+
+use super::{key_to_modifier_bitfield, KeyboardOps};
+
+/// Default hold time, in milliseconds, before a held key is shifted.
+pub const AUTO_SHIFT_TIMEOUT_MS: u32 = 175;
+
+const LEFT_SHIFT: u8 = KeyboardUsage::KeyboardLeftShift as u8;
+const RIGHT_SHIFT: u8 = KeyboardUsage::KeyboardRightShift as u8;
+const SHIFT_MODIFIERS: u8 =
+    key_to_modifier_bitfield(LEFT_SHIFT) | key_to_modifier_bitfield(RIGHT_SHIFT);
+
+/// Gets whether [AutoShift] applies to the key: letters, digits, and punctuation.
+pub const fn is_auto_shift_key(key: u8) -> bool {
+    matches!(key, 0x04..=0x27 | 0x2d..=0x38)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum HoldState {
+    // Held for less than the timeout, not added to the report yet.
+    Pending,
+    // Added to the report without shift.
+    Unshifted,
+    // Added to the report with shift, `added_shift` is set if the shift was added by AutoShift.
+    Shifted { added_shift: bool },
+}
+
+/// Sends the shifted variant of a key held past the timeout, e.g. `A` for a long press of `a`.
+///
+/// Pass key events through [press](Self::press) and [release](Self::release), and call
+/// [tick](Self::tick) regularly. A pressed [auto-shift key](is_auto_shift_key) is held back
+/// until it is either released, and sent as a tap, or held past the timeout, and pressed with
+/// shift. Pressing another key sends the held back key unshifted.
+///
+/// Shift added by [AutoShift] is released with the key. Shift pressed explicitly is left alone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoShift {
+    timeout_ms: u32,
+    key: u8,
+    held_ms: u32,
+    state: Option<HoldState>,
+}
+
+impl AutoShift {
+    /// Creates a new [AutoShift], shifting keys held for the timeout in milliseconds.
+    pub const fn new(timeout_ms: u32) -> Self {
+        Self {
+            timeout_ms,
+            key: 0,
+            held_ms: 0,
+            state: None,
+        }
+    }
+
+    /// Gets the hold timeout in milliseconds.
+    pub const fn timeout_ms(&self) -> u32 {
+        self.timeout_ms
+    }
+
+    /// Sets the hold timeout in milliseconds.
+    pub fn set_timeout_ms(&mut self, timeout_ms: u32) {
+        self.timeout_ms = timeout_ms;
+    }
+
+    /// Gets whether a pressed key is held back, waiting for the timeout or its release.
+    pub fn is_pending(&self) -> bool {
+        self.state == Some(HoldState::Pending)
+    }
+
+    /// Press a key on the keyboard.
+    ///
+    /// [Auto-shift keys](is_auto_shift_key) are held back, other keys are pressed immediately.
+    ///
+    /// Returns 1 if the key was held back or added to the current report, 0 otherwise.
+    pub fn press<K: KeyboardOps>(&mut self, keyboard: &mut K, key: u8) -> usize {
+        match self.state {
+            Some(HoldState::Pending) => {
+                keyboard.press(self.key);
+                self.state = Some(HoldState::Unshifted);
+            }
+            Some(HoldState::Shifted { added_shift: true }) => {
+                if key == LEFT_SHIFT {
+                    // the added shift is now held explicitly, keep it on release
+                    self.state = Some(HoldState::Shifted { added_shift: false });
+                } else if key != RIGHT_SHIFT {
+                    // only the held key is shifted, not the keys pressed after it
+                    keyboard.remove_modifiers(key_to_modifier_bitfield(LEFT_SHIFT));
+                    self.state = None;
+                }
+            }
+            _ => (),
+        }
+
+        if is_auto_shift_key(key) {
+            self.key = key;
+            self.held_ms = 0;
+            self.state = Some(HoldState::Pending);
+            1
+        } else {
+            keyboard.press(key)
+        }
+    }
+
+    /// Release a key on the keyboard.
+    ///
+    /// A held back key is sent as a tap: the keyboard report with the key pressed is sent, and
+    /// the key is released from the current report, to be sent by the next `send_report`.
+    ///
+    /// The key is released from the current report even if sending the tap fails, so a failed
+    /// send doesn't leave the key stuck.
+    ///
+    /// Returns 1 if the key was released, 0 otherwise.
+    pub fn release<K: KeyboardOps>(&mut self, keyboard: &mut K, key: u8) -> KeyboardResult<usize> {
+        let mut sent = Ok(());
+
+        if self.state.is_some() && key == self.key {
+            let state = self.state.take();
+
+            match state {
+                Some(HoldState::Pending) => {
+                    keyboard.press(key);
+                    sent = keyboard.send_report();
+                }
+                Some(HoldState::Shifted { added_shift: true }) => {
+                    keyboard.remove_modifiers(key_to_modifier_bitfield(LEFT_SHIFT));
+                }
+                _ => (),
+            }
+        }
+
+        let released = keyboard.release(key);
+        sent.map(|_| released)
+    }
+
+    /// Advances the hold clock by the elapsed milliseconds.
+    ///
+    /// Presses a held back key with shift once the timeout is reached, unless shift is already
+    /// held. Returns true if the key was pressed. The hold clock saturates at `u32::MAX` instead
+    /// of wrapping around, so a saturated clock counts as timed out.
+    pub fn tick<K: KeyboardOps>(&mut self, keyboard: &mut K, elapsed_ms: u32) -> bool {
+        if !self.is_pending() {
+            return false;
+        }
+
+        self.held_ms = self.held_ms.saturating_add(elapsed_ms);
+        if self.held_ms < self.timeout_ms {
+            return false;
+        }
+
+        let added_shift = keyboard.modifiers() & SHIFT_MODIFIERS == 0;
+        if added_shift {
+            keyboard.add_modifiers(key_to_modifier_bitfield(LEFT_SHIFT));
+        }
+        keyboard.press(self.key);
+        self.state = Some(HoldState::Shifted { added_shift });
+
+        true
+    }
+}
+
+impl Default for AutoShift {
+    fn default() -> Self {
+        Self::new(AUTO_SHIFT_TIMEOUT_MS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usb_device::bus::UsbBusAllocator;
+    use usb_device::UsbError;
+
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use crate::Keyboard;
+
+    const KEY_A: u8 = KeyboardUsage::KeyboardAa as u8;
+
+    #[test]
+    fn test_auto_shift_tap() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+        let mut auto_shift = AutoShift::default();
+
+        assert_eq!(auto_shift.press(&mut keyboard, KEY_A), 1);
+        assert!(!auto_shift.tick(&mut keyboard, AUTO_SHIFT_TIMEOUT_MS - 1));
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        assert_eq!(auto_shift.release(&mut keyboard, KEY_A).unwrap(), 1);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_auto_shift_hold() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+        let mut auto_shift = AutoShift::default();
+
+        auto_shift.press(&mut keyboard, KEY_A);
+        assert!(auto_shift.tick(&mut keyboard, AUTO_SHIFT_TIMEOUT_MS));
+        assert!(!auto_shift.is_pending());
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        auto_shift.release(&mut keyboard, KEY_A).unwrap();
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_auto_shift_tap_send_error() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+        let mut auto_shift = AutoShift::default();
+
+        auto_shift.press(&mut keyboard, KEY_A);
+        usb_dev.bus().fail_next_write(UsbError::InvalidEndpoint);
+        assert!(auto_shift.release(&mut keyboard, KEY_A).is_err());
+
+        // the failed tap doesn't leave the key pressed
+        assert!(!auto_shift.is_pending());
+        assert!(!keyboard.is_key_pressed(KEY_A));
+    }
+
+    #[test]
+    fn test_auto_shift_explicit_shift() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+        let mut auto_shift = AutoShift::default();
+
+        auto_shift.press(&mut keyboard, RIGHT_SHIFT);
+        auto_shift.press(&mut keyboard, KEY_A);
+        auto_shift.tick(&mut keyboard, AUTO_SHIFT_TIMEOUT_MS);
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0x20);
+
+        // the explicit shift is kept after releasing the key
+        auto_shift.release(&mut keyboard, KEY_A).unwrap();
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0x20);
+
+        // pressing another key sends the held back key unshifted
+        auto_shift.release(&mut keyboard, RIGHT_SHIFT).unwrap();
+        auto_shift.press(&mut keyboard, KEY_A);
+        auto_shift.press(&mut keyboard, KEY_A + 1);
+        assert!(keyboard.is_key_pressed(KEY_A));
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0);
+    }
+
+    #[test]
+    fn test_auto_shift_right_shift_while_shifted() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+        let mut auto_shift = AutoShift::default();
+
+        auto_shift.press(&mut keyboard, KEY_A);
+        auto_shift.tick(&mut keyboard, AUTO_SHIFT_TIMEOUT_MS);
+        auto_shift.press(&mut keyboard, RIGHT_SHIFT);
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0x22);
+
+        // the added left shift is released with the key, the right shift stays held
+        auto_shift.release(&mut keyboard, KEY_A).unwrap();
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0x20);
+
+        auto_shift.release(&mut keyboard, RIGHT_SHIFT).unwrap();
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0);
+    }
+
+    #[test]
+    fn test_auto_shift_left_shift_while_shifted() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+        let mut auto_shift = AutoShift::default();
+
+        auto_shift.press(&mut keyboard, KEY_A);
+        auto_shift.tick(&mut keyboard, AUTO_SHIFT_TIMEOUT_MS);
+        auto_shift.press(&mut keyboard, LEFT_SHIFT);
+
+        // the left shift is now held explicitly, and kept after releasing the key
+        auto_shift.release(&mut keyboard, KEY_A).unwrap();
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0x02);
+    }
+}
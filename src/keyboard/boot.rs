@@ -1,8 +1,8 @@
-use usb_device::Result;
 use usbd_hid::hid_class::{
     HidClassSettings, HidProtocol, HidSubClass, ProtocolModeConfig,
 };
 
+use crate::error::{KeyboardError, KeyboardResult};
 use crate::hid_settings::{HIDReport, HIDReportId};
 
 use super::*;
@@ -18,7 +18,7 @@ pub const fn boot_hid_class_settings(protocol: HidProtocol) -> HidClassSettings
 
 pub trait BootKeyboard {
     /// End the keyboard reports.
-    fn end(&mut self) -> Result<()>;
+    fn end(&mut self) -> KeyboardResult<()>;
 
     /// Sending the current HID report to the host:
     ///
@@ -44,12 +44,13 @@ pub trait BootKeyboard {
     /// 1. A report with toggled-off non-modifiers removed.
     /// 2. A report with changes to modifiers.
     /// 3. A report with toggled-on non-modifiers added.
-    fn send_report(&mut self) -> Result<()>;
+    fn send_report(&mut self) -> KeyboardResult<()>;
 
     /// Press a key, and add it to the current report.
     ///
     /// Returns 1 if the key is in the printable keycodes, or is a modifier key.
-    /// Returns 0 otherwise.
+    /// Returns 0 otherwise, or if the maximum rollover is reached. Presses dropped by the
+    /// rollover limit are reported to the [RolloverOverflowHook](crate::RolloverOverflowHook).
     fn press(&mut self, key: u8) -> usize;
 
     /// Release a pressed key if the keycode is present in the current report.
@@ -65,35 +66,77 @@ pub trait BootKeyboard {
     fn was_key_pressed(&self, key: u8) -> bool;
 }
 
-impl BootKeyboard for Keyboard<'_> {
-    fn end(&mut self) -> Result<()> {
+impl<B: UsbBus> Keyboard<'_, B> {
+    // Sends the last keyboard report to the host, and notifies the observer.
+    fn send_last_report(&mut self) -> KeyboardResult<()> {
+        let report = HIDReport::Keyboard(self.last_report);
+        // replace the Ok(usize) with Ok(())
+        let ret = self.push_report(report).map(|_| ());
+        self.observe_report(HIDReportId::Keyboard, report, &ret);
+
+        ret.map_err(KeyboardError::from)
+    }
+}
+
+impl<B: UsbBus> BootKeyboard for Keyboard<'_, B> {
+    fn end(&mut self) -> KeyboardResult<()> {
         self.release_all();
-        self.send_report()
+        BootKeyboard::send_report(self)
     }
 
-    fn send_report(&mut self) -> Result<()> {
+    fn send_report(&mut self) -> KeyboardResult<()> {
+        self.check_device_state()?;
+
+        let old_modifiers = self.last_report.modifier;
+        let new_modifiers = self.report.modifier;
+
+        if old_modifiers != new_modifiers {
+            // There was at least one modifier change (toggled on or off), remove any
+            // non-modifiers from the stored previous report that toggled off in the new
+            // report, and send it to the host.
+            let keycodes = self.report.keycodes;
+            let mut non_modifiers_toggled_off = false;
+
+            for last_key in self.last_report.keycodes.iter_mut() {
+                if *last_key != 0 && !keycodes.contains(last_key) {
+                    *last_key = 0;
+                    non_modifiers_toggled_off = true;
+                }
+            }
+
+            if non_modifiers_toggled_off {
+                utils::sort_keycodes_stable(self.last_report.keycodes.as_mut());
+                self.send_last_report()?;
+            }
+
+            self.last_report.modifier = new_modifiers;
+            self.send_last_report()?;
+        }
+
         if self.keycodes_changed() {
-            let report = self.report().clone();
-            // replace the Ok(usize) with Ok(())
-            let ret = self.hid_class_mut().push_input(&report).map(|_| ());
-            self.observer
-                .observe_report(HIDReportId::Keyboard, HIDReport::Keyboard(report), &ret);
-            self.last_report = self.report;
-
-            ret
-        } else {
-            Ok(())
+            self.last_report.keycodes = self.report.keycodes;
+            self.send_last_report()?;
         }
+
+        self.commit_report();
+
+        Ok(())
     }
 
     fn press(&mut self, key: u8) -> usize {
         if is_modifier(key) {
             self.report.modifier |= key_to_modifier_bitfield(key);
             1
+        } else if !is_printable(key) {
+            0
         } else {
             let mut done = false;
 
-            for keycode in self.report.keycodes.iter_mut() {
+            // Compact the keycodes first, hosts stop reading at the first free slot, and a key
+            // after a free slot would be missed by the duplicate check below.
+            utils::sort_keycodes_stable(self.report.keycodes.as_mut());
+
+            for keycode in self.report.keycodes[..self.max_rollover].iter_mut() {
                 if *keycode == key {
                     done = true;
                     break;
@@ -106,6 +149,10 @@ impl BootKeyboard for Keyboard<'_> {
                 }
             }
 
+            if !done {
+                self.observer.observe_rollover_overflow(key);
+            }
+
             done as usize
         }
     }
@@ -123,7 +170,7 @@ impl BootKeyboard for Keyboard<'_> {
                 }
             }
 
-            utils::sort_keycodes(self.report.keycodes.as_mut());
+            utils::sort_keycodes_stable(self.report.keycodes.as_mut());
         }
 
         1
@@ -155,3 +202,167 @@ impl BootKeyboard for Keyboard<'_> {
         found && is_printable(key)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+
+    fn assert_max_rollover(max_rollover: usize) {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = KeyboardBuilder::new()
+            .max_rollover(max_rollover)
+            .build(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        assert_eq!(keyboard.max_rollover(), max_rollover);
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+        let keys = key_a..key_a + max_rollover as u8;
+
+        for key in keys.clone() {
+            keyboard.try_press(key).unwrap();
+        }
+        // modifiers don't take keycode slots
+        keyboard
+            .try_press(KeyboardUsage::KeyboardLeftShift as u8)
+            .unwrap();
+
+        let extra = key_a + max_rollover as u8;
+        assert!(matches!(
+            keyboard.try_press(extra),
+            Err(KeyboardError::RolloverFull)
+        ));
+        assert!(!keyboard.is_key_pressed(extra));
+        assert!(keyboard.pressed_keys().eq(keys));
+
+        // releasing a key frees its slot
+        BootKeyboard::release(&mut keyboard, key_a);
+        keyboard.try_press(extra).unwrap();
+        assert_eq!(keyboard.count_pressed_keys() as usize, max_rollover);
+    }
+
+    #[test]
+    fn test_boot_max_rollover() {
+        assert_max_rollover(3);
+        assert_max_rollover(6);
+    }
+
+    #[test]
+    fn test_boot_shifted_key_order() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let key_4 = KeyboardUsage::Keyboard4Dollar as u8;
+        let shift = key_to_modifier_bitfield(KeyboardUsage::KeyboardLeftShift as u8);
+
+        keyboard.press_with_modifiers(key_4, shift);
+        BootKeyboard::send_report(&mut keyboard).unwrap();
+
+        keyboard.release_with_modifiers(key_4, shift);
+        BootKeyboard::send_report(&mut keyboard).unwrap();
+
+        // modifiers are pressed before, and released after the key
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_boot_press_compacts_keycodes() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let [a, b, c, d] = [0x04, 0x05, 0x06, 0x07];
+
+        for key in [a, b, c] {
+            BootKeyboard::press(&mut keyboard, key);
+        }
+        BootKeyboard::release(&mut keyboard, b);
+        BootKeyboard::press(&mut keyboard, d);
+
+        let keycodes = keyboard.report().keycodes;
+        let first_free = keycodes.iter().position(|&k| k == 0).unwrap();
+        assert!(keycodes[first_free..].iter().all(|&k| k == 0));
+        assert_eq!(first_free, 3);
+
+        // free slots left in the report are compacted, keeping the key order
+        keyboard.report_mut().keycodes = [a, 0, 0, c, 0, 0];
+        BootKeyboard::press(&mut keyboard, d);
+        BootKeyboard::press(&mut keyboard, c);
+        assert_eq!(keyboard.report().keycodes, [a, c, d, 0, 0, 0]);
+
+        // releasing a key keeps the order of the remaining keys
+        keyboard.report_mut().keycodes = [a, b, c, 0, 0, 0];
+        BootKeyboard::release(&mut keyboard, a);
+        assert_eq!(keyboard.report().keycodes, [b, c, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_boot_modifier_release_keeps_key_order() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let [a, b, c] = [0x04, 0x05, 0x06];
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+
+        for key in [a, b, c] {
+            BootKeyboard::press(&mut keyboard, key);
+        }
+        BootKeyboard::send_report(&mut keyboard).unwrap();
+
+        BootKeyboard::press(&mut keyboard, shift);
+        BootKeyboard::release(&mut keyboard, a);
+        BootKeyboard::send_report(&mut keyboard).unwrap();
+
+        // the released key is sent before the modifier change, keeping the order of the others
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x00, 0x00, a, b, c, 0x00, 0x00, 0x00],
+                [0x00, 0x00, b, c, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, b, c, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    static OVERFLOWS: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_overflow(key: u8) {
+        assert_eq!(key, KeyboardUsage::KeyboardGg as u8);
+        OVERFLOWS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_boot_rollover_overflow_hook() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let observer = HIDReportObserver::default().with_rollover_overflow_hook(count_overflow);
+        let mut keyboard = KeyboardBuilder::new()
+            .boot()
+            .observer(observer)
+            .build(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+
+        for key in key_a..key_a + 7 {
+            BootKeyboard::press(&mut keyboard, key);
+        }
+        // pressing an already pressed key, or a modifier, doesn't overflow
+        BootKeyboard::press(&mut keyboard, key_a);
+        BootKeyboard::press(&mut keyboard, KeyboardUsage::KeyboardLeftShift as u8);
+
+        assert_eq!(OVERFLOWS.load(Ordering::SeqCst), 1);
+    }
+}
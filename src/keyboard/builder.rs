@@ -0,0 +1,417 @@
+use usb_device::bus::UsbBus;
+use usbd_hid::descriptor::{
+    KeyboardReport, MediaKeyboardReport, SerializedDescriptor, SystemControlReport,
+};
+use usbd_hid::hid_class::{HIDClass, HidCountryCode, HidProtocol, HidProtocolMode};
+
+use crate::HIDReportObserver;
+
+use super::composite::{self, COMPOSITE_DESCRIPTOR};
+use super::media::{self, ConsumerRolloverPolicy};
+use super::usb_strings::UsbStrings;
+use super::{
+    boot, keyboard_locale, nkro, system_control, Keyboard, KeyboardUsbBusAllocator,
+    ProtocolChangeHook, POLL_MS, ZERO_KEYS,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum DeviceMode {
+    Boot,
+    Nkro,
+    Media,
+    SystemControl,
+    Composite,
+}
+
+/// Builder for configuring a boot, NKRO, media, system control, or composite [Keyboard].
+pub struct KeyboardBuilder {
+    observer: HIDReportObserver,
+    poll_ms: u8,
+    locale: HidCountryCode,
+    protocol: Option<HidProtocolMode>,
+    mode: DeviceMode,
+    consumer_rollover: ConsumerRolloverPolicy,
+    max_rollover: usize,
+    on_protocol_change: Option<ProtocolChangeHook>,
+    usb_strings: Option<UsbStrings<'static>>,
+    vid_pid: Option<(u16, u16)>,
+    num_lock_remap: bool,
+    interface_number: u8,
+}
+
+impl KeyboardBuilder {
+    /// Creates a new [KeyboardBuilder] for a boot keyboard with the default settings.
+    pub const fn new() -> Self {
+        Self {
+            observer: HIDReportObserver::default(),
+            poll_ms: POLL_MS,
+            locale: keyboard_locale(),
+            protocol: None,
+            mode: DeviceMode::Boot,
+            consumer_rollover: ConsumerRolloverPolicy::DropNewest,
+            max_rollover: ZERO_KEYS.len(),
+            on_protocol_change: None,
+            usb_strings: None,
+            vid_pid: None,
+            num_lock_remap: false,
+            interface_number: 0,
+        }
+    }
+
+    /// Sets the [HIDReportObserver] notified on HID report events.
+    pub const fn observer(mut self, observer: HIDReportObserver) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Sets the interval for the host to poll the keyboard, in milliseconds.
+    pub const fn poll_ms(mut self, poll_ms: u8) -> Self {
+        self.poll_ms = poll_ms;
+        self
+    }
+
+    /// Sets the keyboard locale reported to the host.
+    pub const fn locale(mut self, locale: HidCountryCode) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Sets the default protocol of the keyboard.
+    ///
+    /// Defaults to [HidProtocolMode::Report] for NKRO keyboards, and [HidProtocolMode::Boot]
+    /// otherwise.
+    pub const fn protocol(mut self, protocol: HidProtocolMode) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Sets the [ConsumerRolloverPolicy] for over-capacity consumer usages.
+    pub const fn consumer_rollover(mut self, policy: ConsumerRolloverPolicy) -> Self {
+        self.consumer_rollover = policy;
+        self
+    }
+
+    /// Sets the maximum number of keycodes pressed at once on a boot keyboard.
+    ///
+    /// See [Keyboard::set_max_rollover].
+    pub const fn max_rollover(mut self, max_rollover: usize) -> Self {
+        self.max_rollover = max_rollover;
+        self
+    }
+
+    /// Sets the [ProtocolChangeHook] fired when the host changes the keyboard protocol.
+    pub const fn on_protocol_change(mut self, hook: ProtocolChangeHook) -> Self {
+        self.on_protocol_change = Some(hook);
+        self
+    }
+
+    /// Sets the [UsbStrings] used by [Keyboard::usb_device_builder].
+    pub const fn usb_strings(mut self, strings: UsbStrings<'static>) -> Self {
+        self.usb_strings = Some(strings);
+        self
+    }
+
+    /// Sets the USB vendor and product IDs used by [Keyboard::usb_device_builder].
+    ///
+    /// # Panics
+    ///
+    /// Panics if either ID is zero. In a `const` context, e.g. a `static` builder, the build
+    /// fails instead.
+    pub const fn vid_pid(mut self, vid: u16, pid: u16) -> Self {
+        assert!(vid != 0 && pid != 0, "USB vendor and product IDs must be non-zero");
+        self.vid_pid = Some((vid, pid));
+        self
+    }
+
+    /// Sets whether pressed keypad keys follow the host Num Lock state.
+    ///
+    /// See [Keyboard::set_num_lock_remap].
+    pub const fn num_lock_remap(mut self, enabled: bool) -> Self {
+        self.num_lock_remap = enabled;
+        self
+    }
+
+    /// Sets the USB interface number allocated to the keyboard, the number of interfaces
+    /// allocated on the USB bus before the keyboard is built.
+    ///
+    /// See [Keyboard::set_interface_number].
+    pub const fn interface_number(mut self, interface_number: u8) -> Self {
+        self.interface_number = interface_number;
+        self
+    }
+
+    /// Builds a boot keyboard.
+    pub const fn boot(mut self) -> Self {
+        self.mode = DeviceMode::Boot;
+        self
+    }
+
+    /// Builds an NKRO keyboard.
+    pub const fn nkro(mut self) -> Self {
+        self.mode = DeviceMode::Nkro;
+        self
+    }
+
+    /// Builds a media keyboard.
+    pub const fn media(mut self) -> Self {
+        self.mode = DeviceMode::Media;
+        self
+    }
+
+    /// Builds a system control keyboard.
+    pub const fn system_control(mut self) -> Self {
+        self.mode = DeviceMode::SystemControl;
+        self
+    }
+
+    /// Builds a composite keyboard, sending keyboard, media, and system control reports over one
+    /// interface.
+    ///
+    /// Reports are prefixed with their [HIDReportId](crate::HIDReportId), see
+    /// [COMPOSITE_DESCRIPTOR]. Composite keyboards always use the report protocol, since report
+    /// IDs are not supported by the boot protocol.
+    pub const fn composite(mut self) -> Self {
+        self.mode = DeviceMode::Composite;
+        self
+    }
+
+    /// Builds the configured [Keyboard] on the USB bus.
+    pub fn build<B: UsbBus>(self, bus: &KeyboardUsbBusAllocator<B>) -> Keyboard<'_, B> {
+        let (descriptor, mut settings, default_protocol) = match self.mode {
+            DeviceMode::Boot => (
+                KeyboardReport::desc(),
+                boot::boot_hid_class_settings(HidProtocol::Keyboard),
+                HidProtocolMode::Boot,
+            ),
+            DeviceMode::Nkro => (
+                nkro::nkro_descriptor(),
+                nkro::nkro_hid_class_settings(),
+                HidProtocolMode::Report,
+            ),
+            DeviceMode::Media => (
+                MediaKeyboardReport::desc(),
+                media::media_hid_class_settings(),
+                HidProtocolMode::Boot,
+            ),
+            DeviceMode::SystemControl => (
+                SystemControlReport::desc(),
+                system_control::system_control_hid_class_settings(),
+                HidProtocolMode::Boot,
+            ),
+            DeviceMode::Composite => (
+                COMPOSITE_DESCRIPTOR,
+                composite::composite_hid_class_settings(),
+                HidProtocolMode::Report,
+            ),
+        };
+        settings.locale = self.locale;
+
+        let hid_class = HIDClass::new_with_settings(bus, descriptor, self.poll_ms, settings);
+        let protocol = self.protocol.unwrap_or(default_protocol);
+
+        let mut keyboard = Keyboard {
+            observer: self.observer,
+            default_protocol: protocol,
+            protocol,
+            nkro: self.mode == DeviceMode::Nkro,
+            consumer_rollover: self.consumer_rollover,
+            on_protocol_change: self.on_protocol_change,
+            usb_strings: self.usb_strings,
+            vid_pid: self.vid_pid,
+            num_lock_remap: self.num_lock_remap,
+            report_ids: self.mode == DeviceMode::Composite,
+            interface_number: self.interface_number,
+            ..Keyboard::new_with_hid_class(hid_class)
+        };
+        keyboard.set_max_rollover(self.max_rollover);
+
+        keyboard
+    }
+}
+
+impl Default for KeyboardBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use usb_device::bus::UsbBusAllocator;
+    use usb_device::device::UsbDevice;
+    use usb_device::Result;
+    use usbd_hid::descriptor::SystemControlKey;
+
+    use super::*;
+    use crate::hid_settings::{HIDReport, HIDReportId};
+    use crate::system_control::SystemControlKeyboard;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use crate::{KeyboardOps, HID_ENDPOINT_SIZE};
+
+    static NKRO_REPORTS: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_nkro_reports(id: HIDReportId, _report: HIDReport, result: &Result<()>) {
+        if id == HIDReportId::NKROKeyboard && result.is_ok() {
+            NKRO_REPORTS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_build_nkro() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = KeyboardBuilder::new()
+            .nkro()
+            .poll_ms(1)
+            .locale(HidCountryCode::German)
+            .observer(HIDReportObserver::new(count_nkro_reports))
+            .build(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        assert!(keyboard.is_nkro());
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Report);
+        assert_eq!(keyboard.default_protocol(), HidProtocolMode::Report);
+        assert_eq!(usb_dev.bus().in_interval(1), 1);
+
+        keyboard.press(0x04);
+        keyboard.send_report().unwrap();
+
+        assert_eq!(NKRO_REPORTS.load(Ordering::SeqCst), 1);
+
+        let mut bytes = [0x00; 2 + nkro::NKRO_KEYCODES_LEN];
+        bytes[2] = 0x10;
+        assert_eq!(usb_dev.bus().in_reports(), [bytes]);
+    }
+
+    #[test]
+    fn test_build_endpoint_size() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let _boot = KeyboardBuilder::new().boot().build(&alloc);
+        let _nkro = KeyboardBuilder::new().nkro().build(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        for ep_index in [1, 2] {
+            assert_eq!(
+                usb_dev.bus().in_max_packet_size(ep_index),
+                HID_ENDPOINT_SIZE
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_boot_protocol() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let keyboard = KeyboardBuilder::new()
+            .nkro()
+            .boot()
+            .protocol(HidProtocolMode::Report)
+            .build(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        assert!(!keyboard.is_nkro());
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Report);
+        assert_eq!(usb_dev.bus().in_interval(1), POLL_MS);
+    }
+
+    #[test]
+    fn test_build_system_control() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = KeyboardBuilder::new().system_control().poll_ms(4).build(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        assert!(!keyboard.is_nkro());
+        assert_eq!(keyboard.protocol(), HidProtocolMode::Boot);
+        assert_eq!(usb_dev.bus().in_interval(1), 4);
+
+        SystemControlKeyboard::press(&mut keyboard, SystemControlKey::Sleep as u8);
+        SystemControlKeyboard::send_report(&mut keyboard).unwrap();
+        assert_eq!(usb_dev.bus().in_reports(), [[0x82]]);
+    }
+
+    // Requests the string descriptor with the index, returning its UTF-16 characters.
+    fn get_string(usb_dev: &mut UsbDevice<'_, MockUsbBus>, index: u8) -> std::vec::Vec<u16> {
+        usb_dev
+            .bus()
+            .queue_setup([0x80, 0x06, index, 0x03, 0x09, 0x04, 0xff, 0x00]);
+        usb_dev.poll(&mut []);
+
+        let desc = usb_dev.bus().take_control_in();
+        assert_eq!(desc[0] as usize, desc.len());
+        assert_eq!(desc[1], 0x03);
+
+        desc[2..]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_build_usb_strings() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let strings = UsbStrings::new("Keyboardio", "Model 100").serial("kbio-0042");
+        let keyboard = KeyboardBuilder::new()
+            .usb_strings(strings)
+            .vid_pid(0x1209, 0x2301)
+            .build(&alloc);
+
+        assert_eq!(keyboard.usb_strings(), Some(strings));
+
+        let mut usb_dev = keyboard
+            .usb_device_builder(&alloc)
+            .unwrap()
+            .max_packet_size_0(64)
+            .build();
+
+        for (index, expected) in [(1, "Keyboardio"), (2, "Model 100"), (3, "kbio-0042")] {
+            let chars = get_string(&mut usb_dev, index);
+            assert!(chars.into_iter().eq(expected.encode_utf16()));
+        }
+    }
+
+    #[test]
+    fn test_build_vid_pid() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let keyboard = KeyboardBuilder::new().vid_pid(0x1209, 0x2301).build(&alloc);
+
+        assert_eq!(keyboard.vid_pid(), Some((0x1209, 0x2301)));
+
+        let mut usb_dev = keyboard
+            .usb_device_builder(&alloc)
+            .unwrap()
+            .max_packet_size_0(64)
+            .build();
+
+        // GET_DESCRIPTOR(Device)
+        usb_dev
+            .bus()
+            .queue_setup([0x80, 0x06, 0x00, 0x01, 0x00, 0x00, 0x12, 0x00]);
+        usb_dev.poll(&mut []);
+
+        let desc = usb_dev.bus().take_control_in();
+        assert_eq!(desc[1], 0x01);
+        assert_eq!(desc[8..12], [0x09, 0x12, 0x01, 0x23]);
+    }
+
+    #[test]
+    fn test_build_no_vid_pid() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let keyboard = KeyboardBuilder::new().build(&alloc);
+
+        assert!(keyboard.usb_device_builder(&alloc).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_build_zero_vid() {
+        let _ = KeyboardBuilder::new().vid_pid(0, 0x2301);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_build_zero_pid() {
+        let _ = KeyboardBuilder::new().vid_pid(0x1209, 0);
+    }
+}
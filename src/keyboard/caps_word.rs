@@ -0,0 +1,206 @@
+use usbd_hid::descriptor::KeyboardUsage;
+
+use super::{key_to_modifier_bitfield, KeyboardOps};
+
+const KEY_A: u8 = KeyboardUsage::KeyboardAa as u8;
+const KEY_Z: u8 = KeyboardUsage::KeyboardZz as u8;
+const KEY_1: u8 = KeyboardUsage::Keyboard1Exclamation as u8;
+const KEY_0: u8 = KeyboardUsage::Keyboard0CloseParens as u8;
+const BACKSPACE: u8 = KeyboardUsage::KeyboardBackspace as u8;
+const MINUS: u8 = KeyboardUsage::KeyboardDashUnderscore as u8;
+const LEFT_CONTROL: u8 = KeyboardUsage::KeyboardLeftControl as u8;
+const LEFT_SHIFT: u8 = KeyboardUsage::KeyboardLeftShift as u8;
+const RIGHT_SHIFT: u8 = KeyboardUsage::KeyboardRightShift as u8;
+const RIGHT_GUI: u8 = KeyboardUsage::KeyboardRightGUI as u8;
+const SHIFT_MODIFIERS: u8 =
+    key_to_modifier_bitfield(LEFT_SHIFT) | key_to_modifier_bitfield(RIGHT_SHIFT);
+
+/// Gets whether the key is one of the letters `a` to `z`.
+pub const fn is_caps_word_letter(key: u8) -> bool {
+    matches!(key, KEY_A..=KEY_Z)
+}
+
+/// Gets whether [CapsWord] shifts the key: the letters, and `-` into `_`.
+pub const fn is_caps_word_shifted(key: u8) -> bool {
+    is_caps_word_letter(key) || key == MINUS
+}
+
+/// Gets whether the key keeps [CapsWord] active without being shifted: digits, backspace, and
+/// modifiers.
+pub const fn continues_caps_word(key: u8) -> bool {
+    matches!(key, KEY_1..=KEY_0 | BACKSPACE | LEFT_CONTROL..=RIGHT_GUI)
+}
+
+/// Shifts the letters of a word, e.g. for typing `CONST_NAME` without holding shift.
+///
+/// Pass key presses through [press](Self::press) once [activated](Self::activate). Letters and
+/// `-` are pressed with left shift, so `-` types `_`. Digits, backspace, and modifiers are
+/// pressed unshifted, and any other key, e.g. space, enter, or other punctuation, deactivates
+/// [CapsWord] before it is pressed.
+///
+/// Shift added by [CapsWord] is released when it deactivates. Shift pressed explicitly is left
+/// alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CapsWord {
+    active: bool,
+    added_shift: bool,
+}
+
+impl CapsWord {
+    /// Creates a new inactive [CapsWord].
+    pub const fn new() -> Self {
+        Self {
+            active: false,
+            added_shift: false,
+        }
+    }
+
+    /// Gets whether letters are shifted.
+    pub const fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Starts shifting letters, e.g. when a caps word key is tapped.
+    pub fn activate(&mut self) {
+        self.active = true;
+    }
+
+    /// Stops shifting letters, and releases the shift added by [CapsWord] from the current
+    /// report.
+    pub fn deactivate<K: KeyboardOps>(&mut self, keyboard: &mut K) {
+        self.release_added_shift(keyboard);
+        self.active = false;
+    }
+
+    /// Press a key on the keyboard, shifting letters while active.
+    ///
+    /// Returns 1 if the key was added to the current report, 0 otherwise.
+    pub fn press<K: KeyboardOps>(&mut self, keyboard: &mut K, key: u8) -> usize {
+        if !self.active {
+            return keyboard.press(key);
+        }
+
+        if is_caps_word_shifted(key) {
+            if keyboard.modifiers() & SHIFT_MODIFIERS == 0 {
+                keyboard.add_modifiers(key_to_modifier_bitfield(LEFT_SHIFT));
+                self.added_shift = true;
+            }
+        } else if key == LEFT_SHIFT && self.added_shift {
+            // the shift is now held explicitly, keep it on deactivation
+            self.added_shift = false;
+        } else if continues_caps_word(key) {
+            self.release_added_shift(keyboard);
+        } else {
+            self.deactivate(keyboard);
+        }
+
+        keyboard.press(key)
+    }
+
+    /// Release a key on the keyboard.
+    ///
+    /// Returns 1 if the key was released, 0 otherwise.
+    pub fn release<K: KeyboardOps>(&mut self, keyboard: &mut K, key: u8) -> usize {
+        keyboard.release(key)
+    }
+
+    fn release_added_shift<K: KeyboardOps>(&mut self, keyboard: &mut K) {
+        if self.added_shift {
+            keyboard.remove_modifiers(key_to_modifier_bitfield(LEFT_SHIFT));
+            self.added_shift = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usb_device::bus::UsbBusAllocator;
+
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use crate::Keyboard;
+
+    #[test]
+    fn test_caps_word_typing() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+        let mut caps_word = CapsWord::new();
+
+        caps_word.activate();
+        for key in [
+            KeyboardUsage::KeyboardAa,
+            KeyboardUsage::KeyboardBb,
+            KeyboardUsage::KeyboardCc,
+            KeyboardUsage::KeyboardSpacebar,
+        ] {
+            caps_word.press(&mut keyboard, key as u8);
+            KeyboardOps::send_report(&mut keyboard).unwrap();
+            caps_word.release(&mut keyboard, key as u8);
+            KeyboardOps::send_report(&mut keyboard).unwrap();
+        }
+
+        // the space deactivated caps word
+        assert!(!caps_word.is_active());
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+
+        // letters after the word are not shifted
+        caps_word.press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0);
+    }
+
+    #[test]
+    fn test_caps_word_explicit_shift() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+        let mut caps_word = CapsWord::new();
+
+        caps_word.activate();
+        caps_word.press(&mut keyboard, RIGHT_SHIFT);
+        caps_word.press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0x20);
+
+        // digits keep caps word active, and the explicit shift is kept on deactivation
+        caps_word.press(&mut keyboard, KeyboardUsage::Keyboard1Exclamation as u8);
+        assert!(caps_word.is_active());
+        caps_word.press(&mut keyboard, KeyboardUsage::KeyboardEnter as u8);
+        assert!(!caps_word.is_active());
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0x20);
+    }
+
+    #[test]
+    fn test_caps_word_underscore() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+        let mut caps_word = CapsWord::new();
+
+        caps_word.activate();
+        caps_word.press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        caps_word.release(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+
+        // `-` is shifted into `_`, and keeps caps word active
+        caps_word.press(&mut keyboard, KeyboardUsage::KeyboardDashUnderscore as u8);
+        assert!(caps_word.is_active());
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0x02);
+
+        caps_word.press(&mut keyboard, KeyboardUsage::KeyboardSpacebar as u8);
+        assert!(!caps_word.is_active());
+        assert_eq!(KeyboardOps::modifiers(&keyboard), 0);
+    }
+}
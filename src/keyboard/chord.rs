@@ -0,0 +1,164 @@
+/// Maximum number of pressed keys considered when matching chords.
+pub const CHORD_MAX_PRESSED_KEYS: usize = 16;
+
+/// Callback function fired with the action of a detected [Chord].
+pub type ChordHook = fn(action: u8);
+
+/// Key combination triggering an action when pressed together.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chord<'c> {
+    /// Keys that must all be pressed, in any order.
+    pub keys: &'c [u8],
+    /// Action reported when the chord is detected.
+    pub action: u8,
+}
+
+impl<'c> Chord<'c> {
+    /// Creates a new [Chord].
+    pub const fn new(keys: &'c [u8], action: u8) -> Self {
+        Self { keys, action }
+    }
+}
+
+/// Detects registered [Chord]s from the currently pressed keys.
+///
+/// A chord fires when exactly its keys are pressed, and the last of them was pressed within
+/// the timeout after the first. Each chord fires once until all keys are released.
+pub struct ChordTracker<'c> {
+    chords: &'c [Chord<'c>],
+    timeout_ms: u32,
+    // Time since the first key of the current press was pressed.
+    pressed_ms: Option<u32>,
+    fired: bool,
+    hook: ChordHook,
+}
+
+impl<'c> ChordTracker<'c> {
+    /// Creates a new [ChordTracker] for the table of chords.
+    pub const fn new(chords: &'c [Chord<'c>], timeout_ms: u32, hook: ChordHook) -> Self {
+        Self {
+            chords,
+            timeout_ms,
+            pressed_ms: None,
+            fired: false,
+            hook,
+        }
+    }
+
+    /// Gets the chord timeout in milliseconds.
+    pub const fn timeout_ms(&self) -> u32 {
+        self.timeout_ms
+    }
+
+    /// Sets the chord timeout in milliseconds.
+    pub fn set_timeout_ms(&mut self, timeout_ms: u32) {
+        self.timeout_ms = timeout_ms;
+    }
+
+    /// Advances the tracker clock by the elapsed milliseconds.
+    ///
+    /// The clock saturates instead of wrapping around, so a press held for longer than
+    /// `u32::MAX` milliseconds stays timed out.
+    pub fn tick(&mut self, elapsed_ms: u32) {
+        if let Some(pressed_ms) = self.pressed_ms.as_mut() {
+            *pressed_ms = pressed_ms.saturating_add(elapsed_ms);
+        }
+    }
+
+    /// Updates the tracker with the currently pressed keys, e.g. from
+    /// [Keyboard::pressed_keys](crate::Keyboard::pressed_keys).
+    ///
+    /// Returns the action of the detected chord, after firing the [ChordHook].
+    pub fn update<I: IntoIterator<Item = u8>>(&mut self, pressed: I) -> Option<u8> {
+        let mut keys = [0u8; CHORD_MAX_PRESSED_KEYS];
+        let mut len = 0;
+
+        for key in pressed.into_iter().take(CHORD_MAX_PRESSED_KEYS) {
+            keys[len] = key;
+            len += 1;
+        }
+
+        if len == 0 {
+            self.pressed_ms = None;
+            self.fired = false;
+            return None;
+        }
+
+        let pressed_ms = *self.pressed_ms.get_or_insert(0);
+
+        if self.fired || pressed_ms > self.timeout_ms {
+            return None;
+        }
+
+        let pressed = &keys[..len];
+        let chord = self.chords.iter().find(|chord| {
+            chord.keys.len() == pressed.len() && chord.keys.iter().all(|k| pressed.contains(k))
+        })?;
+
+        self.fired = true;
+        (self.hook)(chord.action);
+
+        Some(chord.action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    use super::*;
+
+    const KEY_J: u8 = 0x0d;
+    const KEY_K: u8 = 0x0e;
+    const KEY_ESC: u8 = 0x29;
+
+    static CHORDS: [Chord<'static>; 1] = [Chord::new(&[KEY_J, KEY_K], KEY_ESC)];
+
+    static FIRED: AtomicU8 = AtomicU8::new(0);
+
+    fn hook(action: u8) {
+        FIRED.store(action, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_chord_within_window() {
+        let mut tracker = ChordTracker::new(&CHORDS, 50, hook);
+
+        assert_eq!(tracker.update([KEY_J]), None);
+        tracker.tick(30);
+        assert_eq!(tracker.update([KEY_J, KEY_K]), Some(KEY_ESC));
+        assert_eq!(FIRED.swap(0, Ordering::SeqCst), KEY_ESC);
+
+        // held chords only fire once
+        tracker.tick(10);
+        assert_eq!(tracker.update([KEY_K, KEY_J]), None);
+
+        tracker.tick(10);
+        assert_eq!(tracker.update([]), None);
+        assert_eq!(tracker.update([KEY_K, KEY_J]), Some(KEY_ESC));
+    }
+
+    #[test]
+    fn test_chord_outside_window() {
+        let mut tracker = ChordTracker::new(&CHORDS, 50, |_| panic!("unexpected chord"));
+
+        assert_eq!(tracker.update([KEY_J]), None);
+        tracker.tick(51);
+        assert_eq!(tracker.update([KEY_J, KEY_K]), None);
+
+        // extra keys do not match the chord
+        tracker.update([]);
+        assert_eq!(tracker.update([KEY_J, KEY_K, KEY_ESC]), None);
+    }
+
+    #[test]
+    fn test_chord_clock_saturates() {
+        let mut tracker = ChordTracker::new(&CHORDS, 50, |_| panic!("unexpected chord"));
+
+        assert_eq!(tracker.update([KEY_J]), None);
+        tracker.tick(u32::MAX - 1);
+        // a wrapping clock would be back inside the window
+        tracker.tick(10);
+        assert_eq!(tracker.update([KEY_J, KEY_K]), None);
+    }
+}
@@ -0,0 +1,317 @@
+use usbd_hid::descriptor::KeyboardUsage;
+
+use crate::error::KeyboardResult;
+
+use super::{key_to_modifier_bitfield, KeyboardOps};
+
+/// Maximum number of keys following the compose key in a [ComposeSequence].
+pub const COMPOSE_MAX_KEYS: usize = 2;
+
+/// Keys typed after the compose key, resolving to a single character.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComposeSequence<'c> {
+    /// Keys pressed after the compose key, in order. Up to [COMPOSE_MAX_KEYS] keys are matched.
+    pub keys: &'c [u8],
+    /// Character typed when the sequence is completed.
+    pub output: char,
+}
+
+impl<'c> ComposeSequence<'c> {
+    /// Creates a new [ComposeSequence].
+    pub const fn new(keys: &'c [u8], output: char) -> Self {
+        Self { keys, output }
+    }
+}
+
+/// Result of passing a key press to [Compose::process].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ComposeResult {
+    /// The key is not part of a compose sequence, and should be pressed as usual.
+    Passthrough(u8),
+    /// The key was consumed by an unfinished compose sequence.
+    Pending,
+    /// The key completed a compose sequence, type the character with [send_unicode].
+    Composed(char),
+    /// The key doesn't continue any compose sequence, and the sequence was dropped.
+    Cancelled,
+}
+
+/// Host input method used by [send_unicode] to type a character.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UnicodeInputMethod {
+    /// `Ctrl+Shift+U`, the hex code point, then `Space`, e.g. for IBus and GTK on Linux.
+    #[default]
+    Linux,
+    /// Hex UTF-16 code units typed while holding `Option`, with the "Unicode Hex Input" layout
+    /// selected on macOS.
+    MacOs,
+    /// `Alt` held while typing keypad `+` and the hex code point, with `EnableHexNumpad` set in
+    /// the Windows registry.
+    WindowsHexNumpad,
+}
+
+/// Compose key handler, resolving [ComposeSequence]s from the key presses after the compose key.
+///
+/// Pass every key press through [process](Self::process). Releases of consumed keys may still be
+/// passed to the keyboard, since the keys were never pressed in the report.
+pub struct Compose<'c> {
+    compose_key: u8,
+    sequences: &'c [ComposeSequence<'c>],
+    keys: [u8; COMPOSE_MAX_KEYS],
+    len: usize,
+    active: bool,
+}
+
+impl<'c> Compose<'c> {
+    /// Creates a new [Compose] handler, starting sequences on the compose key.
+    pub const fn new(compose_key: u8, sequences: &'c [ComposeSequence<'c>]) -> Self {
+        Self {
+            compose_key,
+            sequences,
+            keys: [0; COMPOSE_MAX_KEYS],
+            len: 0,
+            active: false,
+        }
+    }
+
+    /// Gets whether a compose sequence is in progress.
+    pub const fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Drops the compose sequence in progress.
+    pub fn cancel(&mut self) {
+        self.active = false;
+        self.len = 0;
+    }
+
+    /// Processes a key press.
+    pub fn process(&mut self, key: u8) -> ComposeResult {
+        if !self.active {
+            if key == self.compose_key {
+                self.active = true;
+                return ComposeResult::Pending;
+            }
+
+            return ComposeResult::Passthrough(key);
+        }
+
+        self.keys[self.len] = key;
+        self.len += 1;
+        let typed = &self.keys[..self.len];
+
+        if let Some(sequence) = self.sequences.iter().find(|s| s.keys == typed) {
+            self.cancel();
+            ComposeResult::Composed(sequence.output)
+        } else if self.len < COMPOSE_MAX_KEYS
+            && self
+                .sequences
+                .iter()
+                .any(|s| s.keys.len() > typed.len() && s.keys.starts_with(typed))
+        {
+            ComposeResult::Pending
+        } else {
+            self.cancel();
+            ComposeResult::Cancelled
+        }
+    }
+}
+
+// Gets the keycode typing the hex digit, from the keypad for the numpad input method.
+const fn hex_digit_key(digit: u8, keypad: bool) -> u8 {
+    match (digit, keypad) {
+        (0, false) => KeyboardUsage::Keyboard0CloseParens as u8,
+        (0, true) => KeyboardUsage::Keypad0Insert as u8,
+        (1..=9, false) => KeyboardUsage::Keyboard1Exclamation as u8 + digit - 1,
+        (1..=9, true) => KeyboardUsage::Keypad1End as u8 + digit - 1,
+        _ => KeyboardUsage::KeyboardAa as u8 + digit - 10,
+    }
+}
+
+// Types the value in hex, without leading zeros unless `width` digits are requested.
+fn tap_hex<K: KeyboardOps>(
+    keyboard: &mut K,
+    value: u32,
+    width: u32,
+    keypad: bool,
+) -> KeyboardResult<()> {
+    let mut digits = width.max(1);
+    while digits < 8 && value >> (digits * 4) != 0 {
+        digits += 1;
+    }
+
+    for shift in (0..digits).rev() {
+        let digit = ((value >> (shift * 4)) & 0xf) as u8;
+        keyboard.tap(hex_digit_key(digit, keypad))?;
+    }
+
+    Ok(())
+}
+
+/// Types the character on the host, using the host [UnicodeInputMethod].
+///
+/// Modifiers held on the keyboard are released first, and are not restored. The input method
+/// modifiers are also released if a report fails to send.
+pub fn send_unicode<K: KeyboardOps>(
+    keyboard: &mut K,
+    ch: char,
+    method: UnicodeInputMethod,
+) -> KeyboardResult<()> {
+    keyboard.set_modifiers(0);
+
+    let typed = type_unicode(keyboard, ch, method);
+
+    keyboard.set_modifiers(0);
+    typed?;
+    keyboard.send_report()
+}
+
+// Types the input method sequence for the character, leaving the last modifiers set.
+fn type_unicode<K: KeyboardOps>(
+    keyboard: &mut K,
+    ch: char,
+    method: UnicodeInputMethod,
+) -> KeyboardResult<()> {
+    let ctrl = key_to_modifier_bitfield(KeyboardUsage::KeyboardLeftControl as u8);
+    let shift = key_to_modifier_bitfield(KeyboardUsage::KeyboardLeftShift as u8);
+    let alt = key_to_modifier_bitfield(KeyboardUsage::KeyboardLeftAlt as u8);
+
+    match method {
+        UnicodeInputMethod::Linux => {
+            keyboard.set_modifiers(ctrl | shift);
+            keyboard.tap(KeyboardUsage::KeyboardUu as u8)?;
+            keyboard.set_modifiers(0);
+            keyboard.send_report()?;
+
+            tap_hex(keyboard, ch as u32, 0, false)?;
+            keyboard.tap(KeyboardUsage::KeyboardSpacebar as u8)
+        }
+        UnicodeInputMethod::MacOs => {
+            keyboard.set_modifiers(alt);
+            for unit in ch.encode_utf16(&mut [0; 2]) {
+                tap_hex(keyboard, *unit as u32, 4, false)?;
+            }
+
+            Ok(())
+        }
+        UnicodeInputMethod::WindowsHexNumpad => {
+            keyboard.set_modifiers(alt);
+            keyboard.tap(KeyboardUsage::KeypadPlus as u8)?;
+            tap_hex(keyboard, ch as u32, 0, true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use usb_device::bus::UsbBusAllocator;
+    use usb_device::UsbError;
+
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use crate::Keyboard;
+
+    const COMPOSE: u8 = KeyboardUsage::KeyboardRightAlt as u8;
+    const KEY_E: u8 = KeyboardUsage::KeyboardEe as u8;
+    const KEY_O: u8 = KeyboardUsage::KeyboardOo as u8;
+    const KEY_S: u8 = KeyboardUsage::KeyboardSs as u8;
+    const QUOTE: u8 = KeyboardUsage::KeyboardSingleDoubleQuote as u8;
+
+    static SEQUENCES: [ComposeSequence<'static>; 3] = [
+        ComposeSequence::new(&[QUOTE, KEY_E], 'é'),
+        ComposeSequence::new(&[QUOTE, KEY_O], 'ó'),
+        ComposeSequence::new(&[KEY_S, KEY_S], 'ß'),
+    ];
+
+    #[test]
+    fn test_compose_sequence() {
+        let mut compose = Compose::new(COMPOSE, &SEQUENCES);
+
+        assert_eq!(compose.process(KEY_E), ComposeResult::Passthrough(KEY_E));
+
+        assert_eq!(compose.process(COMPOSE), ComposeResult::Pending);
+        assert!(compose.is_active());
+        assert_eq!(compose.process(QUOTE), ComposeResult::Pending);
+        assert_eq!(compose.process(KEY_E), ComposeResult::Composed('é'));
+        assert!(!compose.is_active());
+
+        // keys not continuing a sequence cancel it
+        assert_eq!(compose.process(COMPOSE), ComposeResult::Pending);
+        assert_eq!(compose.process(QUOTE), ComposeResult::Pending);
+        assert_eq!(compose.process(KEY_S), ComposeResult::Cancelled);
+        assert_eq!(compose.process(KEY_S), ComposeResult::Passthrough(KEY_S));
+
+        assert_eq!(compose.process(COMPOSE), ComposeResult::Pending);
+        assert_eq!(compose.process(KEY_E), ComposeResult::Cancelled);
+    }
+
+    #[test]
+    fn test_send_unicode_linux() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        send_unicode(&mut keyboard, 'é', UnicodeInputMethod::Linux).unwrap();
+
+        let reports: std::vec::Vec<[u8; 3]> = usb_dev
+            .bus()
+            .in_reports()
+            .iter()
+            .map(|r| [r[0], r[2], r[3]])
+            .collect();
+        assert_eq!(
+            reports,
+            [
+                // Ctrl+Shift+U
+                [0x03, 0x00, 0x00],
+                [0x03, 0x18, 0x00],
+                [0x03, 0x00, 0x00],
+                [0x00, 0x00, 0x00],
+                // e9
+                [0x00, 0x08, 0x00],
+                [0x00, 0x00, 0x00],
+                [0x00, 0x26, 0x00],
+                [0x00, 0x00, 0x00],
+                // Space
+                [0x00, 0x2c, 0x00],
+                [0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_send_unicode_macos() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        send_unicode(&mut keyboard, 'é', UnicodeInputMethod::MacOs).unwrap();
+
+        // Option held while typing 00e9
+        let keys: std::vec::Vec<u8> = usb_dev
+            .bus()
+            .in_reports()
+            .iter()
+            .filter(|r| r[0] == 0x04 && r[2] != 0)
+            .map(|r| r[2])
+            .collect();
+        assert_eq!(keys, [0x27, 0x27, 0x08, 0x26]);
+    }
+
+    #[test]
+    fn test_send_unicode_send_error() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        for method in [UnicodeInputMethod::Linux, UnicodeInputMethod::MacOs] {
+            usb_dev.bus().fail_next_write(UsbError::InvalidEndpoint);
+            assert!(send_unicode(&mut keyboard, 'é', method).is_err());
+
+            // the failed send doesn't leave Ctrl+Shift or Option held
+            assert_eq!(KeyboardOps::modifiers(&keyboard), 0);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use usb_device::bus::UsbBus;
+use usbd_hid::hid_class::{HidClassSettings, HidProtocol, HidSubClass, ProtocolModeConfig};
+
+use crate::error::KeyboardResult;
+use crate::hid_settings::HIDReportId;
+use crate::media::MediaKeyboard;
+use crate::system_control::SystemControlKeyboard;
+
+use super::{keyboard_locale, Keyboard, KeyboardOps, KeyboardUsbBusAllocator};
+
+pub const fn composite_hid_class_settings() -> HidClassSettings {
+    HidClassSettings {
+        subclass: HidSubClass::NoSubClass,
+        protocol: HidProtocol::Keyboard,
+        config: ProtocolModeConfig::ForceReport,
+        locale: keyboard_locale(),
+    }
+}
+
+/// Report descriptor for a keyboard, consumer control, and system control sharing one interface.
+///
+/// Each collection is tagged with its [HIDReportId], so the host can tell the reports apart by
+/// their leading report ID byte. The report layouts match [KeyboardReport],
+/// [MediaKeyboardReport], and [SystemControlReport].
+///
+/// [KeyboardReport]: usbd_hid::descriptor::KeyboardReport
+/// [MediaKeyboardReport]: usbd_hid::descriptor::MediaKeyboardReport
+/// [SystemControlReport]: usbd_hid::descriptor::SystemControlReport
+#[rustfmt::skip]
+pub const COMPOSITE_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x06, // Usage (Keyboard)
+    0xa1, 0x01, // Collection (Application)
+    0x85, HIDReportId::Keyboard as u8, // Report ID
+    0x05, 0x07, //   Usage Page (Keyboard)
+    0x19, 0xe0, //   Usage Minimum (Left Control)
+    0x29, 0xe7, //   Usage Maximum (Right GUI)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x08, //   Report Count (8)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x03, //   Input (Constant, Variable, Absolute)
+    0x05, 0x08, //   Usage Page (LEDs)
+    0x19, 0x01, //   Usage Minimum (Num Lock)
+    0x29, 0x05, //   Usage Maximum (Kana)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x05, //   Report Count (5)
+    0x91, 0x02, //   Output (Data, Variable, Absolute)
+    0x75, 0x03, //   Report Size (3)
+    0x95, 0x01, //   Report Count (1)
+    0x91, 0x03, //   Output (Constant, Variable, Absolute)
+    0x05, 0x07, //   Usage Page (Keyboard)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x29, 0xdd, //   Usage Maximum (Keypad Hexadecimal)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xdd, 0x00, // Logical Maximum (221)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x06, //   Report Count (6)
+    0x81, 0x00, //   Input (Data, Array, Absolute)
+    0xc0, // End Collection
+    0x05, 0x0c, // Usage Page (Consumer)
+    0x09, 0x01, // Usage (Consumer Control)
+    0xa1, 0x01, // Collection (Application)
+    0x85, HIDReportId::ConsumerControl as u8, // Report ID
+    0x19, 0x00, //   Usage Minimum (0)
+    0x2a, 0x14, 0x05, // Usage Maximum (0x514)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0x14, 0x05, // Logical Maximum (0x514)
+    0x75, 0x10, //   Report Size (16)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x00, //   Input (Data, Array, Absolute)
+    0xc0, // End Collection
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x80, // Usage (System Control)
+    0xa1, 0x01, // Collection (Application)
+    0x85, HIDReportId::SystemControl as u8, // Report ID
+    0x19, 0x81, //   Usage Minimum (System Power Down)
+    0x29, 0xb7, //   Usage Maximum (System Display LCD Autoscale)
+    0x15, 0x01, //   Logical Minimum (1)
+    0x26, 0xb7, 0x00, // Logical Maximum (183)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x00, //   Input (Data, Array, Absolute)
+    0xc0, // End Collection
+];
+
+impl<'k, B: UsbBus> Keyboard<'k, B> {
+    /// Creates a new composite [Keyboard] device, sending keyboard, media, and system control
+    /// reports over one interface.
+    ///
+    /// See [KeyboardBuilder::composite](super::builder::KeyboardBuilder::composite).
+    pub fn new_composite(bus: &'k KeyboardUsbBusAllocator<B>) -> Self {
+        Self::builder().composite().build(bus)
+    }
+
+    /// Sends the changed keyboard, media, and system control reports, in that order, e.g. for a
+    /// media key pressed with a normal key in the same scan.
+    ///
+    /// Unchanged reports are skipped. Every report is sent even if an earlier send fails, and the
+    /// first error is returned.
+    pub fn send_all(&mut self) -> KeyboardResult<()> {
+        let keyboard = KeyboardOps::send_report(self);
+        let media = MediaKeyboard::send_report(self);
+        let system_control = SystemControlKeyboard::send_report(self);
+
+        keyboard.and(media).and(system_control)
+    }
+
+    /// Gets whether reports are prefixed with their [HIDReportId], as required by the
+    /// [COMPOSITE_DESCRIPTOR].
+    pub fn uses_report_ids(&self) -> bool {
+        self.report_ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usb_device::bus::UsbBusAllocator;
+    use usbd_hid::descriptor::{KeyboardUsage, MediaKey, SystemControlKey};
+
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+
+    #[test]
+    fn test_composite_report_ids() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_composite(&alloc);
+        let boot_keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        assert!(keyboard.uses_report_ids());
+        assert!(!boot_keyboard.uses_report_ids());
+
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        MediaKeyboard::press(&mut keyboard, MediaKey::VolumeIncrement as u8);
+        MediaKeyboard::send_report(&mut keyboard).unwrap();
+
+        SystemControlKeyboard::press(&mut keyboard, SystemControlKey::Sleep as u8);
+        SystemControlKeyboard::send_report(&mut keyboard).unwrap();
+
+        let reports = usb_dev.bus().in_reports();
+        assert_eq!(
+            reports[0],
+            [0x02, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(reports[1], [0x04, 0xe9, 0x00]);
+        assert_eq!(reports[2], [0x05, 0x82]);
+
+        // LED output reports are prefixed with the keyboard report ID
+        usb_dev
+            .bus()
+            .queue_out(1, &[HIDReportId::Keyboard as u8, 0x02]);
+        assert_eq!(keyboard.read_leds(), Some(0x02));
+    }
+
+    #[test]
+    fn test_composite_send_all() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_composite(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        // e.g. Fn+Play with a normal key held
+        KeyboardOps::press(&mut keyboard, KeyboardUsage::KeyboardAa as u8);
+        MediaKeyboard::press(&mut keyboard, MediaKey::PlayPause as u8);
+        keyboard.send_all().unwrap();
+        // unchanged reports are not sent again
+        keyboard.send_all().unwrap();
+
+        MediaKeyboard::release(&mut keyboard, MediaKey::PlayPause as u8);
+        keyboard.send_all().unwrap();
+
+        let reports = usb_dev.bus().in_reports();
+        assert_eq!(
+            reports,
+            [
+                &[0x02, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00][..],
+                &[0x04, 0xcd, 0x00],
+                &[0x04, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_composite_end_keeps_keyboard_keys() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_composite(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+        KeyboardOps::press(&mut keyboard, key_a);
+        MediaKeyboard::press(&mut keyboard, MediaKey::PlayPause as u8);
+        SystemControlKeyboard::press(&mut keyboard, SystemControlKey::Sleep as u8);
+        keyboard.send_all().unwrap();
+
+        MediaKeyboard::end(&mut keyboard).unwrap();
+        SystemControlKeyboard::end(&mut keyboard).unwrap();
+
+        // the held keyboard key is still pressed, and nothing is left to send
+        assert!(keyboard.is_key_pressed(key_a));
+        assert!(!keyboard.report_changed());
+        keyboard.send_all().unwrap();
+
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                &[0x02, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00][..],
+                &[0x04, 0xcd, 0x00],
+                &[0x05, 0x82],
+                &[0x04, 0x00, 0x00],
+                &[0x05, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_composite_descriptor() {
+        let ids: [u8; 3] = [
+            HIDReportId::Keyboard.into(),
+            HIDReportId::ConsumerControl.into(),
+            HIDReportId::SystemControl.into(),
+        ];
+        let mut found = ids.iter().map(|&id| {
+            COMPOSITE_DESCRIPTOR
+                .windows(2)
+                .filter(|w| w == &[0x85, id])
+                .count()
+        });
+
+        assert!(found.all(|count| count == 1));
+        assert_eq!(COMPOSITE_DESCRIPTOR.last(), Some(&0xc0));
+    }
+}
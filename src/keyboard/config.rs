@@ -0,0 +1,308 @@
+use usbd_hid::hid_class::HidCountryCode;
+
+use super::builder::KeyboardBuilder;
+use super::{keyboard_locale, POLL_MS};
+
+/// Version of the persisted [Config] layout, bumped on layout changes.
+pub const CONFIG_VERSION: u8 = 1;
+/// Size of the persisted [Config] layout in bytes.
+pub const CONFIG_LEN: usize = 11;
+
+// Marks the start of a persisted config, and rules out erased (all 0x00 or 0xff) storage.
+const CONFIG_MAGIC: [u8; 2] = *b"KB";
+
+const MAGIC: usize = 0;
+const VERSION: usize = 2;
+const LOCALE: usize = 3;
+const POLL: usize = 4;
+const FLAGS: usize = 5;
+const VID: usize = 6;
+const PID: usize = 8;
+const CHECKSUM: usize = 10;
+
+const FLAG_VID_PID: u8 = 1 << 0;
+
+// Country codes indexed by their HID value, see `country_code`.
+const COUNTRY_CODES: [HidCountryCode; 36] = [
+    HidCountryCode::NotSupported,
+    HidCountryCode::Arabic,
+    HidCountryCode::Belgian,
+    HidCountryCode::CanadianBilingual,
+    HidCountryCode::CanadianFrench,
+    HidCountryCode::CzechRepublic,
+    HidCountryCode::Danish,
+    HidCountryCode::Finnish,
+    HidCountryCode::French,
+    HidCountryCode::German,
+    HidCountryCode::Greek,
+    HidCountryCode::Hebrew,
+    HidCountryCode::Hungary,
+    HidCountryCode::InternationalISO,
+    HidCountryCode::Italian,
+    HidCountryCode::JapanKatakana,
+    HidCountryCode::Korean,
+    HidCountryCode::LatinAmerica,
+    HidCountryCode::NetherlandsDutch,
+    HidCountryCode::Norwegian,
+    HidCountryCode::PersianFarsi,
+    HidCountryCode::Poland,
+    HidCountryCode::Portuguese,
+    HidCountryCode::Russia,
+    HidCountryCode::Slovakia,
+    HidCountryCode::Spanish,
+    HidCountryCode::Swedish,
+    HidCountryCode::SwissFrench,
+    HidCountryCode::SwissGerman,
+    HidCountryCode::Switzerland,
+    HidCountryCode::Taiwan,
+    HidCountryCode::TurkishQ,
+    HidCountryCode::UK,
+    HidCountryCode::US,
+    HidCountryCode::Yugoslavia,
+    HidCountryCode::TurkishF,
+];
+
+/// Converts a HID country code value into its [HidCountryCode].
+///
+/// Returns `None` for values without a country code.
+pub const fn country_code(code: u8) -> Option<HidCountryCode> {
+    if (code as usize) < COUNTRY_CODES.len() {
+        Some(COUNTRY_CODES[code as usize])
+    } else {
+        None
+    }
+}
+
+/// Errors returned when loading or storing a [Config].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigError {
+    /// The storage could not be read or written, e.g. it is smaller than [CONFIG_LEN].
+    Storage,
+    /// No config is stored, or the stored data is not a config.
+    NotFound,
+    /// The config was stored with an unsupported layout version.
+    UnsupportedVersion(u8),
+    /// The stored checksum doesn't match the config data.
+    Checksum,
+    /// A config value is out of range, e.g. a zero poll interval.
+    InvalidValue,
+}
+
+/// Byte-addressed storage persisting the [Config], e.g. the ATmega32u4 EEPROM.
+///
+/// Byte arrays implement [Eeprom] as an in-memory backend.
+pub trait Eeprom {
+    /// Reads the bytes at the offset into the buffer.
+    fn read(&self, offset: usize, buf: &mut [u8]) -> Result<(), ConfigError>;
+
+    /// Writes the bytes to the offset.
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), ConfigError>;
+}
+
+impl<const N: usize> Eeprom for [u8; N] {
+    fn read(&self, offset: usize, buf: &mut [u8]) -> Result<(), ConfigError> {
+        let data = self
+            .get(offset..offset + buf.len())
+            .ok_or(ConfigError::Storage)?;
+        buf.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), ConfigError> {
+        self.get_mut(offset..offset + data.len())
+            .ok_or(ConfigError::Storage)?
+            .copy_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Runtime keyboard settings persisted across power cycles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    /// Keyboard locale reported to the host.
+    pub locale: HidCountryCode,
+    /// Interval for the host to poll the keyboard, in milliseconds.
+    pub poll_ms: u8,
+    /// USB vendor and product ID overrides.
+    pub vid_pid: Option<(u16, u16)>,
+}
+
+impl Config {
+    /// Creates a new [Config] with the build defaults.
+    pub const fn new() -> Self {
+        Self {
+            locale: keyboard_locale(),
+            poll_ms: POLL_MS,
+            vid_pid: None,
+        }
+    }
+
+    /// Applies the settings to the [KeyboardBuilder].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a VID/PID override has a zero ID, see [KeyboardBuilder::vid_pid]. A [Config]
+    /// returned by [load] never has one.
+    pub const fn apply(&self, builder: KeyboardBuilder) -> KeyboardBuilder {
+        let builder = builder.locale(self.locale).poll_ms(self.poll_ms);
+
+        match self.vid_pid {
+            Some((vid, pid)) => builder.vid_pid(vid, pid),
+            None => builder,
+        }
+    }
+
+    // Gets whether the settings can be stored, and applied to a keyboard.
+    fn is_valid(&self) -> bool {
+        self.poll_ms != 0 && !matches!(self.vid_pid, Some((0, _)) | Some((_, 0)))
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// CRC-8 with the 0x07 polynomial, catching the swapped and shifted bytes a plain sum misses.
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// Loads the [Config] stored at the start of the [Eeprom].
+///
+/// Data from another layout version, or with a bad checksum, is rejected.
+pub fn load<E: Eeprom>(eeprom: &E) -> Result<Config, ConfigError> {
+    let mut data = [0u8; CONFIG_LEN];
+    eeprom.read(0, &mut data)?;
+
+    if data[MAGIC..VERSION] != CONFIG_MAGIC {
+        return Err(ConfigError::NotFound);
+    }
+    if data[VERSION] != CONFIG_VERSION {
+        return Err(ConfigError::UnsupportedVersion(data[VERSION]));
+    }
+    if checksum(&data[..CHECKSUM]) != data[CHECKSUM] {
+        return Err(ConfigError::Checksum);
+    }
+
+    let vid_pid = (data[FLAGS] & FLAG_VID_PID != 0).then(|| {
+        (
+            u16::from_le_bytes([data[VID], data[VID + 1]]),
+            u16::from_le_bytes([data[PID], data[PID + 1]]),
+        )
+    });
+    let config = Config {
+        locale: country_code(data[LOCALE]).ok_or(ConfigError::InvalidValue)?,
+        poll_ms: data[POLL],
+        vid_pid,
+    };
+
+    if config.is_valid() {
+        Ok(config)
+    } else {
+        Err(ConfigError::InvalidValue)
+    }
+}
+
+/// Stores the [Config] at the start of the [Eeprom].
+pub fn store<E: Eeprom>(eeprom: &mut E, config: &Config) -> Result<(), ConfigError> {
+    if !config.is_valid() {
+        return Err(ConfigError::InvalidValue);
+    }
+
+    let mut data = [0u8; CONFIG_LEN];
+    data[MAGIC..VERSION].copy_from_slice(CONFIG_MAGIC.as_ref());
+    data[VERSION] = CONFIG_VERSION;
+    data[LOCALE] = config.locale as u8;
+    data[POLL] = config.poll_ms;
+
+    if let Some((vid, pid)) = config.vid_pid {
+        data[FLAGS] = FLAG_VID_PID;
+        data[VID..PID].copy_from_slice(vid.to_le_bytes().as_ref());
+        data[PID..CHECKSUM].copy_from_slice(pid.to_le_bytes().as_ref());
+    }
+    data[CHECKSUM] = checksum(&data[..CHECKSUM]);
+
+    eeprom.write(0, data.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: Config = Config {
+        locale: HidCountryCode::German,
+        poll_ms: 1,
+        vid_pid: Some((0x1209, 0x2301)),
+    };
+
+    #[test]
+    fn test_config_round_trip() {
+        let mut eeprom = [0xffu8; 16];
+
+        assert_eq!(load(&eeprom), Err(ConfigError::NotFound));
+
+        store(&mut eeprom, &CONFIG).unwrap();
+        assert_eq!(load(&eeprom), Ok(CONFIG));
+
+        let defaults = Config::default();
+        store(&mut eeprom, &defaults).unwrap();
+        assert_eq!(load(&eeprom), Ok(defaults));
+
+        assert_eq!(
+            store(&mut [0u8; CONFIG_LEN - 1], &CONFIG),
+            Err(ConfigError::Storage)
+        );
+        assert_eq!(
+            store(
+                &mut eeprom,
+                &Config {
+                    poll_ms: 0,
+                    ..CONFIG
+                }
+            ),
+            Err(ConfigError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn test_config_rejects_corrupt_data() {
+        let mut eeprom = [0u8; CONFIG_LEN];
+        store(&mut eeprom, &CONFIG).unwrap();
+
+        let mut corrupt = eeprom;
+        corrupt[POLL] ^= 0x02;
+        assert_eq!(load(&corrupt), Err(ConfigError::Checksum));
+
+        let mut corrupt = eeprom;
+        corrupt[CHECKSUM] ^= 0x01;
+        assert_eq!(load(&corrupt), Err(ConfigError::Checksum));
+
+        let mut older = eeprom;
+        older[VERSION] = 0;
+        assert_eq!(load(&older), Err(ConfigError::UnsupportedVersion(0)));
+    }
+
+    #[test]
+    fn test_country_code() {
+        assert_eq!(country_code(0), Some(HidCountryCode::NotSupported));
+        assert_eq!(
+            country_code(HidCountryCode::US as u8),
+            Some(HidCountryCode::US)
+        );
+        assert_eq!(
+            country_code(HidCountryCode::TurkishF as u8),
+            Some(HidCountryCode::TurkishF)
+        );
+        assert_eq!(country_code(36), None);
+    }
+}
@@ -0,0 +1,291 @@
+use usb_device::bus::UsbBus;
+use usbd_hid::descriptor::generator_prelude::*;
+use usbd_hid::hid_class::{
+    HIDClass, HidClassSettings, HidCountryCode, HidProtocol, HidSubClass, ProtocolModeConfig,
+};
+
+use crate::error::{KeyboardError, KeyboardResult};
+use crate::hid_settings::{HIDReport, HIDReportId};
+use crate::HIDReportObserver;
+
+use super::{KeyboardUsbBus, KeyboardUsbBusAllocator, POLL_MS};
+
+/// Number of buttons supported by the [GamepadReport].
+pub const GAMEPAD_BUTTONS: u8 = 32;
+
+pub const fn gamepad_hid_class_settings() -> HidClassSettings {
+    HidClassSettings {
+        subclass: HidSubClass::NoSubClass,
+        protocol: HidProtocol::Generic,
+        config: ProtocolModeConfig::DefaultBehavior,
+        locale: HidCountryCode::NotSupported,
+    }
+}
+
+/// GamepadReport describes a report and its companion descriptor that can be used
+/// to send gamepad button presses and axis positions to a host.
+#[gen_hid_descriptor(
+    (collection = APPLICATION, usage_page = GENERIC_DESKTOP, usage = GAMEPAD) = {
+        (usage_page = BUTTON, usage_min = BUTTON_1, usage_max = 0x20) = {
+            #[packed_bits 32] #[item_settings data,variable,absolute] buttons=input;
+        };
+        (usage_page = GENERIC_DESKTOP,) = {
+            (usage = X,) = {
+                #[item_settings data,variable,absolute] x=input;
+            };
+            (usage = Y,) = {
+                #[item_settings data,variable,absolute] y=input;
+            };
+            (usage = Z,) = {
+                #[item_settings data,variable,absolute] z=input;
+            };
+            (usage = 0x35,) = {
+                #[item_settings data,variable,absolute] rz=input;
+            };
+        };
+    }
+)]
+#[derive(PartialEq)]
+#[allow(dead_code)]
+pub struct GamepadReport {
+    pub buttons: [u8; 4],
+    pub x: i8,
+    pub y: i8,
+    pub z: i8,
+    pub rz: i8,
+}
+
+impl GamepadReport {
+    /// Creates a new [GamepadReport] with no buttons pressed, and all axes centered.
+    pub const fn default() -> Self {
+        Self {
+            buttons: [0; 4],
+            x: 0,
+            y: 0,
+            z: 0,
+            rz: 0,
+        }
+    }
+}
+
+impl Default for GamepadReport {
+    fn default() -> Self {
+        // calls the inherent `const fn`
+        Self::default()
+    }
+}
+
+/// Gamepad axes reported in the [GamepadReport].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GamepadAxis {
+    X,
+    Y,
+    Z,
+    Rz,
+}
+
+pub struct Gamepad<'g, B: UsbBus = KeyboardUsbBus> {
+    hid_class: HIDClass<'g, B>,
+    report: GamepadReport,
+    last_report: GamepadReport,
+    observer: HIDReportObserver,
+}
+
+impl<'g, B: UsbBus> Gamepad<'g, B> {
+    /// Creates a new [Gamepad] device.
+    pub fn new(bus: &'g KeyboardUsbBusAllocator<B>) -> Self {
+        Self::new_with_observer(bus, HIDReportObserver::default())
+    }
+
+    /// Creates a new [Gamepad] device.
+    ///
+    /// Allows setting a custom [HIDReportObserver] implementation for firing a callback function
+    /// on HID report events.
+    pub fn new_with_observer(
+        bus: &'g KeyboardUsbBusAllocator<B>,
+        observer: HIDReportObserver,
+    ) -> Self {
+        let hid_class = HIDClass::new_with_settings(
+            bus,
+            GamepadReport::desc(),
+            POLL_MS,
+            gamepad_hid_class_settings(),
+        );
+
+        Self {
+            hid_class,
+            report: GamepadReport::default(),
+            last_report: GamepadReport::default(),
+            observer,
+        }
+    }
+
+    /// Gets a reference to the current gamepad report.
+    pub fn report(&self) -> &GamepadReport {
+        &self.report
+    }
+
+    /// Gets a reference to the last gamepad report.
+    pub fn last_report(&self) -> &GamepadReport {
+        &self.last_report
+    }
+
+    /// Gets a reference to the [HIDClass] for the USB bus.
+    pub fn hid_class(&self) -> &HIDClass<'g, B> {
+        &self.hid_class
+    }
+
+    /// Sets the position of a gamepad axis.
+    pub fn set_axis(&mut self, axis: GamepadAxis, value: i8) {
+        match axis {
+            GamepadAxis::X => self.report.x = value,
+            GamepadAxis::Y => self.report.y = value,
+            GamepadAxis::Z => self.report.z = value,
+            GamepadAxis::Rz => self.report.rz = value,
+        }
+    }
+
+    /// Press a button, and add it to the current report.
+    ///
+    /// Buttons are numbered from `1` to [GAMEPAD_BUTTONS], matching the HID button usages.
+    ///
+    /// Returns 1 if the button is in range.
+    /// Returns 0 otherwise.
+    pub fn press_button(&mut self, button: u8) -> usize {
+        if is_gamepad_button(button) {
+            let (index, bit) = button_to_bitfield(button);
+            self.report.buttons[index] |= bit;
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Release a pressed button, and remove it from the current report.
+    ///
+    /// Returns 1 if the button is in range.
+    /// Returns 0 otherwise.
+    pub fn release_button(&mut self, button: u8) -> usize {
+        if is_gamepad_button(button) {
+            let (index, bit) = button_to_bitfield(button);
+            self.report.buttons[index] &= !bit;
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Gets whether the provided button is pressed in the current gamepad report.
+    pub fn is_button_pressed(&self, button: u8) -> bool {
+        if is_gamepad_button(button) {
+            let (index, bit) = button_to_bitfield(button);
+            self.report.buttons[index] & bit != 0
+        } else {
+            false
+        }
+    }
+
+    /// Release all buttons and center all axes in the current gamepad report.
+    pub fn release_all(&mut self) {
+        self.report = GamepadReport::default();
+    }
+
+    /// Sends the current gamepad report to the host, if it changed since the last report.
+    pub fn send_report(&mut self) -> KeyboardResult<()> {
+        if self.report != self.last_report {
+            let report = self.report;
+            // replace the Ok(usize) with Ok(())
+            let ret = self.hid_class.push_input(&report).map(|_| ());
+            self.observer
+                .observe_report(HIDReportId::Gamepad, HIDReport::Gamepad(report), &ret);
+            self.last_report = report;
+
+            ret.map_err(KeyboardError::from)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+const fn is_gamepad_button(button: u8) -> bool {
+    button >= 1 && button <= GAMEPAD_BUTTONS
+}
+
+const fn button_to_bitfield(button: u8) -> (usize, u8) {
+    let bit = button - 1;
+    ((bit / 8) as usize, 1 << (bit % 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use usb_device::bus::UsbBusAllocator;
+
+    #[test]
+    fn test_gamepad_descriptor() {
+        let expected = &[
+            0x05, 0x01, // Usage Page (Generic Desktop)
+            0x09, 0x05, // Usage (Gamepad)
+            0xa1, 0x01, // Collection (Application)
+            0x05, 0x09, //   Usage Page (Button)
+            0x19, 0x01, //   Usage Minimum (1)
+            0x29, 0x20, //   Usage Maximum (32)
+            0x15, 0x00, //   Logical Minimum (0)
+            0x25, 0x01, //   Logical Maximum (1)
+            0x75, 0x01, //   Report Size (1)
+            0x95, 0x20, //   Report Count (32)
+            0x81, 0x02, //   Input (Data, Variable, Absolute)
+            0x05, 0x01, //   Usage Page (Generic Desktop)
+            0x09, 0x30, //   Usage (X)
+            0x17, 0x81, 0xff, 0xff, 0xff, //   Logical Minimum (-127)
+            0x25, 0x7f, //   Logical Maximum (127)
+            0x75, 0x08, //   Report Size (8)
+            0x95, 0x01, //   Report Count (1)
+            0x81, 0x02, //   Input (Data, Variable, Absolute)
+            0x09, 0x31, //   Usage (Y)
+            0x81, 0x02, //   Input (Data, Variable, Absolute)
+            0x09, 0x32, //   Usage (Z)
+            0x81, 0x02, //   Input (Data, Variable, Absolute)
+            0x09, 0x35, //   Usage (Rz)
+            0x81, 0x02, //   Input (Data, Variable, Absolute)
+            0xc0, // End Collection
+        ];
+        assert_eq!(GamepadReport::desc(), expected);
+    }
+
+    #[test]
+    fn test_gamepad_encoding() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut gamepad = Gamepad::new(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        assert_eq!(gamepad.press_button(1), 1);
+        assert_eq!(gamepad.press_button(10), 1);
+        assert_eq!(gamepad.press_button(32), 1);
+        assert_eq!(gamepad.press_button(0), 0);
+        assert_eq!(gamepad.press_button(33), 0);
+
+        gamepad.set_axis(GamepadAxis::X, -127);
+        gamepad.set_axis(GamepadAxis::Y, 127);
+        gamepad.set_axis(GamepadAxis::Z, 1);
+        gamepad.set_axis(GamepadAxis::Rz, -1);
+
+        gamepad.send_report().unwrap();
+        // unchanged reports are not pushed
+        gamepad.send_report().unwrap();
+
+        gamepad.release_button(10);
+        gamepad.send_report().unwrap();
+
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x01, 0x02, 0x00, 0x80, 0x81, 0x7f, 0x01, 0xff],
+                [0x01, 0x00, 0x00, 0x80, 0x81, 0x7f, 0x01, 0xff],
+            ]
+        );
+        assert!(gamepad.is_button_pressed(32));
+        assert!(!gamepad.is_button_pressed(10));
+    }
+}
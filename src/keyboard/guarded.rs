@@ -0,0 +1,102 @@
+use core::cell::{RefCell, RefMut};
+
+use critical_section::{CriticalSection, Mutex};
+
+use super::*;
+
+/// [Keyboard] shared between the main loop and interrupt handlers.
+///
+/// On AVR, the keyboard is commonly polled from the USB interrupt while the main loop presses
+/// and releases keys. A `&mut Keyboard` can't be reached from both, so the keyboard is kept in a
+/// `static` behind a [critical_section::Mutex], and every access runs inside a critical section.
+/// The interrupt then never observes a half-updated report, e.g. with a modifier set but its
+/// keycode not yet added.
+///
+/// The keyboard can't be built in a `const` context, so the wrapper starts empty, and is filled
+/// with [init](Self::init) once the USB bus is allocated.
+///
+/// The firmware must provide a `critical-section` implementation, e.g. with the
+/// `critical-section-impl` feature of `avr-device`. Interrupts stay disabled while the closure
+/// runs, so keep it short.
+pub struct SharedKeyboard<'k, B: UsbBus = KeyboardUsbBus> {
+    keyboard: Mutex<RefCell<Option<Keyboard<'k, B>>>>,
+}
+
+impl<'k, B: UsbBus> SharedKeyboard<'k, B> {
+    /// Creates an empty [SharedKeyboard], e.g. for a `static`.
+    pub const fn new() -> Self {
+        Self {
+            keyboard: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    /// Stores the keyboard, replacing the previous one.
+    pub fn init(&self, keyboard: Keyboard<'k, B>) {
+        critical_section::with(|cs| {
+            self.keyboard.borrow_ref_mut(cs).replace(keyboard);
+        });
+    }
+
+    /// Calls the closure with the keyboard inside a critical section.
+    ///
+    /// Returns `None` without calling the closure if the keyboard is not initialized.
+    pub fn with<R>(&self, f: impl FnOnce(&mut Keyboard<'k, B>) -> R) -> Option<R> {
+        critical_section::with(|cs| self.keyboard.borrow_ref_mut(cs).as_mut().map(f))
+    }
+
+    /// Borrows the keyboard for the duration of an already entered critical section.
+    ///
+    /// Useful in interrupt handlers doing more work in the same critical section.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keyboard is already borrowed.
+    pub fn borrow_ref_mut<'cs>(
+        &'cs self,
+        cs: CriticalSection<'cs>,
+    ) -> RefMut<'cs, Option<Keyboard<'k, B>>> {
+        self.keyboard.borrow_ref_mut(cs)
+    }
+}
+
+impl<B: UsbBus> Default for SharedKeyboard<'_, B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+
+    #[test]
+    fn test_shared_keyboard() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let shared = SharedKeyboard::new();
+        assert_eq!(shared.with(|keyboard| keyboard.count_pressed_keys()), None);
+
+        shared.init(Keyboard::new_boot(&alloc));
+        let usb_dev = mock_usb_device(&alloc);
+
+        let key_4 = KeyboardUsage::Keyboard4Dollar as u8;
+        shared.with(|keyboard| {
+            KeyboardOps::press(keyboard, KeyboardUsage::KeyboardLeftShift as u8);
+            KeyboardOps::press(keyboard, key_4);
+        });
+
+        critical_section::with(|cs| {
+            let mut keyboard = shared.borrow_ref_mut(cs);
+            KeyboardOps::send_report(keyboard.as_mut().unwrap()).unwrap();
+        });
+
+        assert_eq!(shared.with(|keyboard| keyboard.count_pressed_keys()), Some(1));
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use usb_device::bus::UsbBus;
+
+use crate::error::KeyboardResult;
+
+use super::{Keyboard, KeyboardOps};
+
+/// Key held down on a [Keyboard] until the guard is dropped, see [Keyboard::hold].
+///
+/// Dropping the guard releases the key and sends the report, ignoring send errors. Call
+/// [release](Self::release) to handle them instead. A key that was already pressed when the
+/// guard was created is left pressed.
+pub struct HeldKey<'h, 'k, B: UsbBus> {
+    keyboard: &'h mut Keyboard<'k, B>,
+    key: u8,
+    // whether `hold` pressed the key, and the guard releases it
+    pressed: bool,
+    released: bool,
+}
+
+impl<'k, B: UsbBus> HeldKey<'_, 'k, B> {
+    /// Gets the held key.
+    pub fn key(&self) -> u8 {
+        self.key
+    }
+
+    /// Gets the keyboard, e.g. to tap other keys while the key is held.
+    pub fn keyboard(&mut self) -> &mut Keyboard<'k, B> {
+        self.keyboard
+    }
+
+    /// Releases the held key, and sends the keyboard report.
+    ///
+    /// The key is not released again on drop, even if the send fails.
+    pub fn release(mut self) -> KeyboardResult<()> {
+        self.release_key()
+    }
+
+    fn release_key(&mut self) -> KeyboardResult<()> {
+        self.released = true;
+        if !self.pressed {
+            return Ok(());
+        }
+
+        KeyboardOps::release(self.keyboard, self.key);
+        KeyboardOps::send_report(self.keyboard)
+    }
+}
+
+impl<B: UsbBus> Drop for HeldKey<'_, '_, B> {
+    fn drop(&mut self) {
+        if !self.released {
+            self.release_key().ok();
+        }
+    }
+}
+
+impl<'k, B: UsbBus> Keyboard<'k, B> {
+    /// Press a key, and send the keyboard report, holding the key until the returned [HeldKey]
+    /// is dropped.
+    ///
+    /// If the key is already pressed, e.g. a physically held shift, nothing is sent, and the
+    /// guard leaves the key pressed when dropped.
+    ///
+    /// Returns the [try_press](Self::try_press) or send error without holding the key.
+    pub fn hold(&mut self, key: u8) -> KeyboardResult<HeldKey<'_, 'k, B>> {
+        let pressed = !self.is_key_pressed(key) && !KeyboardOps::is_modifier_active(self, key);
+
+        if pressed {
+            self.try_press(key)?;
+
+            if let Err(err) = KeyboardOps::send_report(self) {
+                KeyboardOps::release(self, key);
+                return Err(err);
+            }
+        }
+
+        Ok(HeldKey {
+            keyboard: self,
+            key,
+            pressed,
+            released: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usb_device::bus::UsbBusAllocator;
+    use usb_device::UsbError;
+    use usbd_hid::descriptor::KeyboardUsage;
+
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+
+    #[test]
+    fn test_hold_releases_on_drop() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+
+        {
+            let mut held = keyboard.hold(shift).unwrap();
+            assert_eq!(held.key(), shift);
+
+            held.keyboard().try_press(key_a).unwrap();
+            KeyboardOps::send_report(held.keyboard()).unwrap();
+            KeyboardOps::release(held.keyboard(), key_a);
+        }
+
+        assert!(keyboard.report_is_empty());
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+
+        // an explicit release sends the release report only once
+        keyboard.hold(key_a).unwrap().release().unwrap();
+        assert_eq!(usb_dev.bus().in_reports().len(), 6);
+    }
+
+    #[test]
+    fn test_hold_release_error_not_retried() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let held = keyboard.hold(KeyboardUsage::KeyboardAa as u8).unwrap();
+        usb_dev.bus().fail_next_write(UsbError::InvalidEndpoint);
+        assert!(held.release().is_err());
+
+        // dropping the guard doesn't send the release again
+        assert_eq!(usb_dev.bus().in_reports().len(), 1);
+    }
+
+    #[test]
+    fn test_hold_already_pressed() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+        KeyboardOps::press(&mut keyboard, shift);
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+
+        drop(keyboard.hold(shift).unwrap());
+        keyboard.hold(shift).unwrap().release().unwrap();
+
+        // the physically held shift stays pressed
+        assert!(KeyboardOps::is_modifier_active(&keyboard, shift));
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [[0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use usb_device::bus::{StringIndex, UsbBus};
+use usb_device::class::{ControlIn, ControlOut, UsbClass};
+use usb_device::control::{Recipient, RequestType};
+use usb_device::descriptor::{BosWriter, DescriptorWriter};
+use usb_device::endpoint::EndpointAddress;
+use usb_device::Result;
+
+use super::Keyboard;
+
+/// `Set_Protocol` bit of the host request bitset, see [Keyboard::consume_host_requests].
+pub const HOST_SET_PROTOCOL: u8 = 1 << 0;
+/// `Set_Idle` bit of the host request bitset.
+pub const HOST_SET_IDLE: u8 = 1 << 1;
+/// `Get_Report` bit of the host request bitset.
+pub const HOST_GET_REPORT: u8 = 1 << 2;
+/// `Set_Report` bit of the host request bitset.
+pub const HOST_SET_REPORT: u8 = 1 << 3;
+
+// HID class request codes, see the HID 1.11 specification section 7.2.
+const HID_REQ_GET_REPORT: u8 = 0x01;
+const HID_REQ_SET_REPORT: u8 = 0x09;
+const HID_REQ_SET_IDLE: u8 = 0x0a;
+const HID_REQ_SET_PROTOCOL: u8 = 0x0b;
+
+// Gets the host request bit of a HID class request to an interface.
+fn host_request(request: &usb_device::control::Request) -> u8 {
+    if request.request_type != RequestType::Class || request.recipient != Recipient::Interface {
+        return 0;
+    }
+
+    match request.request {
+        HID_REQ_GET_REPORT => HOST_GET_REPORT,
+        HID_REQ_SET_REPORT => HOST_SET_REPORT,
+        HID_REQ_SET_IDLE => HOST_SET_IDLE,
+        HID_REQ_SET_PROTOCOL => HOST_SET_PROTOCOL,
+        _ => 0,
+    }
+}
+
+impl<B: UsbBus> Keyboard<'_, B> {
+    /// Gets the HID class requests sent by the host since the last call, e.g. for logging host
+    /// behavior.
+    ///
+    /// Returns a bitset of [HOST_SET_PROTOCOL], [HOST_SET_IDLE], [HOST_GET_REPORT], and
+    /// [HOST_SET_REPORT]. Requests are only seen when the [Keyboard] itself is passed to
+    /// `UsbDevice::poll`, instead of its [HIDClass](usbd_hid::hid_class::HIDClass). The
+    /// interface number of the class is private, so requests to other HID interfaces on the
+    /// same device are also counted.
+    pub fn consume_host_requests(&mut self) -> u8 {
+        core::mem::take(&mut self.host_requests)
+    }
+}
+
+/// Forwards the USB class events to the [HIDClass](usbd_hid::hid_class::HIDClass), recording
+/// the host requests for [consume_host_requests](Keyboard::consume_host_requests).
+///
+/// A `Set_Idle` request for all reports to the keyboard
+/// [interface](Keyboard::interface_number) also sets the keyboard [idle](Keyboard::idle) rate.
+impl<B: UsbBus> UsbClass<B> for Keyboard<'_, B> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+        self.hid_class.get_configuration_descriptors(writer)
+    }
+
+    fn get_bos_descriptors(&self, writer: &mut BosWriter) -> Result<()> {
+        self.hid_class.get_bos_descriptors(writer)
+    }
+
+    fn get_string(&self, index: StringIndex, lang_id: u16) -> Option<&str> {
+        self.hid_class.get_string(index, lang_id)
+    }
+
+    fn reset(&mut self) {
+        UsbClass::reset(&mut self.hid_class);
+    }
+
+    fn poll(&mut self) {
+        UsbClass::poll(&mut self.hid_class);
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let request = xfer.request();
+        let bit = host_request(request);
+        self.host_requests |= bit;
+
+        // the high byte of wValue is the duration, the low byte the report ID, zero for all
+        // reports, and wIndex is the interface number
+        if bit == HOST_SET_IDLE
+            && request.value as u8 == 0
+            && request.index == self.interface_number as u16
+        {
+            self.set_idle_rate((request.value >> 8) as u8);
+        }
+
+        self.hid_class.control_out(xfer);
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        self.host_requests |= host_request(xfer.request());
+        self.hid_class.control_in(xfer);
+    }
+
+    fn endpoint_setup(&mut self, addr: EndpointAddress) {
+        self.hid_class.endpoint_setup(addr);
+    }
+
+    fn endpoint_out(&mut self, addr: EndpointAddress) {
+        self.hid_class.endpoint_out(addr);
+    }
+
+    fn endpoint_in_complete(&mut self, addr: EndpointAddress) {
+        self.hid_class.endpoint_in_complete(addr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usb_device::bus::UsbBusAllocator;
+
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use crate::builder::KeyboardBuilder;
+
+    #[test]
+    fn test_consume_host_requests() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let mut usb_dev = mock_usb_device(&alloc);
+
+        assert_eq!(keyboard.consume_host_requests(), 0);
+
+        // Set_Idle with an indefinite duration, to interface 0
+        usb_dev
+            .bus()
+            .queue_setup([0x21, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        usb_dev.poll(&mut [&mut keyboard]);
+
+        assert_eq!(keyboard.consume_host_requests(), HOST_SET_IDLE);
+        assert_eq!(keyboard.consume_host_requests(), 0);
+
+        // standard requests are not recorded
+        usb_dev
+            .bus()
+            .queue_setup([0x80, 0x06, 0x00, 0x01, 0x00, 0x00, 0x12, 0x00]);
+        usb_dev.poll(&mut [&mut keyboard]);
+        assert_eq!(keyboard.consume_host_requests(), 0);
+    }
+
+    #[test]
+    fn test_host_set_idle_rate() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let mut usb_dev = mock_usb_device(&alloc);
+
+        assert_eq!(keyboard.idle(), 0);
+
+        // Set_Idle with a duration of 2 (8 ms) for all reports, to interface 0
+        usb_dev
+            .bus()
+            .queue_setup([0x21, 0x0a, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00]);
+        usb_dev.poll(&mut [&mut keyboard]);
+
+        assert_eq!(keyboard.idle(), 2);
+        assert_eq!(keyboard.idle_rate_ms(), 8);
+
+        // a duration for a single report ID leaves the keyboard rate unchanged
+        usb_dev
+            .bus()
+            .queue_setup([0x21, 0x0a, 0x02, 0x7d, 0x00, 0x00, 0x00, 0x00]);
+        usb_dev.poll(&mut [&mut keyboard]);
+        assert_eq!(keyboard.idle(), 2);
+
+        // an indefinite duration stops the idle resends
+        usb_dev
+            .bus()
+            .queue_setup([0x21, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        usb_dev.poll(&mut [&mut keyboard]);
+        assert_eq!(keyboard.idle(), 0);
+    }
+
+    #[test]
+    fn test_host_set_idle_interface() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = KeyboardBuilder::new().interface_number(1).build(&alloc);
+        let mut usb_dev = mock_usb_device(&alloc);
+
+        assert_eq!(keyboard.interface_number(), 1);
+
+        // Set_Idle with a duration of 2 (8 ms) for all reports, to another interface
+        usb_dev
+            .bus()
+            .queue_setup([0x21, 0x0a, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00]);
+        usb_dev.poll(&mut [&mut keyboard]);
+        assert_eq!(keyboard.idle(), 0);
+
+        // the same request to the keyboard interface sets the rate
+        usb_dev
+            .bus()
+            .queue_setup([0x21, 0x0a, 0x00, 0x02, 0x01, 0x00, 0x00, 0x00]);
+        usb_dev.poll(&mut [&mut keyboard]);
+        assert_eq!(keyboard.idle(), 2);
+    }
+}
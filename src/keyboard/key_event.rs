@@ -0,0 +1,97 @@
+use usb_device::bus::UsbBus;
+
+use super::{keys_added, Keyboard};
+
+/// Whether a [KeyEvent] is a key press or release.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyEventKind {
+    Press,
+    Release,
+}
+
+/// Discrete key press or release, see [Keyboard::drain_events].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyEvent {
+    /// Keycode of the key, including modifier keys.
+    pub key: u8,
+    /// Whether the key was pressed, or released.
+    pub kind: KeyEventKind,
+}
+
+impl KeyEvent {
+    /// Creates a key press [KeyEvent].
+    pub const fn press(key: u8) -> Self {
+        Self {
+            key,
+            kind: KeyEventKind::Press,
+        }
+    }
+
+    /// Creates a key release [KeyEvent].
+    pub const fn release(key: u8) -> Self {
+        Self {
+            key,
+            kind: KeyEventKind::Release,
+        }
+    }
+}
+
+impl<B: UsbBus> Keyboard<'_, B> {
+    /// Gets the key presses and releases in the current keyboard report since the last call.
+    ///
+    /// Events are computed from the report diff, like [report_diff](Self::report_diff), and
+    /// pressed keys come before released keys. Sending reports doesn't affect the events, so
+    /// firmware can drain them before or after `send_report`.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = KeyEvent> {
+        let old = (self.event_report, self.event_nkro_keys);
+        let new = (self.report, self.nkro_keys);
+        self.event_report = self.report;
+        self.event_nkro_keys = self.nkro_keys;
+
+        keys_added(old, new)
+            .map(KeyEvent::press)
+            .chain(keys_added(new, old).map(KeyEvent::release))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use usb_device::bus::UsbBusAllocator;
+    use usbd_hid::descriptor::KeyboardUsage;
+
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use crate::KeyboardOps;
+
+    #[test]
+    fn test_drain_events() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+
+        let key_a = KeyboardUsage::KeyboardAa as u8;
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+
+        KeyboardOps::press(&mut keyboard, key_a);
+        KeyboardOps::press(&mut keyboard, shift);
+        let mut events: std::vec::Vec<KeyEvent> = keyboard.drain_events().collect();
+
+        // events are drained, and not repeated after sending the report
+        KeyboardOps::send_report(&mut keyboard).unwrap();
+        assert_eq!(keyboard.drain_events().count(), 0);
+
+        KeyboardOps::release(&mut keyboard, key_a);
+        events.extend(keyboard.drain_events());
+
+        assert_eq!(
+            events,
+            [
+                KeyEvent::press(key_a),
+                KeyEvent::press(shift),
+                KeyEvent::release(key_a),
+            ]
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use heapless::Vec;
+
+use crate::error::KeyboardResult;
+
+use super::KeyboardOps;
+
+/// Key event stored in a [Macro].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MacroEvent {
+    /// Keycode of the event.
+    pub key: u8,
+    /// Whether the key was pressed, or released.
+    pub pressed: bool,
+}
+
+impl MacroEvent {
+    /// Creates a key press [MacroEvent].
+    pub const fn press(key: u8) -> Self {
+        Self { key, pressed: true }
+    }
+
+    /// Creates a key release [MacroEvent].
+    pub const fn release(key: u8) -> Self {
+        Self {
+            key,
+            pressed: false,
+        }
+    }
+}
+
+/// Recorded sequence of up to `N` key events, replayed with [play](Self::play).
+///
+/// Firmware records events by passing its key events to [record_event](Self::record_event)
+/// while recording. Playback drives the keyboard directly, so replayed events never reach
+/// the recorder, and a macro can't end up recording its own playback.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Macro<const N: usize> {
+    events: Vec<MacroEvent, N>,
+    recording: bool,
+}
+
+impl<const N: usize> Macro<N> {
+    /// Creates a new, empty [Macro].
+    pub const fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            recording: false,
+        }
+    }
+
+    /// Clears the recorded events, and starts recording.
+    pub fn start_recording(&mut self) {
+        self.events.clear();
+        self.recording = true;
+    }
+
+    /// Stops recording, keeping the recorded events.
+    pub fn stop_recording(&mut self) {
+        self.recording = false;
+    }
+
+    /// Gets whether key events are being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Records a key event while recording.
+    ///
+    /// Returns false if the macro is not recording, or already holds `N` events.
+    pub fn record_event(&mut self, event: MacroEvent) -> bool {
+        self.recording && self.events.push(event).is_ok()
+    }
+
+    /// Gets the recorded key events, in order.
+    pub fn events(&self) -> &[MacroEvent] {
+        self.events.as_ref()
+    }
+
+    /// Replays the recorded key events on the keyboard.
+    ///
+    /// Sends the keyboard report after each event, so the host sees every step. Stops at the
+    /// first failed send.
+    pub fn play<K: KeyboardOps>(&self, keyboard: &mut K) -> KeyboardResult<()> {
+        for event in self.events.iter() {
+            if event.pressed {
+                keyboard.press(event.key);
+            } else {
+                keyboard.release(event.key);
+            }
+
+            keyboard.send_report()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usb_device::bus::UsbBusAllocator;
+    use usbd_hid::descriptor::KeyboardUsage;
+
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use crate::Keyboard;
+
+    const KEY_A: u8 = KeyboardUsage::KeyboardAa as u8;
+    const KEY_B: u8 = KeyboardUsage::KeyboardBb as u8;
+
+    #[test]
+    fn test_macro_record_and_play() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let events = [
+            MacroEvent::press(KEY_A),
+            MacroEvent::press(KEY_B),
+            MacroEvent::release(KEY_A),
+        ];
+        let mut recorder = Macro::<3>::new();
+
+        // events are only recorded while recording
+        assert!(!recorder.record_event(events[0]));
+
+        recorder.start_recording();
+        for event in events {
+            assert!(recorder.record_event(event));
+        }
+        assert!(!recorder.record_event(MacroEvent::release(KEY_B)));
+        recorder.stop_recording();
+
+        recorder.play(&mut keyboard).unwrap();
+        assert_eq!(recorder.events(), events);
+
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x04, 0x05, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+}
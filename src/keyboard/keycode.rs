@@ -0,0 +1,85 @@
+use usbd_hid::descriptor::{KeyboardUsage, MediaKey, SystemControlKey};
+
+/// Typed usage stored as a raw `u8` keycode in the reports.
+///
+/// `From<KeyboardUsage> for u8` can't be implemented outside of `usbd_hid`, so the conversions
+/// to keycodes live here instead of in `as u8` casts.
+pub trait Keycode: Copy {
+    /// Gets the raw keycode of the usage.
+    fn keycode(self) -> u8;
+}
+
+impl Keycode for KeyboardUsage {
+    fn keycode(self) -> u8 {
+        self as u8
+    }
+}
+
+impl Keycode for MediaKey {
+    fn keycode(self) -> u8 {
+        // every media key usage fits in a byte, see `is_media`
+        self as u8
+    }
+}
+
+impl Keycode for SystemControlKey {
+    fn keycode(self) -> u8 {
+        self.into()
+    }
+}
+
+/// Converts a raw keycode into its [KeyboardUsage].
+///
+/// Returns `None` for reserved keycodes.
+pub fn keyboard_usage(key: u8) -> Option<KeyboardUsage> {
+    Some(KeyboardUsage::from(key)).filter(|&usage| usage != KeyboardUsage::Reserved)
+}
+
+/// Converts a raw keycode into its [MediaKey].
+///
+/// Returns `None` for keycodes without a media key usage.
+pub fn media_key(key: u8) -> Option<MediaKey> {
+    Some(MediaKey::from(key)).filter(|&usage| usage != MediaKey::Reserved)
+}
+
+/// Converts a raw keycode into its [SystemControlKey].
+///
+/// Returns `None` for keycodes without a system control usage.
+pub fn system_control_key(key: u8) -> Option<SystemControlKey> {
+    Some(SystemControlKey::from(key)).filter(|&usage| usage != SystemControlKey::Reserved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keycode_round_trip() {
+        for usage in [
+            KeyboardUsage::KeyboardErrorRollOver,
+            KeyboardUsage::KeyboardAa,
+            KeyboardUsage::KeyboardEnter,
+            KeyboardUsage::KeypadHexadecimal,
+            KeyboardUsage::KeyboardRightGUI,
+        ] {
+            assert_eq!(keyboard_usage(usage.keycode()), Some(usage));
+        }
+
+        for key in [
+            MediaKey::PlayPause,
+            MediaKey::Mute,
+            MediaKey::VolumeDecrement,
+        ] {
+            assert_eq!(media_key(key.keycode()), Some(key));
+        }
+
+        for key in [SystemControlKey::PowerDown, SystemControlKey::Sleep] {
+            assert_eq!(system_control_key(key.keycode()), Some(key));
+        }
+
+        assert_eq!(keyboard_usage(0x00), None);
+        assert_eq!(keyboard_usage(0xe8), None);
+        assert_eq!(media_key(KeyboardUsage::KeyboardAa.keycode()), None);
+        assert_eq!(system_control_key(0x00), None);
+    }
+}
@@ -0,0 +1,155 @@
+use heapless::Vec;
+
+use super::{is_modifier, key_to_modifier_bitfield};
+
+/// Keyboard report storing up to `N` pressed keycodes.
+///
+/// Sits between the six-key boot report and the NKRO bitmap, letting the rollover count be
+/// chosen to balance report size against the number of simultaneous keys.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeycodeVecReport<const N: usize> {
+    pub modifier: u8,
+    pub keycodes: Vec<u8, N>,
+}
+
+impl<const N: usize> KeycodeVecReport<N> {
+    /// Creates a new, empty [KeycodeVecReport].
+    pub const fn new() -> Self {
+        Self {
+            modifier: 0,
+            keycodes: Vec::new(),
+        }
+    }
+
+    /// Press a key, and add it to the report.
+    ///
+    /// Returns 1 if the key is a modifier, or was added to the keycodes.
+    /// Returns 0 if all `N` keycodes are already pressed.
+    pub fn press(&mut self, key: u8) -> usize {
+        if is_modifier(key) {
+            self.modifier |= key_to_modifier_bitfield(key);
+            1
+        } else if key == 0 || self.keycodes.contains(&key) {
+            (key != 0) as usize
+        } else {
+            self.keycodes.push(key).is_ok() as usize
+        }
+    }
+
+    /// Release a pressed key if it is present in the report.
+    ///
+    /// Returns 1 if the key was pressed, or is a modifier key.
+    /// Returns 0 otherwise.
+    pub fn release(&mut self, key: u8) -> usize {
+        if is_modifier(key) {
+            self.modifier &= !key_to_modifier_bitfield(key);
+            1
+        } else {
+            match self.keycodes.iter().position(|&k| k == key) {
+                Some(pos) => {
+                    self.keycodes.remove(pos);
+                    1
+                }
+                None => 0,
+            }
+        }
+    }
+
+    /// Release all keys in the report.
+    pub fn release_all(&mut self) {
+        self.modifier = 0;
+        self.keycodes.clear();
+    }
+
+    /// Sorts the pressed keycodes in ascending order.
+    pub fn sort(&mut self) {
+        self.keycodes.sort_unstable();
+    }
+
+    /// Gets whether the provided key is pressed in the report.
+    pub fn is_key_pressed(&self, key: u8) -> bool {
+        if is_modifier(key) {
+            self.modifier & key_to_modifier_bitfield(key) != 0
+        } else {
+            key != 0 && self.keycodes.contains(&key)
+        }
+    }
+
+    /// Gets whether all `N` keycodes are pressed.
+    pub fn is_full(&self) -> bool {
+        self.keycodes.is_full()
+    }
+
+    /// Serializes the report in the boot keyboard layout, with `N` keycode slots.
+    ///
+    /// Writes the modifier, a reserved byte, then the keycodes padded with zeros.
+    /// Returns the number of bytes written, or 0 if the buffer is too small.
+    pub fn serialize(&self, buf: &mut [u8]) -> usize {
+        let len = N + 2;
+
+        if buf.len() < len {
+            return 0;
+        }
+
+        buf[0] = self.modifier;
+        buf[1] = 0;
+        buf[2..len].fill(0);
+        buf[2..2 + self.keycodes.len()].copy_from_slice(self.keycodes.as_ref());
+
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use usbd_hid::descriptor::KeyboardUsage;
+
+    const KEY_A: u8 = KeyboardUsage::KeyboardAa as u8;
+    const SHIFT: u8 = KeyboardUsage::KeyboardLeftShift as u8;
+
+    fn assert_overflow<const N: usize>() {
+        let mut report = KeycodeVecReport::<N>::new();
+
+        // press in descending order to exercise sorting
+        for key in (KEY_A..KEY_A + N as u8).rev() {
+            assert_eq!(report.press(key), 1);
+        }
+        assert!(report.is_full());
+
+        // duplicates and modifiers still succeed, extra keys are dropped
+        assert_eq!(report.press(KEY_A), 1);
+        assert_eq!(report.press(SHIFT), 1);
+        assert_eq!(report.press(KEY_A + N as u8), 0);
+        assert!(!report.is_key_pressed(KEY_A + N as u8));
+
+        report.sort();
+        assert!(report.keycodes.iter().copied().eq(KEY_A..KEY_A + N as u8));
+
+        let mut buf = [0xffu8; 16];
+        assert_eq!(report.serialize(&mut buf), N + 2);
+        assert_eq!(buf[..2], [0x02, 0x00]);
+        assert_eq!(buf[2..N + 2], report.keycodes[..]);
+
+        assert_eq!(report.release(KEY_A), 1);
+        assert_eq!(report.release(KEY_A), 0);
+        assert_eq!(report.press(KEY_A + N as u8), 1);
+        assert!(report.is_key_pressed(KEY_A + N as u8));
+
+        report.release_all();
+        assert_eq!(report, KeycodeVecReport::new());
+        assert_eq!(report.serialize(&mut buf), N + 2);
+        assert!(buf[..N + 2].iter().all(|&b| b == 0));
+        assert_eq!(report.serialize(&mut buf[..N + 1]), 0);
+    }
+
+    #[test]
+    fn test_keycode_vec_capacity_6() {
+        assert_overflow::<6>();
+    }
+
+    #[test]
+    fn test_keycode_vec_capacity_10() {
+        assert_overflow::<10>();
+    }
+}
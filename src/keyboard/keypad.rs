@@ -0,0 +1,197 @@
+use usb_device::bus::UsbBus;
+use usbd_hid::descriptor::KeyboardUsage;
+
+use super::{Keyboard, LED_NUM_LOCK};
+
+/// Resolves the usage sent for a keypad key with the host Num Lock state.
+///
+/// With Num Lock off, the keypad digits and decimal point send their navigation usages, e.g.
+/// [Keypad7Home](KeyboardUsage::Keypad7Home) sends [KeyboardHome](KeyboardUsage::KeyboardHome).
+/// Other keys, and every key with Num Lock on, are returned unchanged.
+pub const fn keypad_remap(key: u8, num_lock: bool) -> u8 {
+    if num_lock {
+        return key;
+    }
+
+    const KEYPAD_1: u8 = KeyboardUsage::Keypad1End as u8;
+    const KEYPAD_2: u8 = KeyboardUsage::Keypad2DownArrow as u8;
+    const KEYPAD_3: u8 = KeyboardUsage::Keypad3PageDown as u8;
+    const KEYPAD_4: u8 = KeyboardUsage::Keypad4LeftArrow as u8;
+    const KEYPAD_6: u8 = KeyboardUsage::Keypad6RightArrow as u8;
+    const KEYPAD_7: u8 = KeyboardUsage::Keypad7Home as u8;
+    const KEYPAD_8: u8 = KeyboardUsage::Keypad8UpArrow as u8;
+    const KEYPAD_9: u8 = KeyboardUsage::Keypad9PageUp as u8;
+    const KEYPAD_0: u8 = KeyboardUsage::Keypad0Insert as u8;
+    const KEYPAD_DOT: u8 = KeyboardUsage::KeypadPeriodDelete as u8;
+
+    let usage = match key {
+        KEYPAD_1 => KeyboardUsage::KeyboardEnd,
+        KEYPAD_2 => KeyboardUsage::KeyboardDownArrow,
+        KEYPAD_3 => KeyboardUsage::KeyboardPageDown,
+        KEYPAD_4 => KeyboardUsage::KeyboardLeftArrow,
+        KEYPAD_6 => KeyboardUsage::KeyboardRightArrow,
+        KEYPAD_7 => KeyboardUsage::KeyboardHome,
+        KEYPAD_8 => KeyboardUsage::KeyboardUpArrow,
+        KEYPAD_9 => KeyboardUsage::KeyboardPageUp,
+        KEYPAD_0 => KeyboardUsage::KeyboardInsert,
+        KEYPAD_DOT => KeyboardUsage::KeyboardDelete,
+        _ => return key,
+    };
+
+    usage as u8
+}
+
+// Gets the bit of a remappable keypad key in `Keyboard::remapped_keypad`, or 0 for other keys.
+const fn keypad_bit(key: u8) -> u16 {
+    const KEYPAD_1: u8 = KeyboardUsage::Keypad1End as u8;
+    const KEYPAD_DOT: u8 = KeyboardUsage::KeypadPeriodDelete as u8;
+
+    match key {
+        KEYPAD_1..=KEYPAD_DOT => 1 << (key - KEYPAD_1),
+        _ => 0,
+    }
+}
+
+impl<B: UsbBus> Keyboard<'_, B> {
+    /// Gets whether the host has Num Lock on, from the last LED state read by
+    /// [read_leds](Self::read_leds).
+    pub fn num_lock(&self) -> bool {
+        self.report.leds & LED_NUM_LOCK != 0
+    }
+
+    /// Gets whether pressed keypad keys are resolved with [keypad_remap].
+    pub fn num_lock_remap(&self) -> bool {
+        self.num_lock_remap
+    }
+
+    /// Sets whether pressed keypad keys are resolved with [keypad_remap], using the last-known
+    /// host Num Lock state.
+    ///
+    /// Disabled by default, since most hosts apply Num Lock to the keypad usages themselves.
+    pub fn set_num_lock_remap(&mut self, enabled: bool) {
+        self.num_lock_remap = enabled;
+    }
+
+    // Records whether a pressed keypad key was remapped, so its release matches the press.
+    pub(crate) fn record_keypad_press(&mut self, key: u8, resolved: u8) {
+        let bit = keypad_bit(key);
+        if resolved != key {
+            self.remapped_keypad |= bit;
+        } else {
+            self.remapped_keypad &= !bit;
+        }
+    }
+
+    // Gets the usage a released key was pressed with, forgetting a keypad key remap.
+    pub(crate) fn take_keypad_release(&mut self, key: u8) -> u8 {
+        let bit = keypad_bit(key);
+        if self.remapped_keypad & bit != 0 {
+            self.remapped_keypad &= !bit;
+            keypad_remap(key, false)
+        } else {
+            key
+        }
+    }
+
+    // Resolves the usage for a pressed key, when Num Lock remapping is enabled.
+    pub(crate) fn resolve_keypad(&self, key: u8) -> u8 {
+        if self.num_lock_remap {
+            keypad_remap(key, self.num_lock())
+        } else {
+            key
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usb_device::bus::UsbBusAllocator;
+
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use crate::KeyboardOps;
+
+    const KEYPAD_7: u8 = KeyboardUsage::Keypad7Home as u8;
+    const HOME: u8 = KeyboardUsage::KeyboardHome as u8;
+
+    #[test]
+    fn test_keypad_remap() {
+        assert_eq!(keypad_remap(KEYPAD_7, true), KEYPAD_7);
+        assert_eq!(keypad_remap(KEYPAD_7, false), HOME);
+        assert_eq!(
+            keypad_remap(KeyboardUsage::KeypadPeriodDelete as u8, false),
+            KeyboardUsage::KeyboardDelete as u8
+        );
+
+        for key in [
+            KeyboardUsage::Keypad5 as u8,
+            KeyboardUsage::KeypadEnter as u8,
+            KeyboardUsage::KeyboardAa as u8,
+        ] {
+            assert_eq!(keypad_remap(key, false), key);
+        }
+    }
+
+    #[test]
+    fn test_num_lock_remap() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        keyboard.set_num_lock_remap(true);
+        assert!(!keyboard.num_lock());
+
+        KeyboardOps::press(&mut keyboard, KEYPAD_7);
+        assert!(keyboard.is_key_pressed(HOME));
+        assert!(!keyboard.is_key_pressed(KEYPAD_7));
+
+        // the host turns Num Lock on
+        usb_dev.bus().queue_out(1, &[LED_NUM_LOCK]);
+        assert_eq!(keyboard.read_leds(), Some(LED_NUM_LOCK));
+        assert!(keyboard.num_lock());
+
+        // releasing the key after the Num Lock change still releases the resolved usage
+        KeyboardOps::release(&mut keyboard, KEYPAD_7);
+        assert!(keyboard.report_is_empty());
+
+        KeyboardOps::press(&mut keyboard, KEYPAD_7);
+        assert!(keyboard.is_key_pressed(KEYPAD_7));
+        KeyboardOps::release(&mut keyboard, KEYPAD_7);
+
+        usb_dev.bus().queue_out(1, &[0]);
+        keyboard.poll().unwrap();
+        assert!(!keyboard.num_lock());
+
+        keyboard.set_num_lock_remap(false);
+        KeyboardOps::press(&mut keyboard, KEYPAD_7);
+        assert!(keyboard.is_key_pressed(KEYPAD_7));
+    }
+
+    #[test]
+    fn test_num_lock_remap_keeps_held_navigation_key() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        keyboard.set_num_lock_remap(true);
+        usb_dev.bus().queue_out(1, &[LED_NUM_LOCK]);
+        keyboard.read_leds();
+
+        // the real Home key is held while the keypad 7 is tapped with Num Lock on
+        KeyboardOps::press(&mut keyboard, HOME);
+        KeyboardOps::press(&mut keyboard, KEYPAD_7);
+        KeyboardOps::release(&mut keyboard, KEYPAD_7);
+
+        assert!(keyboard.is_key_pressed(HOME));
+        assert!(!keyboard.is_key_pressed(KEYPAD_7));
+
+        // a remapped press with Num Lock off releases the navigation usage
+        usb_dev.bus().queue_out(1, &[0]);
+        keyboard.read_leds();
+        KeyboardOps::release(&mut keyboard, HOME);
+        KeyboardOps::press(&mut keyboard, KEYPAD_7);
+        assert!(keyboard.is_key_pressed(HOME));
+        KeyboardOps::release(&mut keyboard, KEYPAD_7);
+        assert!(keyboard.report_is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+/// Keycode of a transparent layer entry, falling through to the next active layer below.
+pub const TRANSPARENT: u8 = 0;
+
+/// Maximum number of layers in a [LayerStack].
+pub const MAX_LAYERS: usize = 32;
+
+/// Stack of keymap layers, mapping physical key positions to keycodes.
+///
+/// Layer 0 is the base layer, and is always active. Higher layers are activated either
+/// momentarily (e.g. while an Fn key is held), or toggled on until toggled off again.
+/// Lookups resolve from the highest active layer down, falling through [TRANSPARENT] entries.
+pub struct LayerStack<'l, const KEYS: usize> {
+    layers: &'l [[u8; KEYS]],
+    momentary: u32,
+    toggled: u32,
+}
+
+impl<'l, const KEYS: usize> LayerStack<'l, KEYS> {
+    /// Creates a new [LayerStack] from the keymap layers.
+    ///
+    /// Layers past [MAX_LAYERS] are ignored.
+    pub const fn new(layers: &'l [[u8; KEYS]]) -> Self {
+        Self {
+            layers,
+            momentary: 0,
+            toggled: 0,
+        }
+    }
+
+    /// Gets the number of layers in the stack.
+    pub fn len(&self) -> usize {
+        core::cmp::min(self.layers.len(), MAX_LAYERS)
+    }
+
+    /// Gets whether the stack has no layers.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Momentarily activates a layer, until [deactivate](Self::deactivate) is called.
+    pub fn activate(&mut self, layer: usize) {
+        self.momentary |= self.layer_bit(layer);
+    }
+
+    /// Deactivates a momentarily activated layer.
+    ///
+    /// Toggled layers stay active.
+    pub fn deactivate(&mut self, layer: usize) {
+        self.momentary &= !self.layer_bit(layer);
+    }
+
+    /// Toggles a layer on or off.
+    pub fn toggle(&mut self, layer: usize) {
+        self.toggled ^= self.layer_bit(layer);
+    }
+
+    /// Deactivates all layers above the base layer.
+    pub fn reset(&mut self) {
+        self.momentary = 0;
+        self.toggled = 0;
+    }
+
+    /// Gets whether the layer is active.
+    pub fn is_active(&self, layer: usize) -> bool {
+        self.active() & self.layer_bit(layer) != 0
+    }
+
+    /// Gets the highest active layer.
+    pub fn top(&self) -> usize {
+        (u32::BITS - self.active().leading_zeros()).saturating_sub(1) as usize
+    }
+
+    /// Looks up the keycode for the physical key position.
+    ///
+    /// Returns [TRANSPARENT] if the position is out of range, or no active layer maps it.
+    pub fn lookup(&self, position: usize) -> u8 {
+        if position >= KEYS {
+            return TRANSPARENT;
+        }
+
+        (0..self.len())
+            .rev()
+            .filter(|&layer| self.is_active(layer))
+            .map(|layer| self.layers[layer][position])
+            .find(|&key| key != TRANSPARENT)
+            .unwrap_or(TRANSPARENT)
+    }
+
+    fn active(&self) -> u32 {
+        // the base layer is always active
+        self.momentary | self.toggled | self.layer_bit(0)
+    }
+
+    fn layer_bit(&self, layer: usize) -> u32 {
+        if layer < self.len() {
+            1 << layer
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_H: u8 = 0x0b;
+    const KEY_J: u8 = 0x0d;
+    const KEY_ESC: u8 = 0x29;
+    const KEY_LEFT: u8 = 0x50;
+    const KEY_DOWN: u8 = 0x51;
+    const KEY_1: u8 = 0x1e;
+
+    const BASE: usize = 0;
+    const FN: usize = 1;
+    const NUM: usize = 2;
+
+    static LAYERS: [[u8; 3]; 3] = [
+        [KEY_H, KEY_J, KEY_ESC],
+        [KEY_LEFT, KEY_DOWN, TRANSPARENT],
+        [KEY_1, TRANSPARENT, TRANSPARENT],
+    ];
+
+    #[test]
+    fn test_layer_fall_through() {
+        let mut layers = LayerStack::new(&LAYERS);
+
+        assert!(layers.is_active(BASE));
+        assert_eq!(layers.top(), BASE);
+        assert_eq!(layers.lookup(0), KEY_H);
+        assert_eq!(layers.lookup(2), KEY_ESC);
+        assert_eq!(layers.lookup(3), TRANSPARENT);
+
+        layers.activate(FN);
+        assert_eq!(layers.top(), FN);
+        assert_eq!(layers.lookup(0), KEY_LEFT);
+        assert_eq!(layers.lookup(1), KEY_DOWN);
+        // transparent entries fall through to the base layer
+        assert_eq!(layers.lookup(2), KEY_ESC);
+
+        layers.deactivate(FN);
+        assert_eq!(layers.lookup(0), KEY_H);
+    }
+
+    #[test]
+    fn test_layer_toggle() {
+        let mut layers = LayerStack::new(&LAYERS);
+
+        layers.toggle(NUM);
+        layers.activate(FN);
+        assert_eq!(layers.top(), NUM);
+        assert_eq!(layers.lookup(0), KEY_1);
+        // falls through the numpad layer to the Fn layer
+        assert_eq!(layers.lookup(1), KEY_DOWN);
+
+        // deactivating a toggled layer keeps it active
+        layers.deactivate(NUM);
+        layers.deactivate(FN);
+        assert!(layers.is_active(NUM));
+        assert_eq!(layers.lookup(1), KEY_J);
+
+        layers.toggle(NUM);
+        assert!(!layers.is_active(NUM));
+        assert_eq!(layers.lookup(0), KEY_H);
+
+        // out-of-range layers are ignored
+        layers.activate(MAX_LAYERS);
+        layers.toggle(3);
+        assert_eq!(layers.top(), BASE);
+    }
+}
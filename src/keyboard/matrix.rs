@@ -0,0 +1,166 @@
+/// Default number of consecutive stable scans before a key change is reported.
+///
+/// With one scan per millisecond, this is the debounce time in milliseconds.
+pub const DEBOUNCE_MS: u8 = 5;
+
+/// Maximum number of columns in a [Matrix], the width of a row's column bits.
+pub const MAX_COLS: usize = 32;
+
+/// Chip-agnostic access to the raw state of a key matrix.
+pub trait MatrixReader {
+    /// Reads the column bits of a row, bit `n` is set if the key in column `n` is pressed.
+    fn read_row(&mut self, row: usize) -> u32;
+}
+
+impl<F: FnMut(usize) -> u32> MatrixReader for F {
+    fn read_row(&mut self, row: usize) -> u32 {
+        self(row)
+    }
+}
+
+/// Debounced key press or release in a [Matrix].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MatrixEvent {
+    pub row: usize,
+    pub col: usize,
+    pub pressed: bool,
+}
+
+/// Key matrix with per-key debouncing.
+///
+/// A key change is only reported after the raw state has been stable for the debounce time.
+pub struct Matrix<const ROWS: usize, const COLS: usize> {
+    state: [u32; ROWS],
+    counters: [[u8; COLS]; ROWS],
+    debounce_ms: u8,
+}
+
+impl<const ROWS: usize, const COLS: usize> Matrix<ROWS, COLS> {
+    /// Creates a new [Matrix] with the default [DEBOUNCE_MS].
+    pub const fn new() -> Self {
+        Self::with_debounce_ms(DEBOUNCE_MS)
+    }
+
+    /// Creates a new [Matrix] with a custom debounce time.
+    pub const fn with_debounce_ms(debounce_ms: u8) -> Self {
+        assert!(COLS <= MAX_COLS, "matrix has too many columns");
+
+        Self {
+            state: [0; ROWS],
+            counters: [[0; COLS]; ROWS],
+            debounce_ms,
+        }
+    }
+
+    /// Gets the debounce time in milliseconds.
+    pub const fn debounce_ms(&self) -> u8 {
+        self.debounce_ms
+    }
+
+    /// Gets the position of a key in a flat keymap, e.g. for a
+    /// [LayerStack](crate::layer::LayerStack) lookup.
+    pub const fn position(&self, event: &MatrixEvent) -> usize {
+        event.row * COLS + event.col
+    }
+
+    /// Gets whether the key is pressed in the debounced matrix state.
+    pub const fn is_pressed(&self, row: usize, col: usize) -> bool {
+        row < ROWS && col < COLS && self.state[row] & (1 << col) != 0
+    }
+
+    /// Scans the matrix, calling `on_event` for every debounced key change.
+    ///
+    /// Call this once per millisecond.
+    pub fn scan<R: MatrixReader, E: FnMut(MatrixEvent)>(
+        &mut self,
+        reader: &mut R,
+        mut on_event: E,
+    ) {
+        for row in 0..ROWS {
+            let raw = reader.read_row(row);
+
+            for col in 0..COLS {
+                let bit = 1 << col;
+                let counter = &mut self.counters[row][col];
+
+                if (raw ^ self.state[row]) & bit == 0 {
+                    *counter = 0;
+                    continue;
+                }
+
+                *counter = counter.saturating_add(1);
+
+                if *counter >= self.debounce_ms {
+                    *counter = 0;
+                    self.state[row] ^= bit;
+
+                    on_event(MatrixEvent {
+                        row,
+                        col,
+                        pressed: raw & bit != 0,
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl<const ROWS: usize, const COLS: usize> Default for Matrix<ROWS, COLS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_debounce() {
+        // bouncing press of row 1 column 2, followed by a bouncing release
+        let scans = [
+            0b100, 0b000, 0b100, 0b100, 0b100, 0b100, 0b100, 0b100, 0b000, 0b100, 0b000, 0b000,
+            0b000, 0b000, 0b000, 0b000,
+        ];
+
+        let mut matrix: Matrix<2, 3> = Matrix::new();
+        let mut events = [None; 2];
+        let mut count = 0;
+
+        for (ms, &bits) in scans.iter().enumerate() {
+            let mut reader = |row: usize| if row == 1 { bits } else { 0 };
+
+            matrix.scan(&mut reader, |event| {
+                events[count] = Some((ms, event));
+                count += 1;
+            });
+        }
+
+        assert_eq!(count, 2);
+        assert_eq!(
+            events[0],
+            Some((
+                6,
+                MatrixEvent {
+                    row: 1,
+                    col: 2,
+                    pressed: true
+                }
+            ))
+        );
+        assert_eq!(
+            events[1],
+            Some((
+                14,
+                MatrixEvent {
+                    row: 1,
+                    col: 2,
+                    pressed: false
+                }
+            ))
+        );
+
+        assert_eq!(matrix.position(&events[0].unwrap().1), 5);
+        assert!(!matrix.is_pressed(1, 2));
+    }
+}
@@ -1,8 +1,10 @@
-use usb_device::Result;
 use usbd_hid::hid_class::{
     HidClassSettings, HidProtocol, HidSubClass, ProtocolModeConfig,
 };
 
+use crate::error::{KeyboardError, KeyboardResult};
+use crate::hid_settings::{HIDReport, HIDReportId};
+
 use super::*;
 
 pub const fn media_hid_class_settings() -> HidClassSettings {
@@ -14,35 +16,38 @@ pub const fn media_hid_class_settings() -> HidClassSettings {
     }
 }
 
-pub trait MediaKeyboard {
-    /// End the keyboard reports.
-    fn end(&mut self) -> Result<()>;
+/// Maximum number of consumer usages held by a media [Keyboard].
+///
+/// The [MediaKeyboardReport] only carries one usage, the others wait to be reported under the
+/// [Clamp](ConsumerRolloverPolicy::Clamp) policy.
+pub const MEDIA_MAX_HELD_KEYS: usize = 4;
 
-    /// Sending the current HID report to the host:
-    ///
-    /// Depending on the differences between the current and previous HID reports, we
-    /// might need to send one or two extra reports to guarantee that the host will
-    /// process the changes in the correct order. There are two important scenarios
-    /// to consider:
-    ///
-    /// 1. If a non-modifier keycode toggles off in the same report as a modifier
-    /// changes, the host might process the modifier change first. For example, if
-    /// both `shift` and `4` toggle off in the same report (most likely from a
-    /// `LSHIFT(Key_4)` key being released), and that key has been held long enough
-    /// to trigger character repeat, we could end up with a plain `4` in the output
-    /// at the end of the repeat: `$$$$4` instead of `$$$$$`.
-    ///
-    /// 2. If a non-modifier keycode toggles on in the same report as a modifier
-    /// changes, the host might process the non-modifer first. For example, pressing
-    /// and holding an `LSHIFT(Key_4)` key might result in `4$$$` rather than `$$$$`.
+/// Behavior when more consumer usages are pressed than the [MediaKeyboardReport] can carry.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ConsumerRolloverPolicy {
+    /// Ignore the newly pressed usage.
+    #[default]
+    DropNewest = 0,
+    /// Replace the reported usage with the newly pressed usage.
+    DropOldest,
+    /// Hold the newly pressed usage, and report it once the earlier usages are released.
+    Clamp,
+}
+
+pub trait MediaKeyboard {
+    /// End the media reports, releasing and sending the held media keys.
     ///
-    /// Therefore, each call to `sendReport()` must send (up to) three reports to the
-    /// host to guarantee the correct order of processing:
+    /// The keyboard keys of a composite keyboard are left alone.
+    fn end(&mut self) -> KeyboardResult<()>;
+
+    /// Sends the current media report to the host, if the reported usage changed.
     ///
-    /// 1. A report with toggled-off non-modifiers removed.
-    /// 2. A report with changes to modifiers.
-    /// 3. A report with toggled-on non-modifiers added.
-    fn send_report(&mut self) -> Result<()>;
+    /// Unlike the keyboard reports, the [MediaKeyboardReport] has no modifiers, and carries a
+    /// single usage as an array item. Every change, including one usage replacing another, is
+    /// sent as one report: the host treats a usage missing from the array as released, before
+    /// handling the newly reported usage. No intermediate release report is sent.
+    fn send_report(&mut self) -> KeyboardResult<()>;
 
     /// Press a key, and add it to the current report.
     ///
@@ -63,87 +68,240 @@ pub trait MediaKeyboard {
     fn was_key_pressed(&self, key: u8) -> bool;
 }
 
-impl MediaKeyboard for Keyboard<'_> {
-    fn end(&mut self) -> Result<()> {
-        self.release_all();
-        self.send_report()
+// Gets whether the report holds the media key usage. A zero `usage_id` holds no key.
+fn is_media_usage(report: MediaKeyboardReport, key: u8) -> bool {
+    let usage_id = report.usage_id;
+    is_media(key) && usage_id != 0 && usage_id == key as u16
+}
+
+impl<B: UsbBus> Keyboard<'_, B> {
+    /// Gets the current media keyboard report.
+    pub fn media_report(&self) -> MediaKeyboardReport {
+        MediaKeyboardReport {
+            usage_id: self.media_keys[0],
+        }
+    }
+
+    /// Gets the previous media keyboard report.
+    pub fn last_media_report(&self) -> MediaKeyboardReport {
+        self.last_media_report
     }
 
-    fn send_report(&mut self) -> Result<()> {
-        if self.keycodes_changed() {
-            let report = self.report().clone();
+    /// Gets the [ConsumerRolloverPolicy] for over-capacity consumer usages.
+    pub fn consumer_rollover(&self) -> ConsumerRolloverPolicy {
+        self.consumer_rollover
+    }
+
+    /// Sets the [ConsumerRolloverPolicy] for over-capacity consumer usages.
+    pub fn set_consumer_rollover(&mut self, policy: ConsumerRolloverPolicy) {
+        self.consumer_rollover = policy;
+    }
+}
+
+impl<B: UsbBus> MediaKeyboard for Keyboard<'_, B> {
+    fn end(&mut self) -> KeyboardResult<()> {
+        self.media_keys = [0; MEDIA_MAX_HELD_KEYS];
+        MediaKeyboard::send_report(self)
+    }
+
+    fn send_report(&mut self) -> KeyboardResult<()> {
+        self.check_device_state()?;
+
+        let report = self.media_report();
+
+        if report.usage_id != self.last_media_report.usage_id {
             // replace the Ok(usize) with Ok(())
-            let ret = self.hid_class_mut().push_input(&report).map(|_| ());
-            self.last_report = self.report;
+            let ret = self
+                .push_input(HIDReportId::ConsumerControl, &report)
+                .map(|_| ());
+            self.observe_report(
+                HIDReportId::ConsumerControl,
+                HIDReport::MediaKeyboardReport(report),
+                &ret,
+            );
+            self.last_media_report = report;
 
-            ret
+            ret.map_err(KeyboardError::from)
         } else {
             Ok(())
         }
     }
 
     fn press(&mut self, key: u8) -> usize {
-        let mut done = false;
-        let is_media_key = is_media(key);
-
-        if is_media_key {
-            for keycode in self.report.keycodes.iter_mut() {
-                if *keycode == key {
-                    done = true;
-                    break;
-                }
+        // MediaKey::Zero is the "no usage" value of the report, and can't be pressed
+        if !is_media(key) || key == 0 {
+            return 0;
+        }
 
-                if *keycode == 0 {
-                    done = true;
-                    *keycode = key;
-                    break;
-                }
-            }
+        let usage = key as u16;
+        let held = &mut self.media_keys;
+
+        if held.contains(&usage) {
+            return 1;
+        }
+
+        if held[0] == 0 {
+            held[0] = usage;
+            return 1;
         }
 
-        (done && is_media_key) as usize
+        match self.consumer_rollover {
+            ConsumerRolloverPolicy::DropNewest => 0,
+            ConsumerRolloverPolicy::DropOldest => {
+                held[0] = usage;
+                1
+            }
+            ConsumerRolloverPolicy::Clamp => match held.iter_mut().find(|u| **u == 0) {
+                Some(slot) => {
+                    *slot = usage;
+                    1
+                }
+                None => 0,
+            },
+        }
     }
 
     fn release(&mut self, key: u8) -> usize {
         if is_media(key) {
-            // it's some other key:
-            // Test the key report to see if the key is present. Clear it if it exists.
-            // Check all positions in case the key is present more than once (which it shouldn't be)
-            for keycode in self.report.keycodes.iter_mut() {
-                if *keycode == key {
-                    *keycode = 0;
-                }
-            }
+            let usage = key as u16;
 
-            utils::sort_keycodes(self.report.keycodes.as_mut());
+            // Remove the usage, and shift the remaining held usages towards the report slot
+            if let Some(pos) = self.media_keys.iter().position(|&u| u == usage) {
+                self.media_keys.copy_within(pos + 1.., pos);
+                self.media_keys[MEDIA_MAX_HELD_KEYS - 1] = 0;
+            }
         }
 
         1
     }
 
     fn is_key_pressed(&self, key: u8) -> bool {
-        let mut found = false;
+        is_media_usage(self.media_report(), key)
+    }
 
-        for &keycode in self.report.keycodes.iter() {
-            if keycode == key {
-                found = true;
-                break;
-            }
+    fn was_key_pressed(&self, key: u8) -> bool {
+        is_media_usage(self.last_media_report, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{assert_send_gated, mock_usb_device, MockUsbBus};
+
+    const PLAY_PAUSE: u8 = MediaKey::PlayPause as u8;
+    const VOLUME_UP: u8 = MediaKey::VolumeIncrement as u8;
+    const VOLUME_DOWN: u8 = MediaKey::VolumeDecrement as u8;
+
+    // Presses play/pause and both volume keys, then releases play/pause and volume up.
+    fn assert_over_capacity_sequence(policy: ConsumerRolloverPolicy, expected: &[[u8; 2]]) {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = KeyboardBuilder::new()
+            .media()
+            .consumer_rollover(policy)
+            .build(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        assert_eq!(keyboard.consumer_rollover(), policy);
+
+        for key in [PLAY_PAUSE, VOLUME_UP, VOLUME_DOWN] {
+            MediaKeyboard::press(&mut keyboard, key);
+            MediaKeyboard::send_report(&mut keyboard).unwrap();
         }
 
-        found && is_media(key)
+        for key in [PLAY_PAUSE, VOLUME_UP] {
+            MediaKeyboard::release(&mut keyboard, key);
+            MediaKeyboard::send_report(&mut keyboard).unwrap();
+        }
+
+        MediaKeyboard::end(&mut keyboard).unwrap();
+
+        assert_eq!(usb_dev.bus().in_reports(), expected);
     }
 
-    fn was_key_pressed(&self, key: u8) -> bool {
-        let mut found = false;
+    #[test]
+    fn test_media_is_key_pressed() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_media(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
 
-        for &keycode in self.last_report.keycodes.iter() {
-            if keycode == key {
-                found = true;
-                break;
-            }
-        }
+        let zero = MediaKey::Zero as u8;
+
+        // an empty report holds no key, not a zero usage
+        assert!(!MediaKeyboard::is_key_pressed(&keyboard, zero));
+        assert!(!MediaKeyboard::was_key_pressed(&keyboard, zero));
+        assert_eq!(MediaKeyboard::press(&mut keyboard, zero), 0);
+
+        assert_eq!(MediaKeyboard::press(&mut keyboard, VOLUME_UP), 1);
+        assert!(MediaKeyboard::is_key_pressed(&keyboard, VOLUME_UP));
+        assert!(!MediaKeyboard::is_key_pressed(&keyboard, VOLUME_DOWN));
+        assert!(!MediaKeyboard::was_key_pressed(&keyboard, VOLUME_UP));
+
+        MediaKeyboard::send_report(&mut keyboard).unwrap();
+        assert!(MediaKeyboard::was_key_pressed(&keyboard, VOLUME_UP));
+        let usage_id = keyboard.last_media_report().usage_id;
+        assert_eq!(usage_id, u16::from(MediaKey::VolumeIncrement));
+
+        MediaKeyboard::release(&mut keyboard, VOLUME_UP);
+        assert!(!MediaKeyboard::is_key_pressed(&keyboard, VOLUME_UP));
+        assert!(!MediaKeyboard::is_key_pressed(&keyboard, zero));
+
+        assert_eq!(usb_dev.bus().in_reports(), [[0xe9, 0x00]]);
+    }
+
+    #[test]
+    fn test_media_send_report_single_push() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_media(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        MediaKeyboard::press(&mut keyboard, VOLUME_UP);
+        MediaKeyboard::send_report(&mut keyboard).unwrap();
+
+        // both usages change in one report
+        MediaKeyboard::release(&mut keyboard, VOLUME_UP);
+        MediaKeyboard::press(&mut keyboard, VOLUME_DOWN);
+        MediaKeyboard::send_report(&mut keyboard).unwrap();
+        MediaKeyboard::send_report(&mut keyboard).unwrap();
+
+        assert_eq!(usb_dev.bus().in_reports(), [[0xe9, 0x00], [0xea, 0x00]]);
+    }
+
+    #[test]
+    fn test_media_send_unconfigured() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_media(&alloc);
+
+        MediaKeyboard::press(&mut keyboard, VOLUME_UP);
+        assert_send_gated(
+            &alloc,
+            &mut keyboard,
+            |keyboard| MediaKeyboard::send_report(keyboard),
+            &[&[0xe9, 0x00]],
+        );
+    }
+
+    #[test]
+    fn test_media_rollover_drop_newest() {
+        assert_over_capacity_sequence(
+            ConsumerRolloverPolicy::DropNewest,
+            &[[0xcd, 0x00], [0x00, 0x00]],
+        );
+    }
+
+    #[test]
+    fn test_media_rollover_drop_oldest() {
+        assert_over_capacity_sequence(
+            ConsumerRolloverPolicy::DropOldest,
+            &[[0xcd, 0x00], [0xe9, 0x00], [0xea, 0x00], [0x00, 0x00]],
+        );
+    }
 
-        found && is_media(key)
+    #[test]
+    fn test_media_rollover_clamp() {
+        assert_over_capacity_sequence(
+            ConsumerRolloverPolicy::Clamp,
+            &[[0xcd, 0x00], [0xe9, 0x00], [0xea, 0x00], [0x00, 0x00]],
+        );
     }
 }
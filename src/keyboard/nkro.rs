@@ -1,10 +1,21 @@
-use usb_device::Result;
+use usbd_hid::descriptor::generator_prelude::*;
 use usbd_hid::hid_class::{
     HidClassSettings, HidProtocol, HidSubClass, ProtocolModeConfig,
 };
 
+use crate::error::KeyboardResult;
+use crate::hid_settings::{HIDReport, HIDReportId};
+
 use super::*;
 
+/// Number of bytes in the keycode bitmap of the [NKROReport].
+pub const NKRO_KEYCODES_LEN: usize = 28;
+
+/// Keycode bitmap of the [NKROReport], one bit per keycode from `0x00` to `0xDF`.
+pub type NKROKeycodes = [u8; NKRO_KEYCODES_LEN];
+
+pub(crate) const ZERO_NKRO_KEYS: NKROKeycodes = [0u8; NKRO_KEYCODES_LEN];
+
 pub const fn nkro_hid_class_settings() -> HidClassSettings {
     HidClassSettings {
         subclass: HidSubClass::NoSubClass,
@@ -14,9 +25,161 @@ pub const fn nkro_hid_class_settings() -> HidClassSettings {
     }
 }
 
+/// Report descriptor of an NKRO [Keyboard].
+///
+/// Follows the [NKROReport] layout, with the keycodes declared as a bitmap of the keycodes below
+/// [NKRO_CAPACITY], one bit per keycode. The modifiers and the 5-LED output report match the
+/// boot keyboard, so the host LED state can be read with [read_leds](Keyboard::read_leds) in
+/// both modes.
+#[rustfmt::skip]
+pub const NKRO_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x06, // Usage (Keyboard)
+    0xa1, 0x01, // Collection (Application)
+    0x05, 0x07, //   Usage Page (Keyboard)
+    0x19, 0xe0, //   Usage Minimum (Left Control)
+    0x29, 0xe7, //   Usage Maximum (Right GUI)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x08, //   Report Count (8)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x03, //   Input (Constant, Variable, Absolute)
+    0x05, 0x08, //   Usage Page (LEDs)
+    0x19, 0x01, //   Usage Minimum (Num Lock)
+    0x29, 0x05, //   Usage Maximum (Kana)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x05, //   Report Count (5)
+    0x91, 0x02, //   Output (Data, Variable, Absolute)
+    0x75, 0x03, //   Report Size (3)
+    0x95, 0x01, //   Report Count (1)
+    0x91, 0x03, //   Output (Constant, Variable, Absolute)
+    0x05, 0x07, //   Usage Page (Keyboard)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x29, 0xdf, //   Usage Maximum (0xdf)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0xe0, //   Report Count (224)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0xc0, // End Collection
+];
+
+/// Gets the report descriptor of an NKRO [Keyboard], see [NKRO_DESCRIPTOR].
+pub fn nkro_descriptor() -> &'static [u8] {
+    NKRO_DESCRIPTOR
+}
+
+/// Number of keycodes the NKRO bitmap can represent, one bit per keycode.
+///
+/// Every printable keycode, up to [LAST_PRINTABLE], fits in the bitmap.
+pub const NKRO_CAPACITY: u16 = (NKRO_KEYCODES_LEN * 8) as u16;
+
+const _: () = assert!((LAST_PRINTABLE as u16) < NKRO_CAPACITY);
+
+/// NKROReport describes the input report of an NKRO [Keyboard], see [NKRO_DESCRIPTOR].
+///
+/// Unlike the six keycodes of the [KeyboardReport], the keycodes are a bitmap, so any number of
+/// keys can be pressed at once. The LED state is read into the [KeyboardReport] of the keyboard.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NKROReport {
+    pub modifier: u8,
+    pub reserved: u8,
+    pub keycodes: NKROKeycodes,
+}
+
+impl SerializedDescriptor for NKROReport {
+    fn desc() -> &'static [u8] {
+        NKRO_DESCRIPTOR
+    }
+}
+
+impl Serialize for NKROReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_tuple(2 + NKRO_KEYCODES_LEN)?;
+        s.serialize_element(&self.modifier)?;
+        s.serialize_element(&self.reserved)?;
+        for key in self.keycodes.iter() {
+            s.serialize_element(key)?;
+        }
+        s.end()
+    }
+}
+
+impl AsInputReport for NKROReport {}
+
+impl<B: UsbBus> Keyboard<'_, B> {
+    /// Gets the number of keycodes the NKRO bitmap can represent, see [NKRO_CAPACITY].
+    pub const fn nkro_capacity(&self) -> u16 {
+        NKRO_CAPACITY
+    }
+
+    /// Gets the current keyboard report as an [NKROReport].
+    ///
+    /// The keycodes of an NKRO keyboard are kept in this bitmap, the keycodes of
+    /// [report](KeyboardOps::report) stay empty. On a boot keyboard, the bitmap is empty.
+    pub fn nkro_report(&self) -> NKROReport {
+        NKROReport {
+            modifier: self.report.modifier,
+            reserved: 0,
+            keycodes: self.nkro_keys,
+        }
+    }
+
+    /// Gets the last keyboard report as an [NKROReport], see [nkro_report](Self::nkro_report).
+    pub fn last_nkro_report(&self) -> NKROReport {
+        NKROReport {
+            modifier: self.last_report.modifier,
+            reserved: 0,
+            keycodes: self.last_nkro_keys,
+        }
+    }
+}
+
+// Gets whether the key is set in the NKRO bitmap, ignoring keys out of range of the bitmap.
+pub(crate) fn bitmap_contains(keycodes: &[u8], key: u8) -> bool {
+    match key_to_index_checked(key, keycodes.len()) {
+        Some(index) => keycodes[index] & key_to_printable_bitfield(key) != 0,
+        None => false,
+    }
+}
+
+// Gets an iterator over the keys set in the NKRO bitmap.
+pub(crate) fn bitmap_keys(keycodes: NKROKeycodes) -> impl Iterator<Item = u8> {
+    (0..NKRO_CAPACITY as usize)
+        .map(|key| key as u8)
+        .filter(move |&key| bitmap_contains(&keycodes, key))
+}
+
+/// Converts an NKRO bitmap report into a 6-key boot protocol report.
+///
+/// If more than six keys are pressed, every keycode is set to `ErrorRollOver`, as required by
+/// the HID specification.
+pub fn nkro_to_boot_report(report: &NKROReport) -> KeyboardReport {
+    let mut boot_report = KeyboardReport {
+        modifier: report.modifier,
+        reserved: 0,
+        leds: 0,
+        keycodes: ZERO_KEYS,
+    };
+
+    for (count, key) in bitmap_keys(report.keycodes).enumerate() {
+        if count == boot_report.keycodes.len() {
+            boot_report.keycodes = [KeyboardUsage::KeyboardErrorRollOver as u8; 6];
+            break;
+        }
+
+        boot_report.keycodes[count] = key;
+    }
+
+    boot_report
+}
+
 pub trait NKROKeyboard {
     /// End the keyboard reports.
-    fn end(&mut self) -> Result<()>;
+    fn end(&mut self) -> KeyboardResult<()>;
 
     /// Press a key, and add it to the current report.
     ///
@@ -54,10 +217,7 @@ pub trait NKROKeyboard {
     /// 1. A report with toggled-off non-modifiers removed.
     /// 2. A report with changes to modifiers.
     /// 3. A report with toggled-on non-modifiers added.
-    fn send_report(&mut self) -> Result<()>;
-
-    /// Sends a keyboard report without check report validity.
-    fn send_report_unchecked(&mut self) -> Result<usize>;
+    fn send_report(&mut self) -> KeyboardResult<()>;
 
     /// Gets whether the provided key is pressed in the current keyboard report.
     fn is_key_pressed(&self, key: u8) -> bool;
@@ -66,17 +226,31 @@ pub trait NKROKeyboard {
     fn was_key_pressed(&self, key: u8) -> bool;
 }
 
-impl NKROKeyboard for Keyboard<'_> {
-    fn end(&mut self) -> Result<()> {
+impl<B: UsbBus> Keyboard<'_, B> {
+    // Sends the last NKRO report to the host, and notifies the observer.
+    //
+    // `send_report` stages the intermediate reports in the last report, see
+    // `send_last_report` for the boot keyboard.
+    pub(crate) fn send_last_nkro_report(&mut self) -> KeyboardResult<()> {
+        let report = HIDReport::NKROKeyboard(self.last_nkro_report());
+        // replace the Ok(usize) with Ok(())
+        let ret = self.push_report(report).map(|_| ());
+        self.observe_report(HIDReportId::NKROKeyboard, report, &ret);
+
+        ret.map_err(KeyboardError::from)
+    }
+}
+
+impl<B: UsbBus> NKROKeyboard for Keyboard<'_, B> {
+    fn end(&mut self) -> KeyboardResult<()> {
         self.release_all();
-        self.send_report_unchecked()?;
-        Ok(())
+        NKROKeyboard::send_report(self)
     }
 
     fn press(&mut self, key: u8) -> usize {
         if is_printable(key) {
-            // If the key is in the range of printable keys
-            self.report.keycodes[key_to_index(key)] |= key_to_printable_bitfield(key);
+            // Every printable key fits in the bitmap, see `NKRO_CAPACITY`
+            self.nkro_keys[key_to_index(key)] |= key_to_printable_bitfield(key);
             1
         } else if is_modifier(key) {
             // It's a modifier key, convert key into bitfield
@@ -90,7 +264,7 @@ impl NKROKeyboard for Keyboard<'_> {
     fn release(&mut self, key: u8) -> usize {
         if is_printable(key) {
             // If we're releasing a printable key
-            self.report.keycodes[key_to_index(key)] &= !key_to_printable_bitfield(key);
+            self.nkro_keys[key_to_index(key)] &= !key_to_printable_bitfield(key);
             1
         } else if is_modifier(key) {
             // It's a modifier key
@@ -102,7 +276,20 @@ impl NKROKeyboard for Keyboard<'_> {
         }
     }
 
-    fn send_report(&mut self) -> Result<()> {
+    fn send_report(&mut self) -> KeyboardResult<()> {
+        self.check_device_state()?;
+
+        if self.protocol() == HidProtocolMode::Boot {
+            // The host only understands the boot protocol, send the bitmap as a 6-key report.
+            // A failed report is kept, and sent again by the next call.
+            if self.report_changed() {
+                self.push_boot_report()?;
+                self.commit_report();
+            }
+
+            return Ok(());
+        }
+
         let old_modifiers = self.last_report.modifier;
         let new_modifiers = self.report.modifier;
 
@@ -114,12 +301,7 @@ impl NKROKeyboard for Keyboard<'_> {
             // report, and send it to the host.
             let mut non_modifiers_toggled_off = false;
 
-            for (last_key, key) in self
-                .last_report
-                .keycodes
-                .iter_mut()
-                .zip(self.report.keycodes.iter())
-            {
+            for (last_key, key) in self.last_nkro_keys.iter_mut().zip(self.nkro_keys.iter()) {
                 let released_keycodes = *last_key & !key;
                 if released_keycodes != 0 {
                     *last_key &= !released_keycodes;
@@ -128,35 +310,380 @@ impl NKROKeyboard for Keyboard<'_> {
             }
 
             if non_modifiers_toggled_off {
-                self.send_report_unchecked()?;
+                self.send_last_nkro_report()?;
             }
 
             self.last_report.modifier = new_modifiers;
-            self.send_report_unchecked()?;
+            self.send_last_nkro_report()?;
         }
 
         if self.keycodes_changed() {
-            self.last_report
-                .keycodes
-                .copy_from_slice(self.report.keycodes.as_ref());
-            self.send_report_unchecked()?;
+            self.last_nkro_keys = self.nkro_keys;
+            self.send_last_nkro_report()?;
         }
 
-        Ok(())
-    }
+        self.commit_report();
 
-    fn send_report_unchecked(&mut self) -> Result<usize> {
-        let report = self.last_report.clone();
-        self.hid_class_mut().push_input(&report)
+        Ok(())
     }
 
     fn is_key_pressed(&self, key: u8) -> bool {
-        is_printable(key)
-            && self.report.keycodes[key_to_index(key)] & key_to_printable_bitfield(key) != 0
+        is_printable(key) && bitmap_contains(&self.nkro_keys, key)
     }
 
     fn was_key_pressed(&self, key: u8) -> bool {
-        is_printable(key)
-            && self.last_report.keycodes[key_to_index(key)] & key_to_printable_bitfield(key) != 0
+        is_printable(key) && bitmap_contains(&self.last_nkro_keys, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report_descriptor::validate_descriptor;
+    use crate::test::{
+        assert_nkro_report_sequence, assert_send_gated, mock_usb_device, MockUsbBus,
+    };
+
+    const KEY_A: u8 = KeyboardUsage::KeyboardAa as u8;
+
+    #[test]
+    fn test_nkro_descriptor_led_output() {
+        let descriptor = nkro_descriptor();
+        let (mut usage_page, mut report_size, mut report_count) = (0, 0, 0);
+        let mut led_outputs = 0;
+
+        // walk the short items, tracking the global state of each main item
+        let mut pos = 0;
+        while pos < descriptor.len() {
+            let (prefix, data) = (descriptor[pos], descriptor.get(pos + 1).copied());
+            match (prefix & 0xfc, data) {
+                (0x04, Some(page)) => usage_page = page,
+                (0x74, Some(size)) => report_size = size,
+                (0x94, Some(count)) => report_count = count,
+                // data (not constant padding) Output items on the LED usage page
+                (0x90, Some(flags)) if usage_page == 0x08 && flags & 0x01 == 0 => {
+                    led_outputs += report_size as usize * report_count as usize;
+                }
+                _ => (),
+            }
+            pos += 1 + [0, 1, 2, 4][(prefix & 0x03) as usize];
+        }
+
+        assert_eq!(led_outputs, 5);
+    }
+
+    #[test]
+    fn test_nkro_capacity() {
+        let descriptor = nkro_descriptor();
+        assert_eq!(validate_descriptor(descriptor), Ok(()));
+
+        let (mut usage_page, mut usage_max, mut report_size, mut report_count) = (0, 0, 0, 0);
+        let mut bitmap = None;
+
+        // walk the short items, finding the keycode bitmap input
+        let mut pos = 0;
+        while pos < descriptor.len() {
+            let (prefix, data) = (descriptor[pos], descriptor.get(pos + 1).copied());
+            match (prefix & 0xfc, data) {
+                (0x04, Some(page)) => usage_page = page,
+                (0x28, Some(max)) => usage_max = max,
+                (0x74, Some(size)) => report_size = size,
+                (0x94, Some(count)) => report_count = count,
+                // data variable Input items on the keyboard page, below the modifiers
+                (0x80, Some(flags)) if usage_page == 0x07 && flags & 0x03 == 0x02 => {
+                    if usage_max < 0xe0 {
+                        bitmap = Some((usage_max, report_size, report_count));
+                    }
+                }
+                _ => (),
+            }
+            pos += 1 + [0, 1, 2, 4][(prefix & 0x03) as usize];
+        }
+
+        let (usage_max, report_size, report_count) = bitmap.unwrap();
+        assert_eq!(usage_max as u16 + 1, NKRO_CAPACITY);
+        assert_eq!((report_size, report_count as u16), (1, NKRO_CAPACITY));
+
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+        assert_eq!(keyboard.nkro_capacity(), NKRO_CAPACITY);
+
+        // every printable key can be pressed at once
+        let printable = || (FIRST_PRINTABLE..=LAST_PRINTABLE).filter(|&key| is_printable(key));
+        for key in printable() {
+            assert_eq!(
+                NKROKeyboard::press(&mut keyboard, key),
+                1,
+                "key: {key:#04x}"
+            );
+        }
+        assert!(keyboard.pressed_keys().eq(printable()));
+        assert!(keyboard.report().keycodes.iter().all(|&key| key == 0));
+    }
+
+    // Gets an NKRO report with the modifiers and keys pressed.
+    fn nkro_report(modifier: u8, keys: &[u8]) -> NKROReport {
+        let mut keycodes = ZERO_NKRO_KEYS;
+        for &key in keys {
+            keycodes[key_to_index(key)] |= key_to_printable_bitfield(key);
+        }
+
+        NKROReport {
+            modifier,
+            reserved: 0,
+            keycodes,
+        }
+    }
+
+    // Gets the bytes of an NKRO report as sent on the IN endpoint.
+    fn nkro_report_bytes(modifier: u8, keys: &[u8]) -> [u8; 2 + NKRO_KEYCODES_LEN] {
+        let report = nkro_report(modifier, keys);
+        let mut bytes = [0u8; 2 + NKRO_KEYCODES_LEN];
+        bytes[0] = report.modifier;
+        bytes[2..].copy_from_slice(&report.keycodes);
+        bytes
+    }
+
+    #[test]
+    fn test_nkro_shifted_key_release_order() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let shift = KeyboardUsage::KeyboardLeftShift as u8;
+        let key_4 = KeyboardUsage::Keyboard4Dollar as u8;
+
+        for key in [shift, key_4] {
+            NKROKeyboard::press(&mut keyboard, key);
+        }
+        NKROKeyboard::send_report(&mut keyboard).unwrap();
+
+        for key in [shift, key_4] {
+            NKROKeyboard::release(&mut keyboard, key);
+        }
+        NKROKeyboard::send_report(&mut keyboard).unwrap();
+
+        // the `4` is released before the shift, so the host doesn't repeat a plain `4`
+        assert_nkro_report_sequence(
+            usb_dev.bus(),
+            &[
+                nkro_report(0x02, &[]),
+                nkro_report(0x02, &[key_4]),
+                nkro_report(0x02, &[]),
+                nkro_report(0x00, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_nkro_send_unconfigured() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+
+        NKROKeyboard::press(&mut keyboard, KEY_A);
+        assert_send_gated(
+            &alloc,
+            &mut keyboard,
+            |keyboard| NKROKeyboard::send_report(keyboard),
+            &[&nkro_report_bytes(0x00, &[KEY_A])],
+        );
+    }
+
+    #[test]
+    fn test_nkro_end() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        NKROKeyboard::press(&mut keyboard, KeyboardUsage::KeyboardLeftShift as u8);
+        NKROKeyboard::press(&mut keyboard, KEY_A);
+        NKROKeyboard::send_report(&mut keyboard).unwrap();
+        NKROKeyboard::end(&mut keyboard).unwrap();
+
+        // the key is released before the modifier, and the last report is the released report
+        assert!(reports_equal(keyboard.last_report(), keyboard.report()));
+        assert_eq!(keyboard.last_nkro_report(), keyboard.nkro_report());
+        assert!(keyboard.report_is_empty());
+        assert_nkro_report_sequence(
+            usb_dev.bus(),
+            &[
+                nkro_report(0x02, &[]),
+                nkro_report(0x02, &[KEY_A]),
+                nkro_report(0x02, &[]),
+                nkro_report(0x00, &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_nkro_end_unconfigured() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        NKROKeyboard::press(&mut keyboard, KEY_A);
+        NKROKeyboard::send_report(&mut keyboard).unwrap();
+
+        // the mock device was never configured by the host
+        keyboard.update_device_state(&usb_dev);
+        assert!(matches!(
+            NKROKeyboard::end(&mut keyboard),
+            Err(KeyboardError::NotConfigured)
+        ));
+        assert_eq!(usb_dev.bus().in_reports().len(), 1);
+    }
+
+    #[test]
+    fn test_push_boot_report_unconfigured() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+
+        NKROKeyboard::press(&mut keyboard, KEY_A);
+        assert_send_gated(
+            &alloc,
+            &mut keyboard,
+            |keyboard| keyboard.push_boot_report(),
+            &[&[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00]],
+        );
+    }
+
+    #[test]
+    fn test_push_boot_report_overflow() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        NKROKeyboard::press(&mut keyboard, KeyboardUsage::KeyboardLeftControl as u8);
+        for key in KEY_A..KEY_A + 8 {
+            NKROKeyboard::press(&mut keyboard, key);
+        }
+        keyboard.push_boot_report().unwrap();
+
+        // the modifiers are kept, only the keycodes overflow
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [[0x01, 0x00, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01]]
+        );
+        assert!(keyboard.report_changed());
+    }
+
+    #[test]
+    fn test_nkro_boot_protocol_rollover() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        keyboard.set_protocol(HidProtocolMode::Boot);
+
+        for key in KEY_A..KEY_A + 3 {
+            NKROKeyboard::press(&mut keyboard, key);
+        }
+        NKROKeyboard::send_report(&mut keyboard).unwrap();
+
+        for key in KEY_A + 3..KEY_A + 8 {
+            NKROKeyboard::press(&mut keyboard, key);
+        }
+        NKROKeyboard::send_report(&mut keyboard).unwrap();
+
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x00, 0x00, 0x04, 0x05, 0x06, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nkro_boot_protocol_send_error() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        keyboard.set_protocol(HidProtocolMode::Boot);
+        NKROKeyboard::press(&mut keyboard, KEY_A);
+
+        usb_dev.bus().fail_next_write(UsbError::InvalidEndpoint);
+        assert!(NKROKeyboard::send_report(&mut keyboard).is_err());
+        assert!(keyboard.report_changed());
+        assert!(usb_dev.bus().in_reports().is_empty());
+
+        // the failed report is sent again
+        NKROKeyboard::send_report(&mut keyboard).unwrap();
+        assert!(!keyboard.report_changed());
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00]]
+        );
+    }
+
+    #[test]
+    fn test_nkro_report_protocol_bitmap() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        for key in KEY_A..KEY_A + 8 {
+            NKROKeyboard::press(&mut keyboard, key);
+        }
+        NKROKeyboard::send_report(&mut keyboard).unwrap();
+
+        let mut bytes = [0u8; 2 + NKRO_KEYCODES_LEN];
+        bytes[2..4].copy_from_slice(&[0xf0, 0x0f]);
+        assert_eq!(usb_dev.bus().in_reports(), [bytes]);
+    }
+
+    #[test]
+    fn test_nkro_inject_report() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        // the boot array keycodes are pressed in the bitmap
+        let key_f12 = KeyboardUsage::KeyboardF12 as u8;
+        keyboard
+            .inject_report(KeyboardReport {
+                modifier: 0x02,
+                reserved: 0,
+                leds: 0,
+                keycodes: [key_f12, KEY_A, 0x00, 0x00, 0x00, 0x00],
+            })
+            .unwrap();
+
+        assert!(keyboard.pressed_keys().eq([KEY_A, key_f12]));
+        assert_eq!(keyboard.report().keycodes, ZERO_KEYS);
+        assert!(!keyboard.report_changed());
+        assert_nkro_report_sequence(usb_dev.bus(), &[nkro_report(0x02, &[KEY_A, key_f12])]);
+    }
+
+    #[test]
+    fn test_nkro_keys_past_right_bracket() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_nkro(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let keys = [
+            KeyboardUsage::KeyboardF12 as u8,
+            KeyboardUsage::KeyboardUpArrow as u8,
+            KeyboardUsage::KeypadHexadecimal as u8,
+        ];
+        for key in keys {
+            assert_eq!(NKROKeyboard::press(&mut keyboard, key), 1);
+            assert!(NKROKeyboard::is_key_pressed(&keyboard, key));
+        }
+        NKROKeyboard::send_report(&mut keyboard).unwrap();
+
+        for key in keys {
+            assert!(NKROKeyboard::was_key_pressed(&keyboard, key));
+            assert_eq!(NKROKeyboard::release(&mut keyboard, key), 1);
+        }
+        NKROKeyboard::send_report(&mut keyboard).unwrap();
+
+        assert!(keyboard.report_is_empty());
+        assert_nkro_report_sequence(
+            usb_dev.bus(),
+            &[nkro_report(0x00, &keys), nkro_report(0x00, &[])],
+        );
     }
 }
@@ -0,0 +1,144 @@
+use usb_device::bus::UsbBus;
+use usb_device::Result;
+use usbd_hid::hid_class::{
+    HIDClass, HidClassSettings, HidCountryCode, HidProtocol, HidSubClass, ProtocolModeConfig,
+};
+
+use crate::hid_settings::HIDReportId;
+use crate::HIDReportObserver;
+
+use super::{KeyboardUsbBus, KeyboardUsbBusAllocator, HID_ENDPOINT_SIZE, POLL_MS};
+
+/// Size of the RawHID input and output reports.
+pub const RAW_HID_REPORT_LEN: usize = 64;
+
+const _: () = assert!(RAW_HID_REPORT_LEN <= HID_ENDPOINT_SIZE as usize);
+
+/// Report descriptor for a vendor-defined RawHID interface.
+///
+/// Uses the same usage page and usages as QMK, so existing host-side configuration tools can
+/// find the interface.
+pub const RAW_HID_DESCRIPTOR: [u8; 34] = [
+    0x06, 0x60, 0xff, // Usage Page (Vendor Defined 0xFF60)
+    0x09, 0x61, // Usage (0x61)
+    0xa1, 0x01, // Collection (Application)
+    0x09, 0x62, //   Usage (0x62)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xff, 0x00, //   Logical Maximum (255)
+    0x95, 0x40, //   Report Count (64)
+    0x75, 0x08, //   Report Size (8)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0x09, 0x63, //   Usage (0x63)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xff, 0x00, //   Logical Maximum (255)
+    0x95, 0x40, //   Report Count (64)
+    0x75, 0x08, //   Report Size (8)
+    0x91, 0x02, //   Output (Data, Variable, Absolute)
+    0xc0, // End Collection
+];
+
+pub const fn raw_hid_class_settings() -> HidClassSettings {
+    HidClassSettings {
+        subclass: HidSubClass::NoSubClass,
+        protocol: HidProtocol::Generic,
+        config: ProtocolModeConfig::DefaultBehavior,
+        locale: HidCountryCode::NotSupported,
+    }
+}
+
+/// Bidirectional vendor-defined HID interface for exchanging raw reports with the host.
+pub struct RawHid<'r, B: UsbBus = KeyboardUsbBus> {
+    hid_class: HIDClass<'r, B>,
+    observer: HIDReportObserver,
+}
+
+impl<'r, B: UsbBus> RawHid<'r, B> {
+    /// Creates a new [RawHid] device.
+    pub fn new(bus: &'r KeyboardUsbBusAllocator<B>) -> Self {
+        Self::new_with_observer(bus, HIDReportObserver::default())
+    }
+
+    /// Creates a new [RawHid] device.
+    ///
+    /// Allows setting a custom [HIDReportObserver] implementation for firing a callback function
+    /// on received output reports.
+    pub fn new_with_observer(
+        bus: &'r KeyboardUsbBusAllocator<B>,
+        observer: HIDReportObserver,
+    ) -> Self {
+        let hid_class = HIDClass::new_with_settings(
+            bus,
+            &RAW_HID_DESCRIPTOR,
+            POLL_MS,
+            raw_hid_class_settings(),
+        );
+
+        Self {
+            hid_class,
+            observer,
+        }
+    }
+
+    /// Gets a reference to the [HIDClass] for the USB bus.
+    pub fn hid_class(&self) -> &HIDClass<'r, B> {
+        &self.hid_class
+    }
+
+    /// Gets a mutable reference to the [HIDClass] for the USB bus.
+    pub fn hid_class_mut(&mut self) -> &mut HIDClass<'r, B> {
+        &mut self.hid_class
+    }
+
+    /// Sends a raw input report to the host.
+    pub fn send(&mut self, data: &[u8; RAW_HID_REPORT_LEN]) -> Result<()> {
+        // replace the Ok(usize) with Ok(())
+        self.hid_class.push_raw_input(data.as_ref()).map(|_| ())
+    }
+
+    /// Receives a raw output report from the host, if one is available.
+    ///
+    /// Short reports are zero-padded to [RAW_HID_REPORT_LEN] bytes. The received bytes are
+    /// passed to the [OutputReportHook](crate::OutputReportHook) with [HIDReportId::RawHID].
+    pub fn receive(&mut self) -> Option<[u8; RAW_HID_REPORT_LEN]> {
+        let mut data = [0u8; RAW_HID_REPORT_LEN];
+
+        let len = self.hid_class.pull_raw_output(data.as_mut()).ok()?;
+        self.observer
+            .observe_output_report(HIDReportId::RawHID, &data[..len]);
+
+        Some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use usb_device::bus::UsbBusAllocator;
+
+    #[test]
+    fn test_raw_hid_round_trip() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut raw_hid = RawHid::new(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let mut data = [0u8; RAW_HID_REPORT_LEN];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        raw_hid.send(&data).unwrap();
+        assert_eq!(usb_dev.bus().in_reports(), [data]);
+
+        assert_eq!(raw_hid.receive(), None);
+
+        usb_dev.bus().queue_out(1, data.as_ref());
+        assert_eq!(raw_hid.receive(), Some(data));
+        assert_eq!(raw_hid.receive(), None);
+
+        usb_dev.bus().queue_out(1, &[0xaa, 0x55]);
+        let mut expected = [0u8; RAW_HID_REPORT_LEN];
+        expected[..2].copy_from_slice(&[0xaa, 0x55]);
+        assert_eq!(raw_hid.receive(), Some(expected));
+    }
+}
@@ -0,0 +1,78 @@
+use super::report_queue::ReportQueue;
+
+/// Number of sent reports kept by the [Keyboard](super::Keyboard), see
+/// [recent_reports](super::Keyboard::recent_reports).
+pub const RECENT_REPORTS_LEN: usize = 16;
+
+/// Fixed-capacity ring buffer of the most recent HID reports, e.g. for dumping the reports
+/// leading up to a phantom keypress.
+///
+/// Once full, pushing a report drops the oldest one.
+pub struct ReportBuffer<T: Copy, const N: usize> {
+    reports: ReportQueue<T, N>,
+}
+
+impl<T: Copy, const N: usize> ReportBuffer<T, N> {
+    /// Creates a new, empty [ReportBuffer].
+    pub const fn new() -> Self {
+        Self {
+            reports: ReportQueue::new(),
+        }
+    }
+
+    /// Gets the number of buffered reports.
+    pub const fn len(&self) -> usize {
+        self.reports.len()
+    }
+
+    /// Gets whether the buffer is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.reports.is_empty()
+    }
+
+    /// Adds a report to the buffer, dropping the oldest report if the buffer is full.
+    pub fn push(&mut self, report: T) {
+        if self.reports.is_full() {
+            self.reports.pop_front();
+        }
+        // a zero-capacity buffer keeps nothing
+        let _ = self.reports.push_back(report);
+    }
+
+    /// Gets an iterator over the buffered reports, from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.reports.iter()
+    }
+
+    /// Removes all buffered reports.
+    pub fn clear(&mut self) {
+        self.reports.clear();
+    }
+}
+
+impl<T: Copy, const N: usize> Default for ReportBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_buffer_keeps_latest() {
+        let mut buffer: ReportBuffer<u8, 3> = ReportBuffer::new();
+        assert!(buffer.is_empty());
+
+        for report in 0..5 {
+            buffer.push(report);
+        }
+
+        assert_eq!(buffer.len(), 3);
+        assert!(buffer.iter().copied().eq([2, 3, 4]));
+
+        buffer.clear();
+        assert_eq!(buffer.iter().count(), 0);
+    }
+}
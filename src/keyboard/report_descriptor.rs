@@ -0,0 +1,169 @@
+use usb_device::bus::UsbBus;
+use usbd_hid::hid_class::{HIDClass, HidClassSettings};
+
+use super::{Keyboard, KeyboardUsbBusAllocator, POLL_MS};
+
+// Item tags with the size bits masked off, see the HID 1.11 specification section 6.2.2.
+const TAG_INPUT: u8 = 0x80;
+const TAG_OUTPUT: u8 = 0x90;
+const TAG_FEATURE: u8 = 0xb0;
+const TAG_COLLECTION: u8 = 0xa0;
+const TAG_END_COLLECTION: u8 = 0xc0;
+const TAG_REPORT_SIZE: u8 = 0x74;
+const TAG_REPORT_COUNT: u8 = 0x94;
+const LONG_ITEM: u8 = 0xfe;
+
+/// Errors found when validating a HID report descriptor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DescriptorError {
+    /// An item runs past the end of the descriptor, at the offset of the item.
+    Truncated(usize),
+    /// An End Collection item has no matching Collection item, at the offset of the item.
+    UnbalancedCollection(usize),
+    /// A Collection item is never closed.
+    UnclosedCollection,
+    /// A main data item comes before any Report Size item, at the offset of the item.
+    MissingReportSize(usize),
+    /// A main data item comes before any Report Count item, at the offset of the item.
+    MissingReportCount(usize),
+}
+
+/// Walks the items of a HID report descriptor, and checks its basic structure.
+///
+/// Collections must be balanced, and Report Size and Report Count must be set before the first
+/// Input, Output, or Feature item. The values of the items are not checked.
+pub const fn validate_descriptor(descriptor: &[u8]) -> Result<(), DescriptorError> {
+    let mut pos = 0;
+    let mut depth = 0usize;
+    let mut report_size = false;
+    let mut report_count = false;
+
+    while pos < descriptor.len() {
+        let prefix = descriptor[pos];
+        let (tag, header, size) = if prefix == LONG_ITEM {
+            if pos + 1 >= descriptor.len() {
+                return Err(DescriptorError::Truncated(pos));
+            }
+            // long items have a data size byte and a tag byte after the prefix
+            (LONG_ITEM, 3, descriptor[pos + 1] as usize)
+        } else {
+            let size = match prefix & 0x03 {
+                3 => 4,
+                size => size as usize,
+            };
+            (prefix & 0xfc, 1, size)
+        };
+
+        if pos + header + size > descriptor.len() {
+            return Err(DescriptorError::Truncated(pos));
+        }
+
+        match tag {
+            TAG_COLLECTION => depth += 1,
+            TAG_END_COLLECTION => {
+                if depth == 0 {
+                    return Err(DescriptorError::UnbalancedCollection(pos));
+                }
+                depth -= 1;
+            }
+            TAG_INPUT | TAG_OUTPUT | TAG_FEATURE => {
+                if !report_size {
+                    return Err(DescriptorError::MissingReportSize(pos));
+                }
+                if !report_count {
+                    return Err(DescriptorError::MissingReportCount(pos));
+                }
+            }
+            TAG_REPORT_SIZE => report_size = true,
+            TAG_REPORT_COUNT => report_count = true,
+            _ => (),
+        }
+
+        pos += header + size;
+    }
+
+    if depth == 0 {
+        Ok(())
+    } else {
+        Err(DescriptorError::UnclosedCollection)
+    }
+}
+
+impl<'k, B: UsbBus> Keyboard<'k, B> {
+    /// Creates a new [Keyboard] device with a custom report descriptor.
+    ///
+    /// In debug builds, the descriptor is checked with [validate_descriptor], and a malformed
+    /// descriptor panics. Prefer this over [new_with_hid_class](Self::new_with_hid_class) for
+    /// custom descriptors, since the descriptor can't be read back from the [HIDClass].
+    pub fn new_with_descriptor(
+        bus: &'k KeyboardUsbBusAllocator<B>,
+        descriptor: &'static [u8],
+        settings: HidClassSettings,
+    ) -> Self {
+        if cfg!(debug_assertions) {
+            if let Err(err) = validate_descriptor(descriptor) {
+                panic!("malformed report descriptor: {err:?}");
+            }
+        }
+
+        Self::new_with_hid_class(HIDClass::new_with_settings(
+            bus, descriptor, POLL_MS, settings,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usb_device::bus::UsbBusAllocator;
+    use usbd_hid::descriptor::{KeyboardReport, SerializedDescriptor};
+    use usbd_hid::hid_class::HidProtocol;
+
+    use super::*;
+    use crate::boot::boot_hid_class_settings;
+    use crate::composite::COMPOSITE_DESCRIPTOR;
+    use crate::raw_hid::RAW_HID_DESCRIPTOR;
+    use crate::test::MockUsbBus;
+
+    #[test]
+    fn test_validate_descriptor() {
+        assert_eq!(validate_descriptor(KeyboardReport::desc()), Ok(()));
+        assert_eq!(validate_descriptor(COMPOSITE_DESCRIPTOR), Ok(()));
+        assert_eq!(validate_descriptor(&RAW_HID_DESCRIPTOR), Ok(()));
+
+        let truncated = &COMPOSITE_DESCRIPTOR[..COMPOSITE_DESCRIPTOR.len() - 1];
+        assert_eq!(
+            validate_descriptor(truncated),
+            Err(DescriptorError::UnclosedCollection)
+        );
+
+        // Logical Maximum with its second data byte cut off
+        let truncated = &[0x05, 0x01, 0x26, 0xff];
+        assert_eq!(
+            validate_descriptor(truncated),
+            Err(DescriptorError::Truncated(2))
+        );
+
+        assert_eq!(
+            validate_descriptor(&[0xa1, 0x01, 0xc0, 0xc0]),
+            Err(DescriptorError::UnbalancedCollection(3))
+        );
+        assert_eq!(
+            validate_descriptor(&[0x75, 0x08, 0x81, 0x02]),
+            Err(DescriptorError::MissingReportCount(2))
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "malformed report descriptor")]
+    fn test_new_with_descriptor_rejects_truncated() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let truncated = &COMPOSITE_DESCRIPTOR[..COMPOSITE_DESCRIPTOR.len() - 1];
+
+        Keyboard::new_with_descriptor(
+            &alloc,
+            truncated,
+            boot_hid_class_settings(HidProtocol::Keyboard),
+        );
+    }
+}
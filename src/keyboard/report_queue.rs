@@ -0,0 +1,106 @@
+/// Fixed-capacity FIFO queue of HID reports waiting to be sent to the host.
+pub struct ReportQueue<T: Copy, const N: usize> {
+    reports: [Option<T>; N],
+    head: usize,
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> ReportQueue<T, N> {
+    /// Creates a new, empty [ReportQueue].
+    pub const fn new() -> Self {
+        Self {
+            reports: [None; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Gets the number of queued reports.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Gets whether the queue is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets whether the queue is full.
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Gets the oldest queued report, without removing it from the queue.
+    pub fn front(&self) -> Option<&T> {
+        if self.is_empty() {
+            None
+        } else {
+            self.reports[self.head].as_ref()
+        }
+    }
+
+    /// Adds a report to the back of the queue.
+    ///
+    /// Returns the report as the error if the queue is full.
+    pub fn push_back(&mut self, report: T) -> Result<(), T> {
+        if self.is_full() {
+            Err(report)
+        } else {
+            self.reports[(self.head + self.len) % N] = Some(report);
+            self.len += 1;
+            Ok(())
+        }
+    }
+
+    /// Removes the oldest report from the front of the queue.
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            let report = self.reports[self.head].take();
+            self.head = (self.head + 1) % N;
+            self.len -= 1;
+            report
+        }
+    }
+
+    /// Gets an iterator over the queued reports, from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len).filter_map(move |i| self.reports[(self.head + i) % N].as_ref())
+    }
+
+    /// Removes all queued reports.
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+impl<T: Copy, const N: usize> Default for ReportQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_queue_wraps() {
+        let mut queue: ReportQueue<u8, 2> = ReportQueue::new();
+
+        assert_eq!(queue.pop_front(), None);
+
+        for i in 0..4 {
+            assert_eq!(queue.push_back(i), Ok(()));
+            assert_eq!(queue.push_back(i + 1), Ok(()));
+            assert_eq!(queue.push_back(i + 2), Err(i + 2));
+            assert!(queue.is_full());
+
+            assert_eq!(queue.front(), Some(&i));
+            assert_eq!(queue.pop_front(), Some(i));
+            assert_eq!(queue.pop_front(), Some(i + 1));
+            assert!(queue.is_empty());
+        }
+    }
+}
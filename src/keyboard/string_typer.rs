@@ -0,0 +1,242 @@
+use usbd_hid::descriptor::{KeyboardReport, KeyboardUsage};
+
+use crate::error::KeyboardResult;
+
+use super::{key_to_modifier_bitfield, KeyboardOps, ZERO_KEYS};
+
+const LEFT_SHIFT: u8 = KeyboardUsage::KeyboardLeftShift as u8;
+
+/// Gets the keycode typing the ASCII character on a US layout, and whether shift is held.
+///
+/// Returns `None` for characters without a key, e.g. control characters other than tab and
+/// newline.
+pub const fn ascii_to_key(ch: u8) -> Option<(u8, bool)> {
+    let key = match ch {
+        b'a'..=b'z' => (KeyboardUsage::KeyboardAa as u8 + ch - b'a', false),
+        b'A'..=b'Z' => (KeyboardUsage::KeyboardAa as u8 + ch - b'A', true),
+        b'1'..=b'9' => (KeyboardUsage::Keyboard1Exclamation as u8 + ch - b'1', false),
+        b'0' => (KeyboardUsage::Keyboard0CloseParens as u8, false),
+        b'!' => (KeyboardUsage::Keyboard1Exclamation as u8, true),
+        b'@' => (KeyboardUsage::Keyboard2At as u8, true),
+        b'#' => (KeyboardUsage::Keyboard3Hash as u8, true),
+        b'$' => (KeyboardUsage::Keyboard4Dollar as u8, true),
+        b'%' => (KeyboardUsage::Keyboard5Percent as u8, true),
+        b'^' => (KeyboardUsage::Keyboard6Caret as u8, true),
+        b'&' => (KeyboardUsage::Keyboard7Ampersand as u8, true),
+        b'*' => (KeyboardUsage::Keyboard8Asterisk as u8, true),
+        b'(' => (KeyboardUsage::Keyboard9OpenParens as u8, true),
+        b')' => (KeyboardUsage::Keyboard0CloseParens as u8, true),
+        b'\n' => (KeyboardUsage::KeyboardEnter as u8, false),
+        b'\t' => (KeyboardUsage::KeyboardTab as u8, false),
+        b' ' => (KeyboardUsage::KeyboardSpacebar as u8, false),
+        b'-' => (KeyboardUsage::KeyboardDashUnderscore as u8, false),
+        b'_' => (KeyboardUsage::KeyboardDashUnderscore as u8, true),
+        b'=' => (KeyboardUsage::KeyboardEqualPlus as u8, false),
+        b'+' => (KeyboardUsage::KeyboardEqualPlus as u8, true),
+        b'[' => (KeyboardUsage::KeyboardOpenBracketBrace as u8, false),
+        b'{' => (KeyboardUsage::KeyboardOpenBracketBrace as u8, true),
+        b']' => (KeyboardUsage::KeyboardCloseBracketBrace as u8, false),
+        b'}' => (KeyboardUsage::KeyboardCloseBracketBrace as u8, true),
+        b'\\' => (KeyboardUsage::KeyboardBackslashBar as u8, false),
+        b'|' => (KeyboardUsage::KeyboardBackslashBar as u8, true),
+        b';' => (KeyboardUsage::KeyboardSemiColon as u8, false),
+        b':' => (KeyboardUsage::KeyboardSemiColon as u8, true),
+        b'\'' => (KeyboardUsage::KeyboardSingleDoubleQuote as u8, false),
+        b'"' => (KeyboardUsage::KeyboardSingleDoubleQuote as u8, true),
+        b'`' => (KeyboardUsage::KeyboardBacktickTilde as u8, false),
+        b'~' => (KeyboardUsage::KeyboardBacktickTilde as u8, true),
+        b',' => (KeyboardUsage::KeyboardCommaLess as u8, false),
+        b'<' => (KeyboardUsage::KeyboardCommaLess as u8, true),
+        b'.' => (KeyboardUsage::KeyboardPeriodGreater as u8, false),
+        b'>' => (KeyboardUsage::KeyboardPeriodGreater as u8, true),
+        b'/' => (KeyboardUsage::KeyboardSlashQuestion as u8, false),
+        b'?' => (KeyboardUsage::KeyboardSlashQuestion as u8, true),
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+/// Incrementally types a string, one report per step, without allocating.
+///
+/// Each character is typed as a press report followed by a release report, so repeated
+/// characters are seen as separate key presses. Characters without a key on the US layout are
+/// skipped, see [ascii_to_key].
+///
+/// Call [tick](Self::tick) from the main loop to type on a keyboard, or iterate over the
+/// [StringTyper] to get the boot protocol reports directly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StringTyper<'s> {
+    text: &'s [u8],
+    pos: usize,
+    // Key and shift state of the pressed character, released by the next step.
+    pressed: Option<(u8, bool)>,
+    // Whether the left shift of the pressed character was added by the typer.
+    added_shift: bool,
+}
+
+impl<'s> StringTyper<'s> {
+    /// Creates a new [StringTyper] for the string.
+    pub const fn new(text: &'s str) -> Self {
+        Self {
+            text: text.as_bytes(),
+            pos: 0,
+            pressed: None,
+            added_shift: false,
+        }
+    }
+
+    /// Gets whether every character of the string has been typed and released.
+    pub fn is_done(&self) -> bool {
+        self.pressed.is_none()
+            && self.text[self.pos..]
+                .iter()
+                .all(|&c| ascii_to_key(c).is_none())
+    }
+
+    // Advances to the next step, returning the key and shift state, and whether it is pressed.
+    fn step(&mut self) -> Option<(u8, bool, bool)> {
+        if let Some((key, shift)) = self.pressed.take() {
+            return Some((key, shift, false));
+        }
+
+        while let Some(&ch) = self.text.get(self.pos) {
+            self.pos += 1;
+
+            if let Some((key, shift)) = ascii_to_key(ch) {
+                self.pressed = Some((key, shift));
+                return Some((key, shift, true));
+            }
+        }
+
+        None
+    }
+
+    /// Types the next step of the string on the keyboard, and sends the keyboard report.
+    ///
+    /// Presses or releases the current character, holding shift with the key if needed. Other
+    /// keys held on the keyboard are left alone, including a left shift that was already held.
+    ///
+    /// Returns false once the string is exhausted, without sending a report.
+    pub fn tick<K: KeyboardOps>(&mut self, keyboard: &mut K) -> KeyboardResult<bool> {
+        let shift_bit = key_to_modifier_bitfield(LEFT_SHIFT);
+
+        match self.step() {
+            Some((key, shift, true)) => {
+                self.added_shift = shift && keyboard.modifiers() & shift_bit == 0;
+                if self.added_shift {
+                    keyboard.add_modifiers(shift_bit);
+                }
+                keyboard.press(key);
+            }
+            Some((key, _, false)) => {
+                keyboard.release(key);
+                if self.added_shift {
+                    keyboard.remove_modifiers(shift_bit);
+                    self.added_shift = false;
+                }
+            }
+            None => return Ok(false),
+        }
+
+        keyboard.send_report()?;
+
+        Ok(true)
+    }
+}
+
+impl Iterator for StringTyper<'_> {
+    type Item = KeyboardReport;
+
+    /// Gets the next boot protocol report typing the string.
+    ///
+    /// Returns `None` when the string is exhausted.
+    fn next(&mut self) -> Option<KeyboardReport> {
+        let (key, shift, pressed) = self.step()?;
+        let mut report = KeyboardReport {
+            modifier: 0,
+            reserved: 0,
+            leds: 0,
+            keycodes: ZERO_KEYS,
+        };
+
+        if pressed {
+            if shift {
+                report.modifier = key_to_modifier_bitfield(LEFT_SHIFT);
+            }
+            report.keycodes[0] = key;
+        }
+
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use usb_device::bus::UsbBusAllocator;
+
+    use super::*;
+    use crate::test::{mock_usb_device, MockUsbBus};
+    use crate::{Keyboard, Keycodes};
+
+    #[test]
+    fn test_string_typer_reports() {
+        let reports: std::vec::Vec<(u8, Keycodes)> = StringTyper::new("ab")
+            .map(|r| (r.modifier, r.keycodes))
+            .collect();
+
+        assert_eq!(
+            reports,
+            [
+                (0x00, [0x04, 0x00, 0x00, 0x00, 0x00, 0x00]),
+                (0x00, ZERO_KEYS),
+                (0x00, [0x05, 0x00, 0x00, 0x00, 0x00, 0x00]),
+                (0x00, ZERO_KEYS),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_typer_tick() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+        let mut typer = StringTyper::new("A\u{e9}a");
+
+        while typer.tick(&mut keyboard).unwrap() {}
+        assert!(typer.is_done());
+
+        // the boot keyboard sends the shift separately from the key, the unmapped character is
+        // skipped, and the repeated key is released in between
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+                [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_typer_held_shift() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_boot(&alloc);
+        let _usb_dev = mock_usb_device(&alloc);
+        let mut typer = StringTyper::new("A");
+
+        KeyboardOps::press(&mut keyboard, LEFT_SHIFT);
+        while typer.tick(&mut keyboard).unwrap() {}
+
+        // the shift held before typing is not released by the typer
+        assert_eq!(
+            KeyboardOps::modifiers(&keyboard),
+            key_to_modifier_bitfield(LEFT_SHIFT)
+        );
+    }
+}
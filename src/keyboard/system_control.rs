@@ -1,8 +1,10 @@
-use usb_device::Result;
 use usbd_hid::hid_class::{
     HidClassSettings, HidProtocol, HidSubClass, ProtocolModeConfig,
 };
 
+use crate::error::{KeyboardError, KeyboardResult};
+use crate::hid_settings::{HIDReport, HIDReportId};
+
 use super::*;
 
 pub const fn system_control_hid_class_settings() -> HidClassSettings {
@@ -15,34 +17,18 @@ pub const fn system_control_hid_class_settings() -> HidClassSettings {
 }
 
 pub trait SystemControlKeyboard {
-    /// End the keyboard reports.
-    fn end(&mut self) -> Result<()>;
-
-    /// Sending the current HID report to the host:
-    ///
-    /// Depending on the differences between the current and previous HID reports, we
-    /// might need to send one or two extra reports to guarantee that the host will
-    /// process the changes in the correct order. There are two important scenarios
-    /// to consider:
+    /// End the system control reports, releasing and sending the held usage.
     ///
-    /// 1. If a non-modifier keycode toggles off in the same report as a modifier
-    /// changes, the host might process the modifier change first. For example, if
-    /// both `shift` and `4` toggle off in the same report (most likely from a
-    /// `LSHIFT(Key_4)` key being released), and that key has been held long enough
-    /// to trigger character repeat, we could end up with a plain `4` in the output
-    /// at the end of the repeat: `$$$$4` instead of `$$$$$`.
-    ///
-    /// 2. If a non-modifier keycode toggles on in the same report as a modifier
-    /// changes, the host might process the non-modifer first. For example, pressing
-    /// and holding an `LSHIFT(Key_4)` key might result in `4$$$` rather than `$$$$`.
-    ///
-    /// Therefore, each call to `sendReport()` must send (up to) three reports to the
-    /// host to guarantee the correct order of processing:
+    /// The keyboard keys of a composite keyboard are left alone.
+    fn end(&mut self) -> KeyboardResult<()>;
+
+    /// Sends the current system control report to the host, if the reported usage changed.
     ///
-    /// 1. A report with toggled-off non-modifiers removed.
-    /// 2. A report with changes to modifiers.
-    /// 3. A report with toggled-on non-modifiers added.
-    fn send_report(&mut self) -> Result<()>;
+    /// Unlike the keyboard reports, the [SystemControlReport] has no modifiers, and carries a
+    /// single usage as an array item. Every change, including one usage replacing another, is
+    /// sent as one report: the host treats a usage missing from the array as released, before
+    /// handling the newly reported usage. No intermediate release report is sent.
+    fn send_report(&mut self) -> KeyboardResult<()>;
 
     /// Press a key, and add it to the current report.
     ///
@@ -63,87 +49,127 @@ pub trait SystemControlKeyboard {
     fn was_key_pressed(&self, key: u8) -> bool;
 }
 
-impl SystemControlKeyboard for Keyboard<'_> {
-    fn end(&mut self) -> Result<()> {
-        self.release_all();
-        self.send_report()
+impl<B: UsbBus> Keyboard<'_, B> {
+    /// Gets the current system control report.
+    pub fn system_control_report(&self) -> SystemControlReport {
+        self.system_control_report
     }
 
-    fn send_report(&mut self) -> Result<()> {
-        if self.keycodes_changed() {
-            let report = self.report().clone();
-            // replace the Ok(usize) with Ok(())
-            let ret = self.hid_class_mut().push_input(&report).map(|_| ());
-            self.last_report = self.report;
+    /// Gets the previous system control report.
+    pub fn last_system_control_report(&self) -> SystemControlReport {
+        self.last_system_control_report
+    }
+}
+
+impl<B: UsbBus> SystemControlKeyboard for Keyboard<'_, B> {
+    fn end(&mut self) -> KeyboardResult<()> {
+        self.system_control_report.usage_id = 0;
+        SystemControlKeyboard::send_report(self)
+    }
+
+    fn send_report(&mut self) -> KeyboardResult<()> {
+        self.check_device_state()?;
 
-            ret
+        let report = self.system_control_report;
+
+        if report.usage_id != self.last_system_control_report.usage_id {
+            // replace the Ok(usize) with Ok(())
+            let ret = self
+                .push_input(HIDReportId::SystemControl, &report)
+                .map(|_| ());
+            self.observe_report(
+                HIDReportId::SystemControl,
+                HIDReport::SystemControl(report),
+                &ret,
+            );
+            self.last_system_control_report = report;
+
+            ret.map_err(KeyboardError::from)
         } else {
             Ok(())
         }
     }
 
     fn press(&mut self, key: u8) -> usize {
-        let mut done = false;
-        let is_system_control_key = is_system_control(key);
-
-        if is_system_control_key {
-            for keycode in self.report.keycodes.iter_mut() {
-                if *keycode == key {
-                    done = true;
-                    break;
-                }
-
-                if *keycode == 0 {
-                    done = true;
-                    *keycode = key;
-                    break;
-                }
-            }
-        }
+        // The system control report holds a single usage, keep the held usage if another is pressed
+        let usage_id = &mut self.system_control_report.usage_id;
+        let done = *usage_id == 0 || *usage_id == key;
 
-        (done && is_system_control_key) as usize
+        if is_system_control(key) && done {
+            *usage_id = key;
+            1
+        } else {
+            0
+        }
     }
 
     fn release(&mut self, key: u8) -> usize {
-        if is_system_control(key) {
-            // it's some other key:
-            // Test the key report to see if the key is present. Clear it if it exists.
-            // Check all positions in case the key is present more than once (which it shouldn't be)
-            for keycode in self.report.keycodes.iter_mut() {
-                if *keycode == key {
-                    *keycode = 0;
-                }
-            }
-
-            utils::sort_keycodes(self.report.keycodes.as_mut());
+        if is_system_control(key) && self.system_control_report.usage_id == key {
+            self.system_control_report.usage_id = 0;
         }
 
         1
     }
 
     fn is_key_pressed(&self, key: u8) -> bool {
-        let mut found = false;
-
-        for &keycode in self.report.keycodes.iter() {
-            if keycode == key {
-                found = true;
-                break;
-            }
-        }
-
-        found && is_system_control(key)
+        is_system_control(key) && self.system_control_report.usage_id == key
     }
 
     fn was_key_pressed(&self, key: u8) -> bool {
-        let mut found = false;
+        is_system_control(key) && self.last_system_control_report.usage_id == key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::{assert_send_gated, mock_usb_device, MockUsbBus};
+
+    #[test]
+    fn test_system_control_send_unconfigured() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_system_control(&alloc);
+
+        SystemControlKeyboard::press(&mut keyboard, SystemControlKey::Sleep as u8);
+        assert_send_gated(
+            &alloc,
+            &mut keyboard,
+            |keyboard| SystemControlKeyboard::send_report(keyboard),
+            &[&[0x82]],
+        );
+    }
 
-        for &keycode in self.last_report.keycodes.iter() {
-            if keycode == key {
-                found = true;
-                break;
-            }
+    #[test]
+    fn test_system_control_report() {
+        let alloc = UsbBusAllocator::new(MockUsbBus::new());
+        let mut keyboard = Keyboard::new_system_control(&alloc);
+        let usb_dev = mock_usb_device(&alloc);
+
+        let sleep = SystemControlKey::Sleep as u8;
+        let power_down = SystemControlKey::PowerDown as u8;
+        let wake_up = SystemControlKey::WakeUp as u8;
+
+        assert_eq!(SystemControlKeyboard::press(&mut keyboard, sleep), 1);
+        // only one usage fits in the report
+        assert_eq!(SystemControlKeyboard::press(&mut keyboard, power_down), 0);
+        assert!(SystemControlKeyboard::is_key_pressed(&keyboard, sleep));
+        SystemControlKeyboard::send_report(&mut keyboard).unwrap();
+        assert!(SystemControlKeyboard::was_key_pressed(&keyboard, sleep));
+
+        SystemControlKeyboard::release(&mut keyboard, sleep);
+        SystemControlKeyboard::send_report(&mut keyboard).unwrap();
+
+        for usage in [power_down, wake_up] {
+            SystemControlKeyboard::press(&mut keyboard, usage);
+            SystemControlKeyboard::send_report(&mut keyboard).unwrap();
+            assert_eq!(keyboard.system_control_report().usage_id, usage);
+            SystemControlKeyboard::release(&mut keyboard, usage);
         }
+        SystemControlKeyboard::end(&mut keyboard).unwrap();
 
-        found && is_system_control(key)
+        assert_eq!(
+            usb_dev.bus().in_reports(),
+            [[0x82], [0x00], [0x81], [0x83], [0x00]]
+        );
     }
 }
@@ -0,0 +1,48 @@
+use usb_device::bus::UsbBus;
+use usb_device::device::UsbDeviceBuilder;
+
+/// USB string descriptors shown by the host for the device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UsbStrings<'s> {
+    /// Manufacturer name.
+    pub manufacturer: &'s str,
+    /// Product name.
+    pub product: &'s str,
+    /// Serial number, identifying the device among others with the same VID and PID.
+    ///
+    /// Hosts use it to keep per-device settings, so it should be unique per device, e.g. derived
+    /// from the chip's signature bytes.
+    pub serial: Option<&'s str>,
+}
+
+impl<'s> UsbStrings<'s> {
+    /// Creates new [UsbStrings] without a serial number.
+    pub const fn new(manufacturer: &'s str, product: &'s str) -> Self {
+        Self {
+            manufacturer,
+            product,
+            serial: None,
+        }
+    }
+
+    /// Sets the serial number.
+    pub const fn serial(mut self, serial: &'s str) -> Self {
+        self.serial = Some(serial);
+        self
+    }
+
+    /// Sets the string descriptors on the [UsbDeviceBuilder].
+    pub fn apply<'a, B: UsbBus>(&self, builder: UsbDeviceBuilder<'a, B>) -> UsbDeviceBuilder<'a, B>
+    where
+        's: 'a,
+    {
+        let builder = builder
+            .manufacturer(self.manufacturer)
+            .product(self.product);
+
+        match self.serial {
+            Some(serial) => builder.serial_number(serial),
+            None => builder,
+        }
+    }
+}
@@ -2,13 +2,19 @@
 #![feature(abi_avr_interrupt)]
 #![cfg_attr(target_arch = "avr", feature(asm_experimental_arch))]
 
+mod error;
 mod hid_report_observer;
 mod hid_settings;
 mod keyboard;
+mod report_dump;
+#[cfg(test)]
+mod test;
 
+pub use error::*;
 pub use hid_report_observer::*;
 pub use hid_settings::*;
 pub use keyboard::*;
+pub use report_dump::*;
 
 /// Re-export of the [usb-device](https://docs.rs/usb-device/latest/usb_device/) library.
 pub use usb_device;
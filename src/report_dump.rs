@@ -0,0 +1,303 @@
+use core::fmt::{Result, Write};
+
+use crate::hid_settings::{HIDReport, HIDReportId};
+use crate::keyboard::nkro::{bitmap_keys, NKRO_CAPACITY};
+
+// Names of the keyboard usages from `0x00` (no key) to `0x65` (application).
+#[rustfmt::skip]
+static KEY_NAMES: [&str; 0x66] = [
+    "", "ErrorRollOver", "POSTFail", "ErrorUndefined", "A", "B", "C", "D", "E", "F", "G", "H",
+    "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z",
+    "1", "2", "3", "4", "5", "6", "7", "8", "9", "0", "Enter", "Escape", "Backspace", "Tab",
+    "Space", "Minus", "Equal", "LeftBracket", "RightBracket", "Backslash", "NonUSHash",
+    "Semicolon", "Quote", "Grave", "Comma", "Period", "Slash", "CapsLock", "F1", "F2", "F3",
+    "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12", "PrintScreen", "ScrollLock",
+    "Pause", "Insert", "Home", "PageUp", "Delete", "End", "PageDown", "Right", "Left", "Down",
+    "Up", "NumLock", "KpSlash", "KpAsterisk", "KpMinus", "KpPlus", "KpEnter", "Kp1", "Kp2",
+    "Kp3", "Kp4", "Kp5", "Kp6", "Kp7", "Kp8", "Kp9", "Kp0", "KpPeriod", "NonUSBackslash",
+    "Application",
+];
+
+// Names of the modifier bits, from bit 0 (left control) to bit 7 (right GUI).
+static MODIFIER_NAMES: [&str; 8] = [
+    "LeftCtrl",
+    "LeftShift",
+    "LeftAlt",
+    "LeftGUI",
+    "RightCtrl",
+    "RightShift",
+    "RightAlt",
+    "RightGUI",
+];
+
+/// Gets the name of the keycode, e.g. `A` or `LeftShift`.
+///
+/// Returns an empty name for keycodes without a name, including `0x00` (no key).
+#[cfg(feature = "key-names")]
+pub fn key_name(keycode: u8) -> &'static str {
+    let name = match keycode {
+        0xe0..=0xe7 => MODIFIER_NAMES.get((keycode - 0xe0) as usize),
+        _ => KEY_NAMES.get(keycode as usize),
+    };
+
+    name.copied().unwrap_or("")
+}
+
+/// Gets the names of the modifiers set in the modifier bitfield, from left control to right
+/// GUI.
+#[cfg(feature = "key-names")]
+pub fn modifier_names(mask: u8) -> impl Iterator<Item = &'static str> {
+    MODIFIER_NAMES
+        .iter()
+        .enumerate()
+        .filter(move |&(bit, _)| mask & (1 << bit) != 0)
+        .map(|(_, &name)| name)
+}
+
+// Writes the name of the keycode, or its hex value for keys missing from the name table.
+fn write_key<W: Write>(w: &mut W, key: u8) -> Result {
+    match KEY_NAMES.get(key as usize) {
+        Some(name) if !name.is_empty() => w.write_str(name),
+        _ => write!(w, "{key:#04x}"),
+    }
+}
+
+// Gets the modifiers and pressed keycodes of boot and NKRO keyboard reports, decoding the bitmap
+// of NKRO reports.
+//
+// Returns `None` for the other reports.
+fn keyboard_keys<'b>(
+    report: &HIDReport,
+    buf: &'b mut [u8; NKRO_CAPACITY as usize],
+) -> Option<(u8, &'b [u8])> {
+    let mut len = 0;
+    let mut push = |key| {
+        buf[len] = key;
+        len += 1;
+    };
+
+    let modifier = match report {
+        HIDReport::Keyboard(r) => {
+            r.keycodes
+                .into_iter()
+                .filter(|&key| key != 0)
+                .for_each(&mut push);
+            r.modifier
+        }
+        HIDReport::NKROKeyboard(r) => {
+            bitmap_keys(r.keycodes).for_each(&mut push);
+            r.modifier
+        }
+        _ => return None,
+    };
+
+    Some((modifier, &buf[..len]))
+}
+
+// Gets the name of the report kind.
+fn report_name(report: &HIDReport) -> &'static str {
+    match report {
+        HIDReport::Keyboard(_) => "Keyboard",
+        HIDReport::NKROKeyboard(_) => "NKROKeyboard",
+        HIDReport::MediaKeyboardReport(_) => "Media",
+        HIDReport::MouseReport(_) => "Mouse",
+        HIDReport::SystemControl(_) => "SystemControl",
+        HIDReport::Gamepad(_) => "Gamepad",
+        HIDReport::AbsoluteMouse(_) => "AbsoluteMouse",
+    }
+}
+
+/// Writes a human-readable line describing the HID report, e.g. to a UART.
+///
+/// Keyboard reports list the active modifier and key names, e.g. `Keyboard: LeftShift + A`.
+/// The keycodes of [NKROKeyboard](HIDReport::NKROKeyboard) reports are decoded from their
+/// bitmap. Other reports are written as their serialized bytes in hex.
+///
+/// Takes the same arguments as a [SendReportHook](crate::SendReportHook), the report kind is
+/// read from the report.
+pub fn dump_report<W: Write>(w: &mut W, _id: HIDReportId, report: &HIDReport) -> Result {
+    w.write_str(report_name(report))?;
+    w.write_str(":")?;
+
+    let mut keys = [0u8; NKRO_CAPACITY as usize];
+    if let Some((modifier, keys)) = keyboard_keys(report, &mut keys) {
+        let mut sep = " ";
+        for (bit, name) in MODIFIER_NAMES.iter().enumerate() {
+            if modifier & (1 << bit) != 0 {
+                w.write_str(sep)?;
+                w.write_str(name)?;
+                sep = " + ";
+            }
+        }
+
+        for &key in keys {
+            w.write_str(sep)?;
+            write_key(w, key)?;
+            sep = " + ";
+        }
+
+        if sep == " " {
+            w.write_str(" (none)")?;
+        }
+    } else {
+        let mut buf = [0u8; 64];
+        let len = report.serialize(&mut buf);
+        for byte in buf[..len].iter() {
+            write!(w, " {byte:02x}")?;
+        }
+    }
+
+    w.write_str("\n")
+}
+
+// Gets the short tag of the report kind for compact log lines.
+fn report_tag(report: &HIDReport) -> &'static str {
+    match report {
+        HIDReport::Keyboard(_) => "KB",
+        HIDReport::NKROKeyboard(_) => "NKRO",
+        HIDReport::MediaKeyboardReport(_) => "MEDIA",
+        HIDReport::MouseReport(_) => "MOUSE",
+        HIDReport::SystemControl(_) => "SYS",
+        HIDReport::Gamepad(_) => "PAD",
+        HIDReport::AbsoluteMouse(_) => "ABS",
+    }
+}
+
+/// Writes a compact single line describing the HID report, e.g. `KB mod=0x02 keys=[0x04,0x05]`.
+///
+/// Meant for logging from a [SendReportHook](crate::SendReportHook) over a UART, without `alloc`.
+/// Keyboard reports list the modifier bitfield and the pressed keycodes, decoding the bitmap of
+/// [NKROKeyboard](HIDReport::NKROKeyboard) reports. Other reports are written as their
+/// serialized bytes, e.g. `SYS data=[0x82]`.
+pub fn log_report<W: Write>(w: &mut W, _id: HIDReportId, report: &HIDReport) -> Result {
+    w.write_str(report_tag(report))?;
+
+    let mut keys = [0u8; NKRO_CAPACITY as usize];
+    let mut buf = [0u8; 64];
+    let bytes = if let Some((modifier, keys)) = keyboard_keys(report, &mut keys) {
+        write!(w, " mod={modifier:#04x} keys=[")?;
+        keys
+    } else {
+        w.write_str(" data=[")?;
+        let len = report.serialize(&mut buf);
+        &buf[..len]
+    };
+
+    for (i, byte) in bytes.iter().enumerate() {
+        let sep = if i == 0 { "" } else { "," };
+        write!(w, "{sep}{byte:#04x}")?;
+    }
+
+    w.write_str("]\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use usbd_hid::descriptor::{KeyboardReport, SystemControlReport};
+
+    use super::*;
+    use crate::keyboard::nkro::NKROReport;
+    use crate::test::HStderr;
+
+    fn dump(id: HIDReportId, report: &HIDReport) -> HStderr {
+        let mut out = HStderr::new();
+        dump_report(&mut out, id, report).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_dump_report() {
+        let shift_a = HIDReport::Keyboard(KeyboardReport {
+            modifier: 0x02,
+            reserved: 0,
+            leds: 0,
+            keycodes: [0x04, 0, 0, 0, 0, 0],
+        });
+        assert_eq!(dump(HIDReportId::Keyboard, &shift_a).as_str(), "Keyboard: LeftShift + A\n");
+
+        let keys = HIDReport::Keyboard(KeyboardReport {
+            modifier: 0x11,
+            reserved: 0,
+            leds: 0,
+            keycodes: [0x1e, 0x52, 0xe8, 0, 0, 0],
+        });
+        assert_eq!(
+            dump(HIDReportId::Keyboard, &keys).as_str(),
+            "Keyboard: LeftCtrl + RightCtrl + 1 + Up + 0xe8\n"
+        );
+
+        let empty = HIDReport::Keyboard(KeyboardReport::default());
+        assert_eq!(dump(HIDReportId::Keyboard, &empty).as_str(), "Keyboard: (none)\n");
+
+        let sleep = HIDReport::SystemControl(SystemControlReport { usage_id: 0x82 });
+        assert_eq!(dump(HIDReportId::SystemControl, &sleep).as_str(), "SystemControl: 82\n");
+    }
+
+    #[test]
+    fn test_log_report() {
+        let log = |id: HIDReportId, report: &HIDReport| {
+            let mut out = HStderr::new();
+            log_report(&mut out, id, report).unwrap();
+            out
+        };
+
+        let shift_ab = HIDReport::Keyboard(KeyboardReport {
+            modifier: 0x02,
+            reserved: 0,
+            leds: 0,
+            keycodes: [0x04, 0x05, 0, 0, 0, 0],
+        });
+        assert_eq!(
+            log(HIDReportId::Keyboard, &shift_ab).as_str(),
+            "KB mod=0x02 keys=[0x04,0x05]\n"
+        );
+
+        let empty = HIDReport::Keyboard(KeyboardReport::default());
+        assert_eq!(log(HIDReportId::Keyboard, &empty).as_str(), "KB mod=0x00 keys=[]\n");
+
+        let sleep = HIDReport::SystemControl(SystemControlReport { usage_id: 0x82 });
+        assert_eq!(log(HIDReportId::SystemControl, &sleep).as_str(), "SYS data=[0x82]\n");
+    }
+
+    #[test]
+    fn test_dump_nkro_report() {
+        // `A` and `B` in the NKRO bitmap, bits 4 and 5 of the first keycode byte, and `Up`,
+        // bit 2 of the 11th byte
+        let mut report = NKROReport {
+            modifier: 0x02,
+            ..NKROReport::default()
+        };
+        report.keycodes[0] = 0x30;
+        report.keycodes[10] = 0x04;
+        let shift_ab = HIDReport::NKROKeyboard(report);
+
+        assert_eq!(
+            dump(HIDReportId::NKROKeyboard, &shift_ab).as_str(),
+            "NKROKeyboard: LeftShift + A + B + Up\n"
+        );
+
+        let mut out = HStderr::new();
+        log_report(&mut out, HIDReportId::NKROKeyboard, &shift_ab).unwrap();
+        assert_eq!(out.as_str(), "NKRO mod=0x02 keys=[0x04,0x05,0x52]\n");
+    }
+
+    #[cfg(feature = "key-names")]
+    #[test]
+    fn test_key_names() {
+        use usbd_hid::descriptor::KeyboardUsage;
+
+        assert_eq!(key_name(KeyboardUsage::KeyboardAa as u8), "A");
+        assert_eq!(key_name(KeyboardUsage::KeyboardEnter as u8), "Enter");
+        assert_eq!(key_name(KeyboardUsage::Keypad0Insert as u8), "Kp0");
+        assert_eq!(
+            key_name(KeyboardUsage::KeyboardApplication as u8),
+            "Application"
+        );
+        assert_eq!(key_name(KeyboardUsage::KeyboardRightGUI as u8), "RightGUI");
+        assert_eq!(key_name(0x00), "");
+        assert_eq!(key_name(0xe8), "");
+
+        assert!(modifier_names(0x22).eq(["LeftShift", "RightShift"]));
+        assert_eq!(modifier_names(0x00).count(), 0);
+    }
+}
@@ -1,17 +1,359 @@
+extern crate std;
+
+use core::cell::RefCell;
+use core::fmt::Write;
+use std::collections::VecDeque;
+use std::string::String;
+use std::vec::Vec;
+
+use usb_device::bus::{PollResult, UsbBus, UsbBusAllocator};
+use usb_device::device::{UsbDevice, UsbDeviceBuilder, UsbVidPid};
+use usb_device::endpoint::{EndpointAddress, EndpointType};
+use usb_device::{Result, UsbDirection, UsbError};
+use usbd_hid::descriptor::KeyboardReport;
+
+use crate::keyboard::nkro::NKROReport;
+use crate::{Keyboard, KeyboardError, KeyboardResult};
+
+#[allow(dead_code)]
 pub struct HStderr {
     buf: [u8; 1024],
+    len: usize,
 }
 
+#[allow(dead_code)]
 impl HStderr {
     pub const fn new() -> Self {
-        Self { buf: [0u8; 1024] }
+        Self {
+            buf: [0u8; 1024],
+            len: 0,
+        }
+    }
+
+    /// Gets the text written so far.
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
     }
 }
 
 impl core::fmt::Write for HStderr {
     fn write_str(&mut self, err: &str) -> core::fmt::Result {
-        let len = core::cmp::min(err.len(), self.buf.len());
-        self.buf.copy_from_slice(err.as_bytes()[..len].as_ref());
+        let end = self.len + err.len();
+        if end > self.buf.len() {
+            return Err(core::fmt::Error);
+        }
+
+        self.buf[self.len..end].copy_from_slice(err.as_bytes());
+        self.len = end;
         Ok(())
     }
 }
+
+const MAX_ENDPOINTS: usize = 16;
+
+/// Host-side [UsbBus] implementation recording endpoint traffic for tests.
+///
+/// Packets written to non-control IN endpoints are recorded in order, and packets queued with
+/// [MockUsbBus::queue_out] are returned from reads on the matching OUT endpoint.
+pub struct MockUsbBus {
+    next_ep: [usize; 2],
+    max_packet_size: RefCell<[[u16; MAX_ENDPOINTS]; 2]>,
+    interval: [[u8; MAX_ENDPOINTS]; 2],
+    written: RefCell<Vec<(usize, Vec<u8>)>>,
+    control_in: RefCell<Vec<u8>>,
+    queued: RefCell<VecDeque<(usize, Vec<u8>)>>,
+    write_errors: RefCell<VecDeque<UsbError>>,
+}
+
+// Tests drive the bus from a single thread.
+unsafe impl Sync for MockUsbBus {}
+
+impl MockUsbBus {
+    /// Creates a new [MockUsbBus] with no allocated endpoints.
+    pub fn new() -> Self {
+        Self {
+            next_ep: [1, 1],
+            max_packet_size: RefCell::new([[0; MAX_ENDPOINTS]; 2]),
+            interval: [[0; MAX_ENDPOINTS]; 2],
+            written: RefCell::new(Vec::new()),
+            control_in: RefCell::new(Vec::new()),
+            queued: RefCell::new(VecDeque::new()),
+            write_errors: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// Gets the packets written to non-control IN endpoints, in order.
+    pub fn in_reports(&self) -> Vec<Vec<u8>> {
+        self.written
+            .borrow()
+            .iter()
+            .map(|(_, data)| data.clone())
+            .collect()
+    }
+
+    /// Gets the data written to the control endpoint, and clears it.
+    pub fn take_control_in(&self) -> Vec<u8> {
+        self.control_in.take()
+    }
+
+    /// Gets the polling interval of the IN endpoint with the provided index.
+    pub fn in_interval(&self, ep_index: usize) -> u8 {
+        self.interval[Self::dir_index(UsbDirection::In)][ep_index]
+    }
+
+    /// Gets the max packet size of the IN endpoint with the provided index.
+    pub fn in_max_packet_size(&self, ep_index: usize) -> u16 {
+        self.max_packet_size.borrow()[Self::dir_index(UsbDirection::In)][ep_index]
+    }
+
+    /// Sets the max packet size of the IN endpoint with the provided index.
+    ///
+    /// Writes larger than the max packet size fail with [UsbError::BufferOverflow].
+    pub fn set_in_max_packet_size(&self, ep_index: usize, max_packet_size: u16) {
+        self.max_packet_size.borrow_mut()[Self::dir_index(UsbDirection::In)][ep_index] =
+            max_packet_size;
+    }
+
+    /// Queues a host-to-device packet on the OUT endpoint with the provided index.
+    pub fn queue_out(&self, ep_index: usize, data: &[u8]) {
+        self.queued.borrow_mut().push_back((ep_index, data.into()));
+    }
+
+    /// Queues a SETUP packet from the host on the control endpoint.
+    ///
+    /// The packet is handled on the next [UsbDevice::poll].
+    pub fn queue_setup(&self, setup: [u8; 8]) {
+        self.queue_out(0, setup.as_ref());
+    }
+
+    /// Fails the next write to a non-control IN endpoint with the provided error.
+    pub fn fail_next_write(&self, err: UsbError) {
+        self.write_errors.borrow_mut().push_back(err);
+    }
+
+    const fn dir_index(dir: UsbDirection) -> usize {
+        match dir {
+            UsbDirection::Out => 0,
+            UsbDirection::In => 1,
+        }
+    }
+}
+
+impl UsbBus for MockUsbBus {
+    fn alloc_ep(
+        &mut self,
+        ep_dir: UsbDirection,
+        ep_addr: Option<EndpointAddress>,
+        _ep_type: EndpointType,
+        max_packet_size: u16,
+        interval: u8,
+    ) -> Result<EndpointAddress> {
+        let dir = Self::dir_index(ep_dir);
+        let index = match ep_addr {
+            Some(addr) => addr.index(),
+            None => {
+                let index = self.next_ep[dir];
+                self.next_ep[dir] += 1;
+                index
+            }
+        };
+
+        if index >= MAX_ENDPOINTS {
+            return Err(UsbError::EndpointOverflow);
+        }
+
+        self.max_packet_size.get_mut()[dir][index] = max_packet_size;
+        self.interval[dir][index] = interval;
+
+        Ok(EndpointAddress::from_parts(index, ep_dir))
+    }
+
+    fn enable(&mut self) {}
+
+    fn reset(&self) {}
+
+    fn set_device_address(&self, _addr: u8) {}
+
+    fn write(&self, ep_addr: EndpointAddress, buf: &[u8]) -> Result<usize> {
+        let index = ep_addr.index();
+
+        if index == 0 {
+            self.control_in.borrow_mut().extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+
+        if let Some(err) = self.write_errors.borrow_mut().pop_front() {
+            return Err(err);
+        }
+
+        if buf.len() > self.in_max_packet_size(index) as usize {
+            return Err(UsbError::BufferOverflow);
+        }
+
+        self.written.borrow_mut().push((index, buf.into()));
+
+        Ok(buf.len())
+    }
+
+    fn read(&self, ep_addr: EndpointAddress, buf: &mut [u8]) -> Result<usize> {
+        let index = ep_addr.index();
+        let mut queued = self.queued.borrow_mut();
+
+        match queued.iter().position(|(ep, _)| *ep == index) {
+            Some(pos) => {
+                let (_, data) = queued.remove(pos).unwrap();
+                if data.len() > buf.len() {
+                    return Err(UsbError::BufferOverflow);
+                }
+                buf[..data.len()].copy_from_slice(data.as_ref());
+                Ok(data.len())
+            }
+            None => Err(UsbError::WouldBlock),
+        }
+    }
+
+    fn set_stalled(&self, _ep_addr: EndpointAddress, _stalled: bool) {}
+
+    fn is_stalled(&self, _ep_addr: EndpointAddress) -> bool {
+        false
+    }
+
+    fn suspend(&self) {}
+
+    fn resume(&self) {}
+
+    fn poll(&self) -> PollResult {
+        if self.queued.borrow().iter().any(|(ep, _)| *ep == 0) {
+            PollResult::Data {
+                ep_out: 0,
+                ep_in_complete: 0,
+                ep_setup: 1,
+            }
+        } else {
+            PollResult::None
+        }
+    }
+}
+
+/// Builds a [UsbDevice] on the [MockUsbBus], completing endpoint allocation.
+///
+/// Must be called after all HID classes have been allocated on the bus.
+pub fn mock_usb_device(alloc: &UsbBusAllocator<MockUsbBus>) -> UsbDevice<'_, MockUsbBus> {
+    UsbDeviceBuilder::new(alloc, UsbVidPid(0x1209, 0x2303)).build()
+}
+
+/// Configures the [UsbDevice] built by [mock_usb_device], as the host does after enumeration.
+pub fn configure_usb_device(usb_dev: &mut UsbDevice<'_, MockUsbBus>) {
+    // SET_CONFIGURATION(1)
+    usb_dev
+        .bus()
+        .queue_setup([0x00, 0x09, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    usb_dev.poll(&mut []);
+}
+
+// Gets the bytes of the keyboard report as sent on the IN endpoint.
+fn keyboard_report_bytes(report: &KeyboardReport) -> Vec<u8> {
+    let mut bytes = Vec::from([report.modifier, report.reserved]);
+    bytes.extend_from_slice(report.keycodes.as_ref());
+    bytes
+}
+
+// Gets the bytes of the NKRO report as sent on the IN endpoint.
+fn nkro_report_bytes(report: &NKROReport) -> Vec<u8> {
+    let mut bytes = Vec::from([report.modifier, report.reserved]);
+    bytes.extend_from_slice(report.keycodes.as_ref());
+    bytes
+}
+
+/// Asserts that the keyboard reports were pushed to the [MockUsbBus] in the expected order.
+///
+/// On mismatch, panics with both sequences side by side, marking the differing reports.
+pub fn assert_report_sequence(bus: &MockUsbBus, expected: &[KeyboardReport]) {
+    assert_bytes_sequence(bus, expected.iter().map(keyboard_report_bytes).collect());
+}
+
+/// Asserts that the NKRO reports were pushed to the [MockUsbBus] in the expected order, see
+/// [assert_report_sequence].
+pub fn assert_nkro_report_sequence(bus: &MockUsbBus, expected: &[NKROReport]) {
+    assert_bytes_sequence(bus, expected.iter().map(nkro_report_bytes).collect());
+}
+
+fn assert_bytes_sequence(bus: &MockUsbBus, expected: Vec<Vec<u8>>) {
+    let actual = bus.in_reports();
+
+    if actual == expected {
+        return;
+    }
+
+    let mut diff = String::new();
+    for i in 0..core::cmp::max(actual.len(), expected.len()) {
+        let (exp, act) = (expected.get(i), actual.get(i));
+        let mark = if exp == act { ' ' } else { '!' };
+
+        writeln!(diff, "{mark} {i}: expected {exp:02x?}, actual {act:02x?}").unwrap();
+    }
+
+    panic!("report sequence mismatch:\n{diff}");
+}
+
+/// Asserts that `send` is refused while the USB device is unconfigured, and pushes the expected
+/// reports once the host configured the device.
+///
+/// The keys to send must be pressed before calling. Builds the [UsbDevice], so it must be called
+/// after all HID classes have been allocated on the bus.
+pub fn assert_send_gated<'a, F>(
+    alloc: &'a UsbBusAllocator<MockUsbBus>,
+    keyboard: &mut Keyboard<'a, MockUsbBus>,
+    mut send: F,
+    expected: &[&[u8]],
+) where
+    F: FnMut(&mut Keyboard<'a, MockUsbBus>) -> KeyboardResult<()>,
+{
+    let mut usb_dev = mock_usb_device(alloc);
+
+    keyboard.update_device_state(&usb_dev);
+    assert!(matches!(send(keyboard), Err(KeyboardError::NotConfigured)));
+    assert!(usb_dev.bus().in_reports().is_empty());
+
+    // the refused report is sent once the device is configured
+    configure_usb_device(&mut usb_dev);
+    keyboard.update_device_state(&usb_dev);
+    send(keyboard).unwrap();
+    assert_eq!(usb_dev.bus().in_reports(), expected);
+}
+
+#[cfg(feature = "defmt")]
+std::thread_local! {
+    static DEFMT_LOGGED: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+}
+
+/// `defmt` logger for the test binary, counting the bytes logged by each test thread.
+///
+/// The firmware provides its own logger, e.g. `defmt-rtt`. Without one, the `defmt` feature
+/// does not link.
+#[cfg(feature = "defmt")]
+#[defmt::global_logger]
+struct TestLogger;
+
+#[cfg(feature = "defmt")]
+unsafe impl defmt::Logger for TestLogger {
+    fn acquire() {}
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {}
+
+    unsafe fn write(bytes: &[u8]) {
+        DEFMT_LOGGED.with(|logged| logged.set(logged.get() + bytes.len()));
+    }
+}
+
+// The firmware linker script provides a default timestamp, missing from the test binary.
+#[cfg(feature = "defmt")]
+defmt::timestamp!("{=u8}", 0);
+
+/// Gets the number of bytes logged with `defmt` by the current test.
+#[cfg(feature = "defmt")]
+pub fn defmt_logged() -> usize {
+    DEFMT_LOGGED.with(|logged| logged.get())
+}